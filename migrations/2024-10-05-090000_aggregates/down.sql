@@ -0,0 +1,5 @@
+DROP MATERIALIZED VIEW industry_aggregates;
+DROP MATERIALIZED VIEW sector_aggregates;
+DROP VIEW industry_market_capitalizations;
+DROP VIEW sector_market_capitalizations;
+DROP VIEW company_period_fundamentals;
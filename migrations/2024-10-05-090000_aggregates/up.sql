@@ -0,0 +1,135 @@
+-- A single row per company and period in every table, the latest one, so companies
+-- with duplicated ratios aren't counted twice
+CREATE VIEW company_period_fundamentals AS
+WITH figures AS (
+    SELECT DISTINCT ON (company_id, period_id) company_id, period_id, reported_currency_id, market_capitalization
+    FROM non_gaap_figures
+    WHERE NOT is_ttm
+    ORDER BY company_id, period_id, date DESC, id DESC
+), margins AS (
+    SELECT DISTINCT ON (company_id, period_id) company_id, period_id, gross_margin, net_income_margin, free_cash_flow_margin
+    FROM margin_ratios
+    WHERE NOT is_ttm
+    ORDER BY company_id, period_id, date DESC, id DESC
+), growths AS (
+    SELECT DISTINCT ON (company_id, period_id) company_id, period_id, revenue_growth, net_income_growth, earnings_per_share_growth
+    FROM company_growth
+    WHERE NOT is_ttm
+    ORDER BY company_id, period_id, date DESC, id DESC
+), prices AS (
+    SELECT DISTINCT ON (company_id, period_id) company_id, period_id, price_to_earnings, price_to_book_value, price_to_sales
+    FROM price_to_ratios
+    WHERE NOT is_ttm
+    ORDER BY company_id, period_id, date DESC, id DESC
+), enterprise_values AS (
+    SELECT DISTINCT ON (company_id, period_id) company_id, period_id, enterprise_value_multiple
+    FROM enterprise_value_ratios
+    WHERE NOT is_ttm
+    ORDER BY company_id, period_id, date DESC, id DESC
+)
+SELECT
+    c.id AS company_id,
+    c.sector_id,
+    c.industry_id,
+    p.id AS period_id,
+    p.year,
+    p.period,
+    n.reported_currency_id,
+    n.market_capitalization,
+    m.gross_margin,
+    m.net_income_margin,
+    m.free_cash_flow_margin,
+    g.revenue_growth,
+    g.net_income_growth,
+    g.earnings_per_share_growth,
+    pr.price_to_earnings,
+    pr.price_to_book_value,
+    pr.price_to_sales,
+    ev.enterprise_value_multiple
+FROM figures n
+JOIN companies c ON c.id = n.company_id
+JOIN periods p ON p.id = n.period_id
+LEFT JOIN margins m ON m.company_id = n.company_id AND m.period_id = n.period_id
+LEFT JOIN growths g ON g.company_id = n.company_id AND g.period_id = n.period_id
+LEFT JOIN prices pr ON pr.company_id = n.company_id AND pr.period_id = n.period_id
+LEFT JOIN enterprise_values ev ON ev.company_id = n.company_id AND ev.period_id = n.period_id;
+
+-- Market capitalizations are reported in the currency of each company, they're only
+-- added up within a currency, keyed by its code
+CREATE VIEW sector_market_capitalizations AS
+SELECT f.sector_id, f.period_id, jsonb_object_agg(f.code, f.total) AS market_capitalization
+FROM (
+    SELECT cpf.sector_id, cpf.period_id, cu.alphabetic_code AS code, SUM(cpf.market_capitalization) AS total
+    FROM company_period_fundamentals cpf
+    JOIN currencies cu ON cu.id = cpf.reported_currency_id
+    WHERE cpf.sector_id IS NOT NULL
+    GROUP BY cpf.sector_id, cpf.period_id, cu.alphabetic_code
+) f
+GROUP BY f.sector_id, f.period_id;
+
+CREATE VIEW industry_market_capitalizations AS
+SELECT f.industry_id, f.period_id, jsonb_object_agg(f.code, f.total) AS market_capitalization
+FROM (
+    SELECT cpf.industry_id, cpf.period_id, cu.alphabetic_code AS code, SUM(cpf.market_capitalization) AS total
+    FROM company_period_fundamentals cpf
+    JOIN currencies cu ON cu.id = cpf.reported_currency_id
+    WHERE cpf.industry_id IS NOT NULL
+    GROUP BY cpf.industry_id, cpf.period_id, cu.alphabetic_code
+) f
+GROUP BY f.industry_id, f.period_id;
+
+CREATE MATERIALIZED VIEW sector_aggregates AS
+SELECT
+    sector_id,
+    period_id,
+    year,
+    period,
+    COUNT(DISTINCT company_id) AS companies,
+    (
+        SELECT mc.market_capitalization
+        FROM sector_market_capitalizations mc
+        WHERE mc.sector_id = cpf.sector_id AND mc.period_id = cpf.period_id
+    ) AS market_capitalization_by_currency,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY gross_margin) AS median_gross_margin,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY net_income_margin) AS median_net_income_margin,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY free_cash_flow_margin) AS median_free_cash_flow_margin,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY revenue_growth) AS median_revenue_growth,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY net_income_growth) AS median_net_income_growth,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY earnings_per_share_growth) AS median_earnings_per_share_growth,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY price_to_earnings) AS median_price_to_earnings,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY price_to_book_value) AS median_price_to_book_value,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY price_to_sales) AS median_price_to_sales,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY enterprise_value_multiple) AS median_enterprise_value_multiple
+FROM company_period_fundamentals cpf
+WHERE sector_id IS NOT NULL
+GROUP BY sector_id, period_id, year, period;
+
+CREATE UNIQUE INDEX idx_sector_aggregates ON sector_aggregates(sector_id, period_id);
+
+CREATE MATERIALIZED VIEW industry_aggregates AS
+SELECT
+    industry_id,
+    period_id,
+    year,
+    period,
+    COUNT(DISTINCT company_id) AS companies,
+    (
+        SELECT mc.market_capitalization
+        FROM industry_market_capitalizations mc
+        WHERE mc.industry_id = cpf.industry_id AND mc.period_id = cpf.period_id
+    ) AS market_capitalization_by_currency,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY gross_margin) AS median_gross_margin,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY net_income_margin) AS median_net_income_margin,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY free_cash_flow_margin) AS median_free_cash_flow_margin,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY revenue_growth) AS median_revenue_growth,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY net_income_growth) AS median_net_income_growth,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY earnings_per_share_growth) AS median_earnings_per_share_growth,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY price_to_earnings) AS median_price_to_earnings,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY price_to_book_value) AS median_price_to_book_value,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY price_to_sales) AS median_price_to_sales,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY enterprise_value_multiple) AS median_enterprise_value_multiple
+FROM company_period_fundamentals cpf
+WHERE industry_id IS NOT NULL
+GROUP BY industry_id, period_id, year, period;
+
+CREATE UNIQUE INDEX idx_industry_aggregates ON industry_aggregates(industry_id, period_id);
@@ -0,0 +1 @@
+DROP TABLE dcf_parameters;
@@ -0,0 +1,12 @@
+CREATE TABLE dcf_parameters (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    company_id BIGINT NOT NULL REFERENCES companies(id) ON DELETE CASCADE,
+    discount_rate double precision NOT NULL,
+    terminal_growth double precision NOT NULL,
+    growth_rate double precision,
+    projection_years INT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (user_id, company_id)
+);
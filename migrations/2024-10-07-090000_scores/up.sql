@@ -0,0 +1,17 @@
+CREATE TABLE company_scores (
+    id BIGSERIAL PRIMARY KEY,
+    company_id BIGINT NOT NULL REFERENCES companies(id) ON DELETE CASCADE,
+    period_id BIGINT NOT NULL REFERENCES periods(id) ON DELETE CASCADE,
+    date DATE NOT NULL,
+    piotroski_f_score INT,
+    piotroski_components JSONB,
+    altman_z_score double precision,
+    altman_components JSONB,
+    beneish_m_score double precision,
+    beneish_components JSONB,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (company_id, period_id)
+);
+
+CREATE INDEX idx_company_scores_period ON company_scores(period_id);
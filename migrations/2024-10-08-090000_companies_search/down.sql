@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS companies_search_vector_idx;
+ALTER TABLE companies DROP COLUMN IF EXISTS search_vector;
@@ -0,0 +1,11 @@
+-- The simple configuration keeps tickers, identifiers and names untouched so
+-- prefix queries behave the same on every field
+ALTER TABLE companies ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', coalesce(ticker, '')), 'A') ||
+    setweight(to_tsvector('simple', coalesce(name, '')), 'A') ||
+    setweight(to_tsvector('simple', coalesce(isin, '') || ' ' || coalesce(cusip, '')), 'B') ||
+    setweight(to_tsvector('simple', coalesce(ceo, '')), 'C') ||
+    setweight(to_tsvector('simple', coalesce(description, '')), 'D')
+) STORED;
+
+CREATE INDEX companies_search_vector_idx ON companies USING GIN (search_vector);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS companies_figi_idx;
+DROP INDEX IF EXISTS companies_cik_idx;
+DROP INDEX IF EXISTS companies_cusip_idx;
+DROP INDEX IF EXISTS companies_isin_idx;
+ALTER TABLE companies DROP COLUMN IF EXISTS figi;
@@ -0,0 +1,6 @@
+ALTER TABLE companies ADD COLUMN figi VARCHAR(12);
+
+CREATE INDEX companies_isin_idx ON companies (isin);
+CREATE INDEX companies_cusip_idx ON companies (cusip);
+CREATE INDEX companies_cik_idx ON companies (cik);
+CREATE INDEX companies_figi_idx ON companies (figi);
@@ -0,0 +1,15 @@
+CREATE TABLE statement_findings (
+    id BIGSERIAL PRIMARY KEY,
+    company_id BIGINT NOT NULL REFERENCES companies(id) ON DELETE CASCADE,
+    period_id BIGINT REFERENCES periods(id) ON DELETE SET NULL,
+    date DATE,
+    rule VARCHAR(64) NOT NULL,
+    source_table VARCHAR(64) NOT NULL,
+    message TEXT NOT NULL,
+    expected DOUBLE PRECISION,
+    actual DOUBLE PRECISION,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_statement_findings_company ON statement_findings(company_id);
+CREATE INDEX idx_statement_findings_rule ON statement_findings(rule);
@@ -0,0 +1,31 @@
+CREATE TABLE corporate_actions (
+    id BIGSERIAL PRIMARY KEY,
+    company_id BIGINT NOT NULL REFERENCES companies(id) ON DELETE CASCADE,
+    kind VARCHAR(30) NOT NULL,
+    ex_date DATE NOT NULL,
+    announcement_date DATE,
+    -- New shares received for `ratio_from` old ones, 4 for 1 in a 4:1 split
+    ratio_to DOUBLE PRECISION,
+    ratio_from DOUBLE PRECISION,
+    old_ticker VARCHAR(255),
+    new_ticker VARCHAR(255),
+    -- Spun-off company or acquirer in a merger
+    related_company_id BIGINT REFERENCES companies(id) ON DELETE SET NULL,
+    cash_amount NUMERIC,
+    currency_id BIGINT REFERENCES currencies(id) ON DELETE SET NULL,
+    description TEXT,
+    -- When lots and tickers were updated with the action
+    applied_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT corporate_actions_kind_check CHECK (
+        kind IN ('split', 'reverse_split', 'spin_off', 'ticker_change', 'merger', 'special_dividend')
+    ),
+    CONSTRAINT corporate_actions_ratio_check CHECK (
+        (ratio_to IS NULL AND ratio_from IS NULL) OR (ratio_to > 0 AND ratio_from > 0)
+    ),
+    UNIQUE (company_id, kind, ex_date)
+);
+
+CREATE INDEX idx_corporate_actions_company_ex_date ON corporate_actions(company_id, ex_date);
+CREATE INDEX idx_corporate_actions_pending ON corporate_actions(ex_date) WHERE applied_at IS NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE transactions DROP COLUMN IF EXISTS dividend_id;
+DROP TABLE IF EXISTS dividends;
@@ -0,0 +1,24 @@
+CREATE TABLE dividends (
+    id BIGSERIAL PRIMARY KEY,
+    company_id BIGINT NOT NULL REFERENCES companies(id) ON DELETE CASCADE,
+    declaration_date DATE,
+    ex_date DATE NOT NULL,
+    record_date DATE,
+    payment_date DATE,
+    -- Per share, in `currency_id`
+    amount NUMERIC NOT NULL CHECK (amount > 0),
+    currency_id BIGINT NOT NULL REFERENCES currencies(id),
+    frequency VARCHAR(20) NOT NULL DEFAULT 'quarterly',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT dividends_frequency_check CHECK (
+        frequency IN ('monthly', 'quarterly', 'semiannual', 'annual', 'irregular', 'special')
+    ),
+    UNIQUE (company_id, ex_date, frequency)
+);
+
+CREATE INDEX idx_dividends_ex_date ON dividends(ex_date);
+CREATE INDEX idx_dividends_payment_date ON dividends(payment_date);
+
+ALTER TABLE transactions ADD COLUMN dividend_id BIGINT REFERENCES dividends(id) ON DELETE SET NULL;
+CREATE INDEX idx_transactions_dividend ON transactions(dividend_id);
@@ -0,0 +1,29 @@
+ALTER TABLE exchange_rates
+    DROP CONSTRAINT IF EXISTS exchange_rates_positive_rate,
+    ADD COLUMN rate VARCHAR(255),
+    ADD COLUMN precision INT,
+    ADD COLUMN scale INT;
+
+UPDATE exchange_rates
+SET rate = conversion_rate::TEXT,
+    precision = LENGTH(REGEXP_REPLACE(conversion_rate::TEXT, '[^0-9]', '', 'g')),
+    scale = SCALE(conversion_rate);
+
+ALTER TABLE exchange_rates DROP COLUMN conversion_rate;
+ALTER TABLE exchange_rates RENAME COLUMN rate TO conversion_rate;
+
+ALTER TABLE exchange_rates
+    ALTER COLUMN conversion_rate SET NOT NULL,
+    ALTER COLUMN precision SET NOT NULL,
+    ALTER COLUMN scale SET NOT NULL;
+
+INSERT INTO exchange_rates (id, base_id, target_id, date, conversion_rate, precision, scale, source, created_at, updated_at)
+SELECT id, base_id, target_id, date, conversion_rate, precision, scale, source, created_at, updated_at
+FROM exchange_rates_rejected;
+
+UPDATE transactions
+SET exchange_rate_id = rejected.id
+FROM exchange_rates_rejected AS rejected
+WHERE transactions.id = ANY(rejected.transaction_ids);
+
+DROP TABLE exchange_rates_rejected;
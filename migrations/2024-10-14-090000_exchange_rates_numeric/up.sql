@@ -0,0 +1,59 @@
+ALTER TABLE exchange_rates ADD COLUMN rate NUMERIC;
+
+-- A comma is a thousands separator next to a dot, "1,234.56", and the decimal
+-- separator on its own, "0,92"
+UPDATE exchange_rates
+SET rate = normalized::NUMERIC
+FROM (
+    SELECT id AS rate_id,
+        CASE
+            WHEN TRIM(conversion_rate) LIKE '%.%' THEN REPLACE(TRIM(conversion_rate), ',', '')
+            ELSE REPLACE(TRIM(conversion_rate), ',', '.')
+        END AS normalized
+    FROM exchange_rates
+) AS rates
+WHERE rates.rate_id = exchange_rates.id
+    AND normalized ~ '^[0-9]*\.?[0-9]+([eE][-+]?[0-9]+)?$';
+
+-- Rates that don't hold a positive number were never usable. They are kept aside with
+-- the transactions they were linked to, which keep their amounts and only lose the
+-- link, the foreign key would delete them otherwise
+CREATE TABLE exchange_rates_rejected AS
+SELECT exchange_rates.id,
+    exchange_rates.base_id,
+    exchange_rates.target_id,
+    exchange_rates.date,
+    exchange_rates.conversion_rate,
+    exchange_rates.precision,
+    exchange_rates.scale,
+    exchange_rates.source,
+    exchange_rates.created_at,
+    exchange_rates.updated_at,
+    ARRAY(
+        SELECT transactions.id
+        FROM transactions
+        WHERE transactions.exchange_rate_id = exchange_rates.id
+        ORDER BY transactions.id
+    ) AS transaction_ids
+FROM exchange_rates
+WHERE rate IS NULL OR rate <= 0;
+
+ALTER TABLE exchange_rates_rejected ADD PRIMARY KEY (id);
+
+UPDATE transactions
+SET exchange_rate_id = NULL
+WHERE exchange_rate_id IN (SELECT id FROM exchange_rates_rejected);
+
+DELETE FROM exchange_rates WHERE id IN (SELECT id FROM exchange_rates_rejected);
+
+-- The scale of a numeric already keeps the digits the rate was published with
+ALTER TABLE exchange_rates
+    DROP COLUMN conversion_rate,
+    DROP COLUMN precision,
+    DROP COLUMN scale;
+
+ALTER TABLE exchange_rates RENAME COLUMN rate TO conversion_rate;
+
+ALTER TABLE exchange_rates
+    ALTER COLUMN conversion_rate SET NOT NULL,
+    ADD CONSTRAINT exchange_rates_positive_rate CHECK (conversion_rate > 0);
@@ -0,0 +1,5 @@
+DROP INDEX currencies_countries_m2m_primary_idx;
+ALTER TABLE currencies_countries_m2m DROP COLUMN is_primary;
+DROP INDEX currencies_countries_m2m_pair_idx;
+DROP INDEX currencies_numeric_code_idx;
+DROP INDEX currencies_alphabetic_code_idx;
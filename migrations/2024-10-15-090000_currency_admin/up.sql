@@ -0,0 +1,109 @@
+-- Codes are unique. Currencies sharing an alphabetic code are merged into the first
+-- one, whatever points to the others points to it afterwards
+UPDATE currencies
+SET alphabetic_code = UPPER(TRIM(alphabetic_code)),
+    numeric_code = TRIM(numeric_code);
+
+CREATE TEMPORARY TABLE currency_duplicates AS
+SELECT duplicate.id AS duplicate_id, kept.id AS kept_id
+FROM currencies AS duplicate
+JOIN (
+    SELECT alphabetic_code, MIN(id) AS id FROM currencies GROUP BY alphabetic_code
+) AS kept ON kept.alphabetic_code = duplicate.alphabetic_code
+WHERE duplicate.id <> kept.id;
+
+-- Rates of a merged pair already published by the same source collapse into the
+-- first one, which takes over their transactions
+CREATE TEMPORARY TABLE rate_duplicates AS
+SELECT id AS duplicate_id, kept_id
+FROM (
+    SELECT rates.id,
+        FIRST_VALUE(rates.id) OVER (
+            PARTITION BY COALESCE(base.kept_id, rates.base_id),
+                COALESCE(target.kept_id, rates.target_id),
+                rates.date,
+                rates.source
+            ORDER BY rates.id
+        ) AS kept_id
+    FROM exchange_rates AS rates
+    LEFT JOIN currency_duplicates AS base ON base.duplicate_id = rates.base_id
+    LEFT JOIN currency_duplicates AS target ON target.duplicate_id = rates.target_id
+    WHERE rates.source IS NOT NULL
+) AS ranked
+WHERE id <> kept_id;
+
+UPDATE transactions
+SET exchange_rate_id = rate_duplicates.kept_id
+FROM rate_duplicates
+WHERE transactions.exchange_rate_id = rate_duplicates.duplicate_id;
+
+DELETE FROM exchange_rates WHERE id IN (SELECT duplicate_id FROM rate_duplicates);
+
+DO $$
+DECLARE
+    reference RECORD;
+BEGIN
+    FOR reference IN
+        SELECT class.relname AS table_name, attribute.attname AS column_name
+        FROM pg_constraint AS constraint_
+        JOIN pg_class AS class ON class.oid = constraint_.conrelid
+        JOIN pg_attribute AS attribute
+            ON attribute.attrelid = constraint_.conrelid
+            AND attribute.attnum = ANY(constraint_.conkey)
+        WHERE constraint_.contype = 'f'
+            AND constraint_.confrelid = 'currencies'::regclass
+    LOOP
+        EXECUTE format(
+            'UPDATE %1$I SET %2$I = currency_duplicates.kept_id FROM currency_duplicates '
+            'WHERE %1$I.%2$I = currency_duplicates.duplicate_id',
+            reference.table_name,
+            reference.column_name
+        );
+    END LOOP;
+END $$;
+
+DELETE FROM currencies WHERE id IN (SELECT duplicate_id FROM currency_duplicates);
+
+-- Different currencies sharing a numeric code can't be merged, staff has to fix them
+DO $$
+DECLARE
+    conflicts TEXT;
+BEGIN
+    SELECT STRING_AGG(codes, '; ') INTO conflicts
+    FROM (
+        SELECT numeric_code || ': ' || STRING_AGG(alphabetic_code, ', ' ORDER BY id) AS codes
+        FROM currencies
+        GROUP BY numeric_code
+        HAVING COUNT(*) > 1
+    ) AS shared;
+    IF conflicts IS NOT NULL THEN
+        RAISE EXCEPTION 'Currencies share numeric codes, fix them before migrating: %', conflicts;
+    END IF;
+END $$;
+
+CREATE UNIQUE INDEX currencies_alphabetic_code_idx ON currencies (alphabetic_code);
+CREATE UNIQUE INDEX currencies_numeric_code_idx ON currencies (numeric_code);
+
+-- A currency is linked once to a country
+DELETE FROM currencies_countries_m2m AS duplicate
+USING currencies_countries_m2m AS kept
+WHERE duplicate.currency_id = kept.currency_id
+    AND duplicate.country_id = kept.country_id
+    AND duplicate.id > kept.id;
+
+CREATE UNIQUE INDEX currencies_countries_m2m_pair_idx
+    ON currencies_countries_m2m (currency_id, country_id);
+
+-- The currency used by default for a country, like the one of a new profile
+ALTER TABLE currencies_countries_m2m ADD COLUMN is_primary BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The first link of each country, the one lookups used to pick, becomes its primary
+UPDATE currencies_countries_m2m
+SET is_primary = TRUE
+WHERE id IN (
+    SELECT MIN(id) FROM currencies_countries_m2m GROUP BY country_id
+);
+
+CREATE UNIQUE INDEX currencies_countries_m2m_primary_idx
+    ON currencies_countries_m2m (country_id)
+    WHERE is_primary;
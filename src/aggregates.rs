@@ -0,0 +1,181 @@
+use crate::{
+    db::views::{industry_aggregates, sector_aggregates},
+    server::{AppError, AppResult},
+    AppState,
+};
+
+use axum::{
+    extract::{Path, Query},
+    routing::get,
+    Json, Router,
+};
+use diesel::prelude::*;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{self, IntoParams, OpenApi, ToResponse, ToSchema};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(list_sector_aggregates, list_industry_aggregates),
+    components(schemas(SectorAggregate, IndustryAggregate),
+    responses(SectorAggregate, IndustryAggregate)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/sectors/:id/aggregates", get(list_sector_aggregates))
+        .route("/industries/:id/aggregates", get(list_industry_aggregates))
+        .with_state(state)
+}
+
+/// Recomputes the sector and industry materialized views from the ratio tables
+pub fn refresh_aggregates(conn: &mut PgConnection) -> QueryResult<()> {
+    diesel::sql_query("REFRESH MATERIALIZED VIEW CONCURRENTLY sector_aggregates").execute(conn)?;
+    diesel::sql_query("REFRESH MATERIALIZED VIEW CONCURRENTLY industry_aggregates")
+        .execute(conn)?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+struct AggregatesQuery {
+    from_year: Option<i32>,
+    to_year: Option<i32>,
+    period: Option<i32>,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Selectable, ToSchema, ToResponse)]
+#[diesel(table_name = sector_aggregates)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct SectorAggregate {
+    sector_id: i64,
+    year: i32,
+    period: i32,
+    companies: i64,
+    /// Market capitalizations added up by the currency they're reported in, keyed by
+    /// its code
+    #[schema(value_type = Object)]
+    market_capitalization_by_currency: Option<Value>,
+    median_gross_margin: Option<f64>,
+    median_net_income_margin: Option<f64>,
+    median_free_cash_flow_margin: Option<f64>,
+    median_revenue_growth: Option<f64>,
+    median_net_income_growth: Option<f64>,
+    median_earnings_per_share_growth: Option<f64>,
+    median_price_to_earnings: Option<f64>,
+    median_price_to_book_value: Option<f64>,
+    median_price_to_sales: Option<f64>,
+    median_enterprise_value_multiple: Option<f64>,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Selectable, ToSchema, ToResponse)]
+#[diesel(table_name = industry_aggregates)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct IndustryAggregate {
+    industry_id: i64,
+    year: i32,
+    period: i32,
+    companies: i64,
+    /// Market capitalizations added up by the currency they're reported in, keyed by
+    /// its code
+    #[schema(value_type = Object)]
+    market_capitalization_by_currency: Option<Value>,
+    median_gross_margin: Option<f64>,
+    median_net_income_margin: Option<f64>,
+    median_free_cash_flow_margin: Option<f64>,
+    median_revenue_growth: Option<f64>,
+    median_net_income_growth: Option<f64>,
+    median_earnings_per_share_growth: Option<f64>,
+    median_price_to_earnings: Option<f64>,
+    median_price_to_book_value: Option<f64>,
+    median_price_to_sales: Option<f64>,
+    median_enterprise_value_multiple: Option<f64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "sectors/{id}/aggregates",
+    params(("id" = i64, Path, description = "Sector ID"), AggregatesQuery),
+    responses(
+            (status = 200, body = Vec<SectorAggregate>, description = "Aggregated fundamentals of a sector per period"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn list_sector_aggregates(
+    Path(id): Path<i64>,
+    Query(query_params): Query<AggregatesQuery>,
+    state: AppState,
+) -> AppResult<Vec<SectorAggregate>> {
+    Ok(Json(
+        state
+            .db_write()
+            .await?
+            .interact(move |conn| {
+                let mut query = sector_aggregates::table
+                    .filter(sector_aggregates::sector_id.eq(id))
+                    .into_boxed();
+                if let Some(from_year) = query_params.from_year {
+                    query = query.filter(sector_aggregates::year.ge(from_year));
+                }
+                if let Some(to_year) = query_params.to_year {
+                    query = query.filter(sector_aggregates::year.le(to_year));
+                }
+                if let Some(period) = query_params.period {
+                    query = query.filter(sector_aggregates::period.eq(period));
+                }
+                query
+                    .order((sector_aggregates::year, sector_aggregates::period))
+                    .select(SectorAggregate::as_select())
+                    .load::<SectorAggregate>(conn)
+                    .map_err(AppError::DatabaseQueryError)
+            })
+            .await
+            .map_err(AppError::DatabaseConnectionInteractError)??,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "industries/{id}/aggregates",
+    params(("id" = i64, Path, description = "Industry ID"), AggregatesQuery),
+    responses(
+            (status = 200, body = Vec<IndustryAggregate>, description = "Aggregated fundamentals of an industry per period"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn list_industry_aggregates(
+    Path(id): Path<i64>,
+    Query(query_params): Query<AggregatesQuery>,
+    state: AppState,
+) -> AppResult<Vec<IndustryAggregate>> {
+    Ok(Json(
+        state
+            .db_write()
+            .await?
+            .interact(move |conn| {
+                let mut query = industry_aggregates::table
+                    .filter(industry_aggregates::industry_id.eq(id))
+                    .into_boxed();
+                if let Some(from_year) = query_params.from_year {
+                    query = query.filter(industry_aggregates::year.ge(from_year));
+                }
+                if let Some(to_year) = query_params.to_year {
+                    query = query.filter(industry_aggregates::year.le(to_year));
+                }
+                if let Some(period) = query_params.period {
+                    query = query.filter(industry_aggregates::period.eq(period));
+                }
+                query
+                    .order((industry_aggregates::year, industry_aggregates::period))
+                    .select(IndustryAggregate::as_select())
+                    .load::<IndustryAggregate>(conn)
+                    .map_err(AppError::DatabaseQueryError)
+            })
+            .await
+            .map_err(AppError::DatabaseConnectionInteractError)??,
+    ))
+}
@@ -0,0 +1,88 @@
+//! One-off tasks run from the command line instead of the HTTP server, as in
+//! `elerem ecb historical`, `elerem fred DEXMXUS DEXUSEU` or `elerem iso4217`. With
+//! `--file` they read a local file, like the ones in `fixtures/`, instead of the
+//! network or the data bundled with the server
+use std::path::PathBuf;
+
+use crate::{
+    currencies::{
+        ingest_ecb, ingest_fred, ingest_iso4217, EcbFeed, EcbInput, FredInput, FredSeries,
+    },
+    server::{AppError, AppState, BulkReport},
+};
+
+const USAGE: &str = "usage: elerem ecb [daily|90d|historical] | elerem ecb --file <path> | \
+elerem fred [SERIES[=BASE/TARGET]...] | elerem fred --file <SERIES[=BASE/TARGET]> <path> | \
+elerem iso4217 [--file <path>]";
+
+fn usage_error() -> AppError {
+    AppError::ValidationError(USAGE.to_string())
+}
+
+fn log_report(name: &str, report: &BulkReport) {
+    info!(
+        "{name}: {} inserted, {} updated, {} rejected",
+        report.inserted, report.updated, report.rejected
+    );
+    for error in &report.errors {
+        warn!("{name}: row {} rejected, {}", error.row, error.reason);
+    }
+}
+
+fn ecb_input(args: &[String]) -> Result<EcbInput, AppError> {
+    match args {
+        [] => Ok(EcbInput::Feed(EcbFeed::Daily)),
+        [flag, path] if flag == "--file" => Ok(EcbInput::File(PathBuf::from(path))),
+        [feed] => EcbFeed::parse(feed)
+            .map(EcbInput::Feed)
+            .ok_or_else(usage_error),
+        _ => Err(usage_error()),
+    }
+}
+
+fn fred_input(args: &[String]) -> Result<FredInput, AppError> {
+    match args {
+        [flag, series, path] if flag == "--file" => Ok(FredInput::File {
+            series: FredSeries::parse(series)?,
+            path: PathBuf::from(path),
+        }),
+        [] => Ok(FredInput::Api {
+            series: FredSeries::configured()?,
+            since: None,
+        }),
+        series if series.iter().all(|series| !series.starts_with("--")) => Ok(FredInput::Api {
+            series: series
+                .iter()
+                .map(|series| FredSeries::parse(series))
+                .collect::<Result<_, _>>()?,
+            since: None,
+        }),
+        _ => Err(usage_error()),
+    }
+}
+
+pub async fn run(state: &AppState, command: &str, args: &[String]) -> Result<(), AppError> {
+    match command {
+        "ecb" => {
+            let report = ingest_ecb(state, ecb_input(args)?).await?;
+            log_report("ECB rates", &report);
+            Ok(())
+        }
+        "fred" => {
+            let report = ingest_fred(state, fred_input(args)?).await?;
+            log_report("FRED rates", &report);
+            Ok(())
+        }
+        "iso4217" => {
+            let path = match args {
+                [] => None,
+                [flag, path] if flag == "--file" => Some(PathBuf::from(path)),
+                _ => return Err(usage_error()),
+            };
+            let report = ingest_iso4217(state, path).await?;
+            log_report("ISO 4217 currencies", &report);
+            Ok(())
+        }
+        _ => Err(usage_error()),
+    }
+}
@@ -0,0 +1,280 @@
+use axum::{body::Bytes, extract::Path, http::HeaderMap, Extension, Json};
+use chrono::NaiveDate;
+use diesel::{dsl::exists, prelude::*, select};
+use serde::{Deserialize, Serialize};
+use utoipa::{self, ToSchema};
+
+use super::{
+    handlers::Company,
+    resolver::{is_valid_cusip, is_valid_figi, is_valid_isin},
+};
+use crate::{
+    db::schema::{assets_details, companies},
+    server::{parse_rows, AppError, AppResult, BulkFormat, BulkReport, JWTUserRequest},
+    AppState,
+};
+
+/// Name of an identifier, its value and the check it must pass
+type IdentifierCheck<'a> = (&'a str, &'a Option<String>, fn(&str) -> bool);
+
+/// Profile and classification of a company. Fields left out are not touched on
+/// update and take their default on insert.
+#[derive(Debug, Serialize, Deserialize, Insertable, AsChangeset, ToSchema)]
+#[diesel(table_name = companies)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub(super) struct CompanyPayload {
+    ticker: String,
+    name: Option<String>,
+    website: Option<String>,
+    state: Option<String>,
+    ceo: Option<String>,
+    image: Option<String>,
+    city: Option<String>,
+    employees: Option<String>,
+    address: Option<String>,
+    zip_code: Option<String>,
+    cik: Option<String>,
+    cusip: Option<String>,
+    isin: Option<String>,
+    figi: Option<String>,
+    description: Option<String>,
+    ipo_date: Option<NaiveDate>,
+    country_id: Option<i64>,
+    exchange_id: Option<i64>,
+    industry_id: Option<i64>,
+    sector_id: Option<i64>,
+    is_adr: Option<bool>,
+    is_fund: Option<bool>,
+}
+
+impl CompanyPayload {
+    fn validate(&mut self) -> Result<(), AppError> {
+        self.ticker = self.ticker.trim().to_string();
+        if self.ticker.is_empty() {
+            return Err(AppError::ValidationError("ticker can't be empty".into()));
+        }
+        let identifiers: [IdentifierCheck; 3] = [
+            ("ISIN", &self.isin, is_valid_isin),
+            ("CUSIP", &self.cusip, is_valid_cusip),
+            ("FIGI", &self.figi, is_valid_figi),
+        ];
+        for (kind, identifier, is_valid) in identifiers {
+            if let Some(identifier) = identifier.as_deref().filter(|id| !is_valid(id)) {
+                return Err(AppError::ValidationError(format!(
+                    "{identifier} isn't a valid {kind}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tickers aren't unique, a company can only be changed through one no other company
+/// uses
+fn company_by_ticker(ticker: &str, conn: &mut PgConnection) -> Result<i64, AppError> {
+    let ids: Vec<i64> = companies::table
+        .filter(companies::ticker.eq(ticker))
+        .select(companies::id)
+        .load(conn)?;
+    match ids.as_slice() {
+        [] => Err(AppError::DoesNotExist),
+        [id] => Ok(*id),
+        _ => Err(AppError::ValidationError(format!(
+            "{ticker} is used by {} companies",
+            ids.len()
+        ))),
+    }
+}
+
+/// Inserts the company or updates the one with the same ticker, returns whether it
+/// was inserted
+fn upsert_company(mut company: CompanyPayload, conn: &mut PgConnection) -> Result<bool, AppError> {
+    company.validate()?;
+    let ids: Vec<i64> = companies::table
+        .filter(companies::ticker.eq(&company.ticker))
+        .select(companies::id)
+        .load(conn)?;
+    match ids.as_slice() {
+        [] => {
+            diesel::insert_into(companies::table)
+                .values(&company)
+                .execute(conn)?;
+            Ok(true)
+        }
+        [id] => {
+            diesel::update(companies::table.find(id))
+                .set((&company, companies::updated_at.eq(diesel::dsl::now)))
+                .execute(conn)?;
+            Ok(false)
+        }
+        _ => Err(AppError::ValidationError(format!(
+            "{} is used by {} companies",
+            company.ticker,
+            ids.len()
+        ))),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "companies",
+    request_body = CompanyPayload,
+    responses(
+        (status = 200, body = Company, description = "Create a new company, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn create_company(
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    Json(mut company): Json<CompanyPayload>,
+) -> AppResult<Company> {
+    current_user.require_staff()?;
+    company.validate()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let taken = select(exists(
+                companies::table.filter(companies::ticker.eq(&company.ticker)),
+            ))
+            .get_result(conn)?;
+            if taken {
+                return Err(AppError::ValidationError(format!(
+                    "{} is already used by a company",
+                    company.ticker
+                )));
+            }
+            diesel::insert_into(companies::table)
+                .values(&company)
+                .returning(Company::as_returning())
+                .get_result(conn)
+                .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    put,
+    path = "companies/{ticker}",
+    params(("ticker", description = "Company's ticker")),
+    request_body = CompanyPayload,
+    responses(
+        (status = 200, body = Company, description = "Update a company by ticker, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn update_company(
+    Path(ticker): Path<String>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    Json(mut company): Json<CompanyPayload>,
+) -> AppResult<Company> {
+    current_user.require_staff()?;
+    company.validate()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let id = company_by_ticker(&ticker, conn)?;
+            diesel::update(companies::table.find(id))
+                .set((&company, companies::updated_at.eq(diesel::dsl::now)))
+                .returning(Company::as_returning())
+                .get_result(conn)
+                .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    delete,
+    path = "companies/{ticker}",
+    params(("ticker", description = "Company's ticker")),
+    responses(
+        (status = 200, description = "Delete a company by ticker along with its statements, staff only. Companies held by users can't be deleted"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn delete_company(
+    Path(ticker): Path<String>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<usize> {
+    current_user.require_staff()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let id = company_by_ticker(&ticker, conn)?;
+            // Deleting it would cascade to the holdings and transactions of users
+            let held = select(exists(
+                assets_details::table.filter(assets_details::company_id.eq(id)),
+            ))
+            .get_result(conn)?;
+            if held {
+                return Err(AppError::ValidationError(format!(
+                    "{ticker} is held by users, it can't be deleted"
+                )));
+            }
+            diesel::delete(companies::table.find(id))
+                .execute(conn)
+                .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "companies/bulk",
+    request_body(content = String, description = "Companies keyed by ticker as NDJSON, a JSON array or CSV with a header line", content_type = "application/x-ndjson"),
+    responses(
+        (status = 200, body = BulkReport, description = "How many companies were inserted, updated or rejected, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn bulk_upsert_companies(
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<BulkReport> {
+    current_user.require_staff()?;
+    let rows = parse_rows::<CompanyPayload>(BulkFormat::from_headers(&headers)?, &body)?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                let mut report = BulkReport::default();
+                for (number, row) in rows {
+                    let company = match row {
+                        Ok(company) => company,
+                        Err(reason) => {
+                            report.reject(number, reason);
+                            continue;
+                        }
+                    };
+                    // Each row gets a savepoint so a rejected one doesn't abort the rest
+                    match conn.transaction(|conn| upsert_company(company, conn)) {
+                        Ok(true) => report.inserted += 1,
+                        Ok(false) => report.updated += 1,
+                        Err(err) => report.reject(number, err),
+                    }
+                }
+                Ok(report)
+            })
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
@@ -1,17 +1,26 @@
 use axum::{
     extract::{Path, Query},
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
 use diesel::{
-    query_dsl::methods::{FilterDsl, SelectDsl},
-    ExpressionMethods, OptionalExtension, Queryable, RunQueryDsl, Selectable, SelectableHelper,
+    query_dsl::methods::{FilterDsl, LimitDsl, OrderDsl, SelectDsl},
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, PgTextExpressionMethods,
+    Queryable, RunQueryDsl, Selectable, SelectableHelper,
+};
+use diesel_full_text_search::{
+    configuration::TsConfigurationByName, to_tsquery_with_search_config, ts_rank,
+    TsVectorExtensions,
 };
 use serde::{Deserialize, Serialize};
 
+use super::admin::{
+    bulk_upsert_companies, create_company, delete_company, update_company, CompanyPayload,
+};
+use super::resolver::{resolve_identifier, IdentifierKind, Resolution, ResolvedCompany};
 use crate::{
     db::{schema::companies, Paginate},
-    server::{AppError, AppResult, AppState},
+    server::{AppError, AppResult, AppState, BulkReport, RejectedRow},
 };
 use chrono::NaiveDate;
 
@@ -19,8 +28,8 @@ use utoipa::{self, IntoParams, OpenApi, ToResponse, ToSchema};
 
 #[derive(OpenApi)]
 #[openapi(
-    paths(get_short_companies,get_company),
-    components(schemas(ShortCompanyResponse, ShortCompany, Company), responses(ShortCompanyResponse, ShortCompany, Company)),
+    paths(get_short_companies,search_companies,resolve_company,get_company,super::admin::create_company,super::admin::update_company,super::admin::delete_company,super::admin::bulk_upsert_companies),
+    components(schemas(ShortCompanyResponse, ShortCompany, Company, CompanySearchResult, Resolution, ResolvedCompany, IdentifierKind, CompanyPayload, BulkReport, RejectedRow), responses(ShortCompanyResponse, ShortCompany, Company, CompanySearchResult, Resolution, BulkReport)),
     tags((name = "Companies", description = "All about companies")),
     security(("token_jwt" = []))
 )]
@@ -28,8 +37,14 @@ pub struct ApiDoc;
 
 pub fn routes(state: AppState) -> Router<AppState> {
     Router::new()
-        .route("/companies", get(get_short_companies))
-        .route("/companies/:ticker", get(get_company))
+        .route("/companies", get(get_short_companies).post(create_company))
+        .route("/companies/bulk", post(bulk_upsert_companies))
+        .route("/companies/search", get(search_companies))
+        .route("/companies/resolve", get(resolve_company))
+        .route(
+            "/companies/:ticker",
+            get(get_company).put(update_company).delete(delete_company),
+        )
         .with_state(state)
 }
 
@@ -95,10 +110,135 @@ async fn get_short_companies(
     }))
 }
 
+const SEARCH_CONFIGURATION: TsConfigurationByName = TsConfigurationByName("simple");
+const DEFAULT_SEARCH_LIMIT: i64 = 10;
+const MAX_SEARCH_LIMIT: i64 = 50;
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+struct CompanySearchQuery {
+    /// Ticker, name, description, CEO, ISIN or CUSIP, the last word may be incomplete
+    q: String,
+    limit: Option<i64>,
+}
+
+#[derive(Queryable, Debug, Serialize, Deserialize, ToResponse, ToSchema)]
+struct CompanySearchResult {
+    ticker: String,
+    name: Option<String>,
+    isin: Option<String>,
+    country_id: Option<i64>,
+    exchange_id: Option<i64>,
+    rank: f32,
+}
+
+/// Turns free text into a tsquery where every word is matched as a prefix, dropping
+/// anything that could be read as tsquery syntax
+fn prefix_tsquery(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{word}:*"))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/search",
+    params(CompanySearchQuery),
+    responses(
+            (status = 200, body = Vec<CompanySearchResult>, description = "Companies matching the search, an exact ticker first and then by relevance"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn search_companies(
+    Query(query_params): Query<CompanySearchQuery>,
+    state: AppState,
+) -> AppResult<Vec<CompanySearchResult>> {
+    let text = query_params.q.trim().to_uppercase();
+    let tsquery = prefix_tsquery(&text)
+        .ok_or_else(|| AppError::ValidationError("q must contain a word to search".into()))?;
+    let limit = query_params
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+    // Tickers like BRK.B are split by the parser, so they are also matched verbatim
+    let ticker_prefix = format!("{}%", text.replace(['%', '_', '\\'], ""));
+
+    Ok(Json(
+        state
+            .db_write()
+            .await?
+            .interact(move |conn| {
+                let tsquery = to_tsquery_with_search_config(SEARCH_CONFIGURATION, tsquery);
+                let rank = ts_rank(companies::search_vector, tsquery.clone());
+                companies::table
+                    .filter(
+                        companies::search_vector
+                            .matches(tsquery.clone())
+                            .or(companies::ticker.ilike(ticker_prefix)),
+                    )
+                    .order((
+                        companies::ticker.eq(text).desc(),
+                        rank.clone().desc(),
+                        companies::ticker,
+                    ))
+                    .select((
+                        companies::ticker,
+                        companies::name,
+                        companies::isin,
+                        companies::country_id,
+                        companies::exchange_id,
+                        rank,
+                    ))
+                    .limit(limit)
+                    .load::<CompanySearchResult>(conn)
+                    .map_err(AppError::DatabaseQueryError)
+            })
+            .await
+            .map_err(AppError::DatabaseConnectionInteractError)??,
+    ))
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+struct ResolveQuery {
+    /// Ticker, optionally suffixed with the exchange (AIR.PA), ISIN, CUSIP, CIK or FIGI
+    identifier: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/resolve",
+    params(ResolveQuery),
+    responses(
+            (status = 200, body = Resolution, description = "The companies the identifier points to, flagged when it's ambiguous"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn resolve_company(
+    Query(query_params): Query<ResolveQuery>,
+    state: AppState,
+) -> AppResult<Resolution> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| resolve_identifier(&query_params.identifier, conn))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
 #[derive(Debug, Serialize, Deserialize, Queryable, Selectable, ToResponse, ToSchema)]
 #[diesel(table_name = companies)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
-struct Company {
+pub(super) struct Company {
     ticker: String,
     name: Option<String>,
     website: Option<String>,
@@ -112,6 +252,7 @@ struct Company {
     cik: Option<String>,
     cusip: Option<String>,
     isin: Option<String>,
+    figi: Option<String>,
     description: Option<String>,
     ipo_date: Option<NaiveDate>,
     country_id: Option<i64>,
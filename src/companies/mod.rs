@@ -1,3 +1,8 @@
+mod admin;
 mod handlers;
+mod querysets;
+mod resolver;
 
 pub use handlers::{routes, ApiDoc};
+pub use querysets::get_company_id;
+pub use resolver::resolve_identifier;
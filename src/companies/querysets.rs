@@ -0,0 +1,13 @@
+use crate::{db::schema::companies, server::AppError};
+
+use diesel::prelude::*;
+
+pub fn get_company_id(ticker: &str, conn: &mut PgConnection) -> Result<i64, AppError> {
+    companies::table
+        .filter(companies::ticker.eq(ticker))
+        .select(companies::id)
+        .first(conn)
+        .optional()
+        .map_err(AppError::DatabaseQueryError)?
+        .ok_or(AppError::DoesNotExist)
+}
@@ -0,0 +1,319 @@
+//! Maps any of the identifiers we store for a company (ticker, ISIN, CUSIP, CIK or
+//! FIGI) to the companies it points to
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{ToResponse, ToSchema};
+
+use crate::{
+    db::schema::{companies, exchanges},
+    server::AppError,
+};
+
+const CIK_LENGTH: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum IdentifierKind {
+    Isin,
+    Cusip,
+    Cik,
+    Figi,
+    Ticker,
+}
+
+#[derive(Debug, Queryable, Serialize, Deserialize, ToSchema)]
+pub struct ResolvedCompany {
+    pub id: i64,
+    pub ticker: String,
+    pub name: Option<String>,
+    pub exchange: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct Resolution {
+    pub identifier: String,
+    pub kind: IdentifierKind,
+    /// More than one company uses the identifier, e.g. a ticker listed on several exchanges
+    pub ambiguous: bool,
+    pub companies: Vec<ResolvedCompany>,
+}
+
+impl Resolution {
+    /// The company the identifier points to, only when there is exactly one
+    pub fn company_id(&self) -> Option<i64> {
+        match self.companies.as_slice() {
+            [company] => Some(company.id),
+            _ => None,
+        }
+    }
+}
+
+/// Value of a character in the ISIN, CUSIP and FIGI check digit algorithms
+fn char_value(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' | 'A'..='Z' => c.to_digit(36),
+        '*' => Some(36),
+        '@' => Some(37),
+        '#' => Some(38),
+        _ => None,
+    }
+}
+
+fn digit_sum(value: u32) -> u32 {
+    value / 10 + value % 10
+}
+
+/// Modulus 10 "double add double" used by CUSIP and FIGI, every second character
+/// from the left is doubled
+fn double_add_double(payload: &str) -> Option<u32> {
+    let mut sum = 0;
+    for (position, c) in payload.chars().enumerate() {
+        let value = char_value(c)?;
+        sum += if position % 2 == 1 {
+            digit_sum(value * 2)
+        } else {
+            digit_sum(value)
+        };
+    }
+    Some((10 - sum % 10) % 10)
+}
+
+/// Luhn over the digits obtained by replacing each letter with its two digits value
+fn luhn(payload: &str) -> Option<u32> {
+    let digits: String = payload
+        .chars()
+        .map(|c| char_value(c).map(|value| value.to_string()))
+        .collect::<Option<_>>()?;
+    let sum: u32 = digits
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(position, digit)| {
+            if position % 2 == 0 {
+                digit_sum(digit * 2)
+            } else {
+                digit
+            }
+        })
+        .sum();
+    Some((10 - sum % 10) % 10)
+}
+
+fn has_check_digit(identifier: &str, check_digit: fn(&str) -> Option<u32>) -> bool {
+    let (payload, check) = identifier.split_at(identifier.len() - 1);
+    check_digit(payload)
+        .is_some_and(|digit| check.chars().next().and_then(|c| c.to_digit(10)) == Some(digit))
+}
+
+pub fn is_valid_isin(isin: &str) -> bool {
+    // Only ASCII is sliced, the length counts bytes
+    isin.len() == 12
+        && isin
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        && isin[..2].chars().all(|c| c.is_ascii_uppercase())
+        && has_check_digit(isin, luhn)
+}
+
+pub fn is_valid_cusip(cusip: &str) -> bool {
+    cusip.len() == 9
+        && cusip.chars().all(|c| char_value(c).is_some())
+        && has_check_digit(cusip, double_add_double)
+}
+
+pub fn is_valid_figi(figi: &str) -> bool {
+    figi.len() == 12
+        && figi
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        && figi[..2].chars().all(|c| c.is_ascii_uppercase())
+        && figi[2..3] == *"G"
+        && has_check_digit(figi, double_add_double)
+}
+
+/// The kinds an identifier can be, in the order they should be looked up. Identifiers
+/// shaped like an ISIN, FIGI or CUSIP must carry a valid check digit
+pub fn identifier_kinds(identifier: &str) -> Result<Vec<IdentifierKind>, AppError> {
+    let alphanumeric = identifier
+        .chars()
+        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+    let ends_in_digit = identifier.ends_with(|c: char| c.is_ascii_digit());
+    let all_digits = identifier.chars().all(|c| c.is_ascii_digit());
+    let mut kinds = Vec::new();
+
+    if identifier.len() == 12 && alphanumeric && ends_in_digit {
+        if is_valid_figi(identifier) {
+            kinds.push(IdentifierKind::Figi);
+        }
+        if is_valid_isin(identifier) {
+            kinds.push(IdentifierKind::Isin);
+        }
+        if kinds.is_empty() {
+            return Err(AppError::ValidationError(format!(
+                "{identifier} has an invalid ISIN or FIGI check digit"
+            )));
+        }
+        return Ok(kinds);
+    }
+    if identifier.len() == 9 && ends_in_digit && identifier.chars().any(|c| c.is_ascii_digit()) {
+        if is_valid_cusip(identifier) {
+            kinds.push(IdentifierKind::Cusip);
+        } else if !all_digits {
+            return Err(AppError::ValidationError(format!(
+                "{identifier} has an invalid CUSIP check digit"
+            )));
+        }
+    }
+    if all_digits && identifier.len() <= CIK_LENGTH {
+        kinds.push(IdentifierKind::Cik);
+    }
+    if kinds.is_empty() {
+        if identifier.is_empty()
+            || !identifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        {
+            return Err(AppError::ValidationError(format!(
+                "{identifier} isn't a valid identifier"
+            )));
+        }
+        kinds.push(IdentifierKind::Ticker);
+    }
+    Ok(kinds)
+}
+
+fn find_companies(
+    kind: IdentifierKind,
+    identifier: &str,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<ResolvedCompany>> {
+    let query = companies::table
+        .left_join(exchanges::table)
+        .select((
+            companies::id,
+            companies::ticker,
+            companies::name,
+            exchanges::ticker.nullable(),
+        ))
+        .order(companies::id)
+        .into_boxed();
+    match kind {
+        IdentifierKind::Isin => query.filter(companies::isin.eq(identifier)).load(conn),
+        IdentifierKind::Cusip => query.filter(companies::cusip.eq(identifier)).load(conn),
+        IdentifierKind::Figi => query.filter(companies::figi.eq(identifier)).load(conn),
+        IdentifierKind::Cik => {
+            // CIKs are stored either padded to ten digits or without the leading zeros
+            let unpadded = identifier.trim_start_matches('0');
+            let padded = format!("{unpadded:0>CIK_LENGTH$}");
+            query
+                .filter(companies::cik.eq_any([unpadded.to_string(), padded]))
+                .load(conn)
+        }
+        IdentifierKind::Ticker => {
+            let found = query.filter(companies::ticker.eq(identifier)).load(conn)?;
+            if !found.is_empty() {
+                return Ok(found);
+            }
+            // Suffixed tickers like AIR.PA point to the listing on the exchange PA
+            let Some((ticker, exchange)) = identifier.rsplit_once('.') else {
+                return Ok(found);
+            };
+            companies::table
+                .inner_join(exchanges::table)
+                .filter(companies::ticker.eq(ticker))
+                .filter(exchanges::ticker.ilike(exchange))
+                .select((
+                    companies::id,
+                    companies::ticker,
+                    companies::name,
+                    exchanges::ticker.nullable(),
+                ))
+                .order(companies::id)
+                .load(conn)
+        }
+    }
+}
+
+pub fn resolve_identifier(
+    identifier: &str,
+    conn: &mut PgConnection,
+) -> Result<Resolution, AppError> {
+    let identifier = identifier.trim().to_uppercase();
+    let kinds = identifier_kinds(&identifier)?;
+    let mut resolution = Resolution {
+        identifier: identifier.clone(),
+        kind: kinds[0],
+        ambiguous: false,
+        companies: Vec::new(),
+    };
+    for kind in kinds {
+        let companies =
+            find_companies(kind, &identifier, conn).map_err(AppError::DatabaseQueryError)?;
+        if !companies.is_empty() {
+            resolution.kind = kind;
+            resolution.ambiguous = companies.len() > 1;
+            resolution.companies = companies;
+            break;
+        }
+    }
+    Ok(resolution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_isin_digits() {
+        assert!(is_valid_isin("US0378331005"));
+        assert!(is_valid_isin("GB0002634946"));
+        assert!(!is_valid_isin("US0378331004"));
+        assert!(!is_valid_isin("1S0378331005"));
+        assert!(!is_valid_isin("US037833100"));
+    }
+
+    #[test]
+    fn checks_cusip_digits() {
+        assert!(is_valid_cusip("037833100"));
+        assert!(is_valid_cusip("38259P508"));
+        assert!(!is_valid_cusip("037833101"));
+        assert!(!is_valid_cusip("03783310"));
+    }
+
+    #[test]
+    fn checks_figi_digits() {
+        assert!(is_valid_figi("BBG000B9XRY4"));
+        assert!(!is_valid_figi("BBG000B9XRY5"));
+        assert!(!is_valid_figi("BBX000B9XRY4"));
+    }
+
+    #[test]
+    fn rejects_non_ascii_identifiers() {
+        // Twelve bytes, but not twelve or nine characters
+        for identifier in ["aé123456789", "USé37833100", "BBé00B9XRY4"] {
+            assert_eq!(identifier.len(), 12);
+            assert!(!is_valid_isin(identifier));
+            assert!(!is_valid_figi(identifier));
+        }
+        assert!(!is_valid_cusip("03é33100"));
+        assert!(identifier_kinds("USé37833100").is_err());
+    }
+
+    #[test]
+    fn orders_identifier_kinds() {
+        assert_eq!(
+            identifier_kinds("US0378331005").unwrap(),
+            vec![IdentifierKind::Isin]
+        );
+        assert_eq!(
+            identifier_kinds("BBG000B9XRY4").unwrap(),
+            vec![IdentifierKind::Figi]
+        );
+        assert_eq!(
+            identifier_kinds("037833100").unwrap(),
+            vec![IdentifierKind::Cusip, IdentifierKind::Cik]
+        );
+        assert!(identifier_kinds("US0378331004").is_err());
+    }
+}
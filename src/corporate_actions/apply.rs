@@ -0,0 +1,193 @@
+use chrono::{NaiveDate, Utc};
+use diesel::prelude::*;
+
+use super::handlers::{ActionKind, CorporateAction};
+use crate::db::schema::{
+    assets_details, companies, corporate_actions, investment_details, transactions,
+    transactions_details,
+};
+
+/// Splits of a company, to restate quantities and per-share figures in today's
+/// shares
+#[derive(Debug, Default)]
+pub struct SplitFactors {
+    /// Ex-date and new shares per old share, sorted by date
+    splits: Vec<(NaiveDate, f64)>,
+}
+
+impl SplitFactors {
+    /// Splits that already happened
+    pub fn load(company_id: i64, conn: &mut PgConnection) -> QueryResult<Self> {
+        Self::query(company_id, false, conn)
+    }
+
+    /// Splits already applied to the lots, the ones new lots have to be adjusted
+    /// with
+    pub fn applied(company_id: i64, conn: &mut PgConnection) -> QueryResult<Self> {
+        Self::query(company_id, true, conn)
+    }
+
+    fn query(company_id: i64, applied: bool, conn: &mut PgConnection) -> QueryResult<Self> {
+        let mut query = corporate_actions::table
+            .filter(corporate_actions::company_id.eq(company_id))
+            .filter(corporate_actions::kind.eq_any(ActionKind::SPLITS.map(ActionKind::as_str)))
+            .filter(corporate_actions::ex_date.le(Utc::now().date_naive()))
+            .order(corporate_actions::ex_date)
+            .select((
+                corporate_actions::ex_date,
+                corporate_actions::ratio_to,
+                corporate_actions::ratio_from,
+            ))
+            .into_boxed();
+        if applied {
+            query = query.filter(corporate_actions::applied_at.is_not_null());
+        }
+        let splits = query
+            .load::<(NaiveDate, Option<f64>, Option<f64>)>(conn)?
+            .into_iter()
+            .filter_map(|(date, to, from)| Some((date, to? / from?)))
+            .collect();
+        Ok(Self { splits })
+    }
+
+    /// Today's shares one share held on the date turned into
+    pub fn since(&self, date: NaiveDate) -> f64 {
+        self.splits
+            .iter()
+            .filter(|(ex_date, _)| *ex_date > date)
+            .map(|(_, ratio)| ratio)
+            .product()
+    }
+}
+
+/// Lots of the company bought before the date
+fn lots_before(company_id: i64, date: NaiveDate, conn: &mut PgConnection) -> QueryResult<Vec<i64>> {
+    transactions_details::table
+        .inner_join(transactions::table.on(transactions::details_id.eq(transactions_details::id)))
+        .inner_join(
+            investment_details::table
+                .on(transactions_details::investment_details_id
+                    .eq(investment_details::id.nullable())),
+        )
+        .inner_join(assets_details::table.on(investment_details::asset_id.eq(assets_details::id)))
+        .filter(assets_details::company_id.eq(company_id))
+        .filter(transactions::date.lt(date))
+        .select(investment_details::id)
+        .distinct()
+        .load(conn)
+}
+
+fn scale_lots(
+    company_id: i64,
+    date: NaiveDate,
+    ratio: f64,
+    conn: &mut PgConnection,
+) -> QueryResult<()> {
+    let lots = lots_before(company_id, date, conn)?;
+    diesel::update(investment_details::table.filter(investment_details::id.eq_any(lots)))
+        .set((
+            investment_details::quantity.eq(investment_details::quantity * ratio),
+            investment_details::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn rename_company(
+    company_id: i64,
+    from: Option<&str>,
+    to: Option<&str>,
+    conn: &mut PgConnection,
+) -> QueryResult<()> {
+    let (Some(from), Some(to)) = (from, to) else {
+        return Ok(());
+    };
+    diesel::update(
+        companies::table
+            .filter(companies::id.eq(company_id))
+            .filter(companies::ticker.eq(from)),
+    )
+    .set((
+        companies::ticker.eq(to),
+        companies::updated_at.eq(diesel::dsl::now),
+    ))
+    .execute(conn)?;
+    Ok(())
+}
+
+/// Updates the lots or the ticker with an action whose ex-date has come, once
+pub(super) fn apply_action(action: &CorporateAction, conn: &mut PgConnection) -> QueryResult<()> {
+    if action.ex_date > Utc::now().date_naive() {
+        return Ok(());
+    }
+    // Claimed before the lots are touched, `action` may be stale and another run may
+    // have applied it since it was loaded
+    let claimed = diesel::update(
+        corporate_actions::table
+            .find(action.id)
+            .filter(corporate_actions::applied_at.is_null()),
+    )
+    .set(corporate_actions::applied_at.eq(diesel::dsl::now))
+    .execute(conn)?;
+    if claimed == 0 {
+        return Ok(());
+    }
+    match ActionKind::parse(&action.kind) {
+        Some(ActionKind::Split | ActionKind::ReverseSplit) => {
+            if let Some(ratio) = action.ratio() {
+                scale_lots(action.company_id, action.ex_date, ratio, conn)?;
+            }
+        }
+        Some(ActionKind::TickerChange) => rename_company(
+            action.company_id,
+            action.old_ticker.as_deref(),
+            action.new_ticker.as_deref(),
+            conn,
+        )?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Undoes what [`apply_action`] did, before the action is removed
+pub(super) fn revert_action(action: &CorporateAction, conn: &mut PgConnection) -> QueryResult<()> {
+    let released = diesel::update(
+        corporate_actions::table
+            .find(action.id)
+            .filter(corporate_actions::applied_at.is_not_null()),
+    )
+    .set(corporate_actions::applied_at.eq(None::<chrono::NaiveDateTime>))
+    .execute(conn)?;
+    if released == 0 {
+        return Ok(());
+    }
+    match ActionKind::parse(&action.kind) {
+        Some(ActionKind::Split | ActionKind::ReverseSplit) => {
+            if let Some(ratio) = action.ratio() {
+                scale_lots(action.company_id, action.ex_date, 1.0 / ratio, conn)?;
+            }
+        }
+        Some(ActionKind::TickerChange) => rename_company(
+            action.company_id,
+            action.new_ticker.as_deref(),
+            action.old_ticker.as_deref(),
+            conn,
+        )?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Applies the actions whose ex-date came since the last run
+pub fn apply_due_actions(conn: &mut PgConnection) -> QueryResult<()> {
+    let due: Vec<CorporateAction> = corporate_actions::table
+        .filter(corporate_actions::applied_at.is_null())
+        .filter(corporate_actions::ex_date.le(Utc::now().date_naive()))
+        .order(corporate_actions::ex_date)
+        .select(CorporateAction::as_select())
+        .load(conn)?;
+    for action in due {
+        conn.transaction(|conn| apply_action(&action, conn))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,361 @@
+use axum::{
+    extract::{Path, Query},
+    routing::{get, put},
+    Extension, Json, Router,
+};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{self, IntoParams, OpenApi, ToResponse, ToSchema};
+
+use super::apply::{apply_action, revert_action};
+use crate::{
+    companies::get_company_id,
+    db::schema::{companies, corporate_actions},
+    server::{AppError, AppResult, JWTUserRequest},
+    AppState,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        list_corporate_actions,
+        create_corporate_action,
+        update_corporate_action,
+        delete_corporate_action
+    ),
+    components(schemas(CorporateAction, CorporateActionPayload, ActionKind),
+    responses(CorporateAction)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/companies/:ticker/corporate_actions",
+            get(list_corporate_actions).post(create_corporate_action),
+        )
+        .route(
+            "/corporate_actions/:id",
+            put(update_corporate_action).delete(delete_corporate_action),
+        )
+        .with_state(state)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    Split,
+    ReverseSplit,
+    SpinOff,
+    TickerChange,
+    Merger,
+    SpecialDividend,
+}
+
+impl ActionKind {
+    pub const SPLITS: [ActionKind; 2] = [ActionKind::Split, ActionKind::ReverseSplit];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ActionKind::Split => "split",
+            ActionKind::ReverseSplit => "reverse_split",
+            ActionKind::SpinOff => "spin_off",
+            ActionKind::TickerChange => "ticker_change",
+            ActionKind::Merger => "merger",
+            ActionKind::SpecialDividend => "special_dividend",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        [
+            ActionKind::Split,
+            ActionKind::ReverseSplit,
+            ActionKind::SpinOff,
+            ActionKind::TickerChange,
+            ActionKind::Merger,
+            ActionKind::SpecialDividend,
+        ]
+        .into_iter()
+        .find(|action| action.as_str() == kind)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable, ToSchema, ToResponse)]
+#[diesel(table_name = corporate_actions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CorporateAction {
+    pub id: i64,
+    pub company_id: i64,
+    pub kind: String,
+    pub ex_date: NaiveDate,
+    pub announcement_date: Option<NaiveDate>,
+    /// New shares received for `ratio_from` old ones
+    pub ratio_to: Option<f64>,
+    pub ratio_from: Option<f64>,
+    pub old_ticker: Option<String>,
+    pub new_ticker: Option<String>,
+    /// Spun-off company or acquirer
+    pub related_company_id: Option<i64>,
+    pub cash_amount: Option<BigDecimal>,
+    pub currency_id: Option<i64>,
+    pub description: Option<String>,
+    /// When lots and tickers were updated with the action, null until the ex-date
+    pub applied_at: Option<NaiveDateTime>,
+}
+
+impl CorporateAction {
+    /// New shares per old share
+    pub fn ratio(&self) -> Option<f64> {
+        Some(self.ratio_to? / self.ratio_from?)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable, AsChangeset, ToSchema)]
+#[diesel(table_name = corporate_actions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(treat_none_as_null = true)]
+pub struct CorporateActionPayload {
+    #[serde(skip)]
+    company_id: i64,
+    /// split, reverse_split, spin_off, ticker_change, merger or special_dividend
+    kind: String,
+    ex_date: NaiveDate,
+    announcement_date: Option<NaiveDate>,
+    /// Required by splits, 4 new shares for 1 old one in a 4:1 split. Spin-offs and
+    /// mergers use it for the shares of the related company
+    ratio_to: Option<f64>,
+    ratio_from: Option<f64>,
+    /// Ticker changes default it to the current ticker
+    old_ticker: Option<String>,
+    new_ticker: Option<String>,
+    related_company_id: Option<i64>,
+    /// Required by special dividends
+    cash_amount: Option<BigDecimal>,
+    currency_id: Option<i64>,
+    description: Option<String>,
+}
+
+impl CorporateActionPayload {
+    fn validate(&mut self, ticker: &str) -> Result<(), AppError> {
+        let invalid = |message: &str| Err(AppError::ValidationError(message.to_string()));
+        self.kind = self.kind.trim().to_lowercase();
+        let Some(kind) = ActionKind::parse(&self.kind) else {
+            return Err(AppError::ValidationError(format!(
+                "Unknown kind {}, use split, reverse_split, spin_off, ticker_change, merger or special_dividend",
+                self.kind
+            )));
+        };
+        let ratio = match (self.ratio_to, self.ratio_from) {
+            (None, None) => None,
+            (Some(to), Some(from)) if to > 0.0 && from > 0.0 => Some(to / from),
+            _ => return invalid("ratio_to and ratio_from go together and must be positive"),
+        };
+        match kind {
+            ActionKind::Split if !ratio.is_some_and(|ratio| ratio > 1.0) => {
+                invalid("a split needs ratio_to greater than ratio_from")
+            }
+            ActionKind::ReverseSplit if !ratio.is_some_and(|ratio| ratio < 1.0) => {
+                invalid("a reverse split needs ratio_to lower than ratio_from")
+            }
+            ActionKind::SpinOff if self.related_company_id.is_none() => {
+                invalid("a spin-off needs the related_company_id of the new company")
+            }
+            ActionKind::TickerChange => {
+                let new_ticker = self
+                    .new_ticker
+                    .as_deref()
+                    .map(str::trim)
+                    .unwrap_or_default();
+                if new_ticker.is_empty() {
+                    return invalid("a ticker change needs the new_ticker");
+                }
+                self.new_ticker = Some(new_ticker.to_string());
+                self.old_ticker.get_or_insert_with(|| ticker.to_string());
+                Ok(())
+            }
+            ActionKind::SpecialDividend
+                if self
+                    .cash_amount
+                    .as_ref()
+                    .is_none_or(|cash| *cash <= BigDecimal::zero())
+                    || self.currency_id.is_none() =>
+            {
+                invalid("a special dividend needs a positive cash_amount and its currency_id")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct CorporateActionsQuery {
+    /// Only actions of this kind
+    pub kind: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/corporate_actions",
+    params(("ticker", description = "Company's ticker"), CorporateActionsQuery),
+    responses(
+        (status = 200, body = Vec<CorporateAction>, description = "Corporate actions of a company, oldest first"),
+        (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+        (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+    )
+)]
+async fn list_corporate_actions(
+    Path(ticker): Path<String>,
+    Query(query_params): Query<CorporateActionsQuery>,
+    state: AppState,
+) -> AppResult<Vec<CorporateAction>> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            let mut query = corporate_actions::table
+                .filter(corporate_actions::company_id.eq(company_id))
+                .order(corporate_actions::ex_date)
+                .select(CorporateAction::as_select())
+                .into_boxed();
+            if let Some(kind) = query_params.kind {
+                query = query.filter(corporate_actions::kind.eq(kind));
+            }
+            query.load(conn).map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "companies/{ticker}/corporate_actions",
+    params(("ticker", description = "Company's ticker")),
+    request_body = CorporateActionPayload,
+    responses(
+        (status = 200, body = CorporateAction, description = "Record a corporate action, staff only. Actions whose ex-date has passed are applied right away"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn create_corporate_action(
+    Path(ticker): Path<String>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    Json(mut action): Json<CorporateActionPayload>,
+) -> AppResult<CorporateAction> {
+    current_user.require_staff()?;
+    action.validate(&ticker)?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            action.company_id = get_company_id(&ticker, conn)?;
+            conn.transaction(|conn| {
+                let action = diesel::insert_into(corporate_actions::table)
+                    .values(&action)
+                    .returning(CorporateAction::as_returning())
+                    .get_result(conn)?;
+                apply_action(&action, conn)?;
+                corporate_actions::table
+                    .find(action.id)
+                    .select(CorporateAction::as_select())
+                    .first(conn)
+            })
+            .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    put,
+    path = "corporate_actions/{id}",
+    params(("id", description = "Corporate action's id")),
+    request_body = CorporateActionPayload,
+    responses(
+        (status = 200, body = CorporateAction, description = "Update a corporate action, staff only. Applied actions are reverted and applied again"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn update_corporate_action(
+    Path(id): Path<i64>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    Json(mut action): Json<CorporateActionPayload>,
+) -> AppResult<CorporateAction> {
+    current_user.require_staff()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                let current = find_action(id, conn)?;
+                revert_action(&current, conn)?;
+                let ticker: String = companies::table
+                    .find(current.company_id)
+                    .select(companies::ticker)
+                    .first(conn)?;
+                action.validate(&ticker)?;
+                action.company_id = current.company_id;
+                let updated = diesel::update(corporate_actions::table.find(id))
+                    .set((&action, corporate_actions::updated_at.eq(diesel::dsl::now)))
+                    .returning(CorporateAction::as_returning())
+                    .get_result(conn)?;
+                apply_action(&updated, conn)?;
+                find_action(id, conn)
+            })
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    delete,
+    path = "corporate_actions/{id}",
+    params(("id", description = "Corporate action's id")),
+    responses(
+        (status = 200, body = CorporateAction, description = "Delete a corporate action and revert it on lots and tickers, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn delete_corporate_action(
+    Path(id): Path<i64>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<CorporateAction> {
+    current_user.require_staff()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                let action = find_action(id, conn)?;
+                revert_action(&action, conn)?;
+                diesel::delete(corporate_actions::table.find(id)).execute(conn)?;
+                Ok(action)
+            })
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+/// Locks the action until the transaction ends, the hourly job can't apply it meanwhile
+fn find_action(id: i64, conn: &mut PgConnection) -> Result<CorporateAction, AppError> {
+    corporate_actions::table
+        .find(id)
+        .select(CorporateAction::as_select())
+        .for_update()
+        .first(conn)
+        .optional()?
+        .ok_or(AppError::DoesNotExist)
+}
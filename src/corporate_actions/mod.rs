@@ -0,0 +1,10 @@
+//! Splits, spin-offs, ticker changes, mergers and special dividends of companies.
+//!
+//! Splits and reverse splits restate per-share figures on request and the quantity
+//! of the lots held before them, ticker changes rename the company. The rest of the
+//! actions are only recorded.
+mod apply;
+mod handlers;
+
+pub use apply::{apply_due_actions, SplitFactors};
+pub use handlers::{routes, ApiDoc};
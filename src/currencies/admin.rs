@@ -0,0 +1,612 @@
+//! Staff management of currencies, the countries they're used in and the import of
+//! the ISO 4217 list
+use std::{collections::HashSet, path::PathBuf};
+
+use axum::{body::Bytes, extract::Path, Extension, Json};
+use diesel::{
+    dsl::exists,
+    pg::upsert::excluded,
+    prelude::*,
+    result::{DatabaseErrorKind, Error as DieselError},
+    select,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{self, ToResponse, ToSchema};
+
+use super::handlers::Currency;
+use crate::{
+    db::schema::{
+        accounts, corporate_actions, countries, currencies, currencies_countries_m2m, dividends,
+        exchange_rates, prices, profiles, transactions,
+    },
+    fundamentals::reported_in,
+    server::{AppError, AppResult, AppState, BulkReport, JWTUserRequest},
+};
+
+/// ISO 4217 list one with an entry per currency, the ISO publishes one per country
+const ISO_4217_LIST: &str = include_str!("iso4217.xml");
+
+/// Value of the minor unit of the entries that aren't a currency, like gold
+const NO_MINOR_UNIT: &str = "N.A.";
+
+/// Most decimals an ISO 4217 currency has, the Chilean unidad de fomento has 4
+const MAX_DECIMALS: i32 = 4;
+
+/// Longest symbol `currencies.symbol` stores
+const MAX_SYMBOL_LENGTH: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, Insertable, AsChangeset, ToSchema)]
+#[diesel(table_name = currencies)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub(super) struct CurrencyPayload {
+    name: String,
+    /// Three letters, like EUR
+    alphabetic_code: String,
+    /// Three digits, like 978
+    numeric_code: String,
+    symbol: String,
+    /// Digits of the minor unit, 2 for the cents of the euro
+    decimals: i32,
+}
+
+impl CurrencyPayload {
+    fn validate(&mut self) -> Result<(), AppError> {
+        self.name = self.name.trim().to_string();
+        self.alphabetic_code = self.alphabetic_code.trim().to_uppercase();
+        self.numeric_code = self.numeric_code.trim().to_string();
+        self.symbol = self.symbol.trim().to_string();
+        if self.name.is_empty() {
+            return Err(AppError::ValidationError("name can't be empty".into()));
+        }
+        if self.alphabetic_code.len() != 3
+            || !self.alphabetic_code.chars().all(|c| c.is_ascii_uppercase())
+        {
+            return Err(AppError::ValidationError(format!(
+                "{} isn't an ISO 4217 alphabetic code, it must be three letters",
+                self.alphabetic_code
+            )));
+        }
+        if self.numeric_code.len() != 3 || !self.numeric_code.chars().all(|c| c.is_ascii_digit()) {
+            return Err(AppError::ValidationError(format!(
+                "{} isn't an ISO 4217 numeric code, it must be three digits",
+                self.numeric_code
+            )));
+        }
+        if !(0..=MAX_DECIMALS).contains(&self.decimals) {
+            return Err(AppError::ValidationError(format!(
+                "decimals must be between 0 and {MAX_DECIMALS}"
+            )));
+        }
+        if self.symbol.is_empty() || self.symbol.chars().count() > MAX_SYMBOL_LENGTH {
+            return Err(AppError::ValidationError(format!(
+                "symbol must have between 1 and {MAX_SYMBOL_LENGTH} characters"
+            )));
+        }
+        Ok(())
+    }
+
+    /// A currency of the bundled ISO 4217 list must keep its numeric code and
+    /// decimals, and can't take the numeric code of another one. Codes the list
+    /// doesn't have, like the ones of withdrawn currencies, are taken as they are
+    fn check_iso4217(&self) -> Result<(), AppError> {
+        for entry in parse_iso4217(ISO_4217_LIST)? {
+            if entry.code == self.alphabetic_code {
+                if entry.number != self.numeric_code {
+                    return Err(AppError::ValidationError(format!(
+                        "The ISO 4217 numeric code of {} is {}",
+                        entry.code, entry.number
+                    )));
+                }
+                match entry.decimals {
+                    Some(decimals) if decimals != self.decimals => {
+                        return Err(AppError::ValidationError(format!(
+                            "{} has {decimals} decimals in ISO 4217",
+                            entry.code
+                        )))
+                    }
+                    None => {
+                        return Err(AppError::ValidationError(format!(
+                            "{} has no minor unit in ISO 4217, it isn't money an account can hold",
+                            entry.code
+                        )))
+                    }
+                    Some(_) => {}
+                }
+            } else if entry.number == self.numeric_code {
+                return Err(AppError::ValidationError(format!(
+                    "{} is the ISO 4217 numeric code of {}",
+                    entry.number, entry.code
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Codes are unique, the indexes on both codes reject one another currency has
+fn codes_taken(currency: &CurrencyPayload, err: diesel::result::Error) -> AppError {
+    match err {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            AppError::ValidationError(format!(
+                "Another currency already uses the code {} or {}",
+                currency.alphabetic_code, currency.numeric_code
+            ))
+        }
+        err => AppError::DatabaseQueryError(err),
+    }
+}
+
+fn currency_by_code(code: &str, conn: &mut PgConnection) -> Result<i64, AppError> {
+    currencies::table
+        .filter(currencies::alphabetic_code.eq(code.trim().to_uppercase()))
+        .select(currencies::id)
+        .first(conn)
+        .optional()?
+        .ok_or(AppError::DoesNotExist)
+}
+
+fn country_by_code(code: &str, conn: &mut PgConnection) -> Result<i64, AppError> {
+    countries::table
+        .filter(countries::alpha_2_code.eq(code.trim().to_uppercase()))
+        .select(countries::id)
+        .first(conn)
+        .optional()?
+        .ok_or(AppError::DoesNotExist)
+}
+
+#[utoipa::path(
+    post,
+    path = "currencies",
+    request_body = CurrencyPayload,
+    responses(
+        (status = 200, body = Currency, description = "Create a new currency, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn create_currency(
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    Json(mut currency): Json<CurrencyPayload>,
+) -> AppResult<Currency> {
+    current_user.require_staff()?;
+    currency.validate()?;
+    currency.check_iso4217()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            diesel::insert_into(currencies::table)
+                .values(&currency)
+                .returning(Currency::as_returning())
+                .get_result(conn)
+                .map_err(|err| codes_taken(&currency, err))
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    put,
+    path = "currencies/{code}",
+    params(("code", description = "Currency's alphabetic code")),
+    request_body = CurrencyPayload,
+    responses(
+        (status = 200, body = Currency, description = "Update a currency by code, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn update_currency(
+    Path(code): Path<String>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    Json(mut currency): Json<CurrencyPayload>,
+) -> AppResult<Currency> {
+    current_user.require_staff()?;
+    currency.validate()?;
+    currency.check_iso4217()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let id = currency_by_code(&code, conn)?;
+            diesel::update(currencies::table.find(id))
+                .set((&currency, currencies::updated_at.eq(diesel::dsl::now)))
+                .returning(Currency::as_returning())
+                .get_result(conn)
+                .map_err(|err| codes_taken(&currency, err))
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    delete,
+    path = "currencies/{code}",
+    params(("code", description = "Currency's alphabetic code")),
+    responses(
+        (status = 200, description = "Delete a currency no account, profile, dividend, transaction, price, corporate action or statement uses, along with its exchange rates, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn delete_currency(
+    Path(code): Path<String>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<usize> {
+    current_user.require_staff()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let id = currency_by_code(&code, conn)?;
+            // Accounts and profiles would be deleted along with the currency, and
+            // transactions along with its exchange rates. Prices, corporate actions and
+            // statements would lose their currency
+            let in_use: bool = select(
+                exists(accounts::table.filter(accounts::currency_id.eq(id)))
+                    .or(exists(profiles::table.filter(profiles::currency_id.eq(id))))
+                    .or(exists(
+                        dividends::table.filter(dividends::currency_id.eq(id)),
+                    ))
+                    .or(exists(
+                        transactions::table
+                            .inner_join(exchange_rates::table)
+                            .filter(
+                                exchange_rates::base_id
+                                    .eq(id)
+                                    .or(exchange_rates::target_id.eq(id)),
+                            ),
+                    ))
+                    .or(exists(prices::table.filter(prices::currency_id.eq(id))))
+                    .or(exists(
+                        corporate_actions::table.filter(corporate_actions::currency_id.eq(id)),
+                    )),
+            )
+            .get_result(conn)?;
+            if in_use || reported_in(id, conn)? {
+                return Err(AppError::ValidationError(format!(
+                    "{code} is used by accounts, profiles, dividends, transactions, prices, \
+                     corporate actions or statements"
+                )));
+            }
+            diesel::delete(currencies::table.find(id))
+                .execute(conn)
+                .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[derive(Debug, Queryable, Serialize, Deserialize, ToSchema, ToResponse)]
+pub(super) struct CountryLink {
+    /// ISO 3166 alpha-2 code of the country
+    country: String,
+    name: String,
+    /// Whether it's the currency used by default in the country
+    is_primary: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub(super) struct CountryLinkPayload {
+    #[serde(default)]
+    is_primary: bool,
+}
+
+fn country_links(currency_id: i64, conn: &mut PgConnection) -> QueryResult<Vec<CountryLink>> {
+    currencies_countries_m2m::table
+        .inner_join(countries::table)
+        .filter(currencies_countries_m2m::currency_id.eq(currency_id))
+        .order(countries::alpha_2_code)
+        .select((
+            countries::alpha_2_code,
+            countries::name,
+            currencies_countries_m2m::is_primary,
+        ))
+        .load(conn)
+}
+
+#[utoipa::path(
+    get,
+    path = "currencies/{code}/countries",
+    params(("code", description = "Currency's alphabetic code")),
+    responses(
+        (status = 200, body = Vec<CountryLink>, description = "Countries where the currency is used"),
+        (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+        (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+    )
+)]
+pub(super) async fn list_currency_countries(
+    Path(code): Path<String>,
+    state: AppState,
+) -> AppResult<Vec<CountryLink>> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let id = currency_by_code(&code, conn)?;
+            country_links(id, conn).map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    put,
+    path = "currencies/{code}/countries/{country}",
+    params(
+        ("code", description = "Currency's alphabetic code"),
+        ("country", description = "Country's alpha-2 code"),
+    ),
+    request_body = CountryLinkPayload,
+    responses(
+        (status = 200, body = Vec<CountryLink>, description = "Link the currency to a country, a primary currency replaces the one the country had, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn link_currency_country(
+    Path((code, country)): Path<(String, String)>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    payload: Option<Json<CountryLinkPayload>>,
+) -> AppResult<Vec<CountryLink>> {
+    current_user.require_staff()?;
+    let Json(payload) = payload.unwrap_or_default();
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let currency_id = currency_by_code(&code, conn)?;
+            let country_id = country_by_code(&country, conn)?;
+            conn.transaction(|conn| {
+                if payload.is_primary {
+                    diesel::update(
+                        currencies_countries_m2m::table
+                            .filter(currencies_countries_m2m::country_id.eq(country_id))
+                            .filter(currencies_countries_m2m::currency_id.ne(currency_id))
+                            .filter(currencies_countries_m2m::is_primary),
+                    )
+                    .set((
+                        currencies_countries_m2m::is_primary.eq(false),
+                        currencies_countries_m2m::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(conn)?;
+                }
+                diesel::insert_into(currencies_countries_m2m::table)
+                    .values((
+                        currencies_countries_m2m::currency_id.eq(currency_id),
+                        currencies_countries_m2m::country_id.eq(country_id),
+                        currencies_countries_m2m::is_primary.eq(payload.is_primary),
+                    ))
+                    .on_conflict((
+                        currencies_countries_m2m::currency_id,
+                        currencies_countries_m2m::country_id,
+                    ))
+                    .do_update()
+                    .set((
+                        currencies_countries_m2m::is_primary
+                            .eq(excluded(currencies_countries_m2m::is_primary)),
+                        currencies_countries_m2m::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(conn)?;
+                country_links(currency_id, conn)
+            })
+            .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    delete,
+    path = "currencies/{code}/countries/{country}",
+    params(
+        ("code", description = "Currency's alphabetic code"),
+        ("country", description = "Country's alpha-2 code"),
+    ),
+    responses(
+        (status = 200, description = "Unlink the currency from a country, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn unlink_currency_country(
+    Path((code, country)): Path<(String, String)>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<usize> {
+    current_user.require_staff()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let currency_id = currency_by_code(&code, conn)?;
+            let country_id = country_by_code(&country, conn)?;
+            diesel::delete(
+                currencies_countries_m2m::table
+                    .filter(currencies_countries_m2m::currency_id.eq(currency_id))
+                    .filter(currencies_countries_m2m::country_id.eq(country_id)),
+            )
+            .execute(conn)
+            .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+/// A currency of the ISO 4217 list, numbered by its position in the file
+#[derive(Debug, Clone)]
+pub struct Iso4217Entry {
+    pub row: usize,
+    pub code: String,
+    pub number: String,
+    pub name: String,
+    /// None for entries without a minor unit, like gold or the SDR
+    pub decimals: Option<i32>,
+}
+
+/// Reads the XML of ISO 4217 list one. A currency used in several countries has an
+/// entry per country, only the first one is kept
+pub fn parse_iso4217(body: &str) -> Result<Vec<Iso4217Entry>, AppError> {
+    let document = roxmltree::Document::parse(body.trim_start_matches('\u{feff}'))
+        .map_err(|err| AppError::ValidationError(format!("ISO 4217 list: {err}")))?;
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for (row, entry) in (1..).zip(
+        document
+            .descendants()
+            .filter(|node| node.has_tag_name("CcyNtry")),
+    ) {
+        let field = |name: &str| {
+            entry
+                .children()
+                .find(|node| node.has_tag_name(name))
+                .and_then(|node| node.text())
+                .map(str::trim)
+                .unwrap_or_default()
+                .to_string()
+        };
+        // Countries without a currency of their own, like Antarctica, have no code
+        let code = field("Ccy");
+        if code.is_empty() || !seen.insert(code.clone()) {
+            continue;
+        }
+        let minor_unit = field("CcyMnrUnts");
+        entries.push(Iso4217Entry {
+            row,
+            number: field("CcyNbr"),
+            name: field("CcyNm"),
+            decimals: if minor_unit == NO_MINOR_UNIT {
+                None
+            } else {
+                minor_unit.parse().ok()
+            },
+            code,
+        });
+    }
+    Ok(entries)
+}
+
+/// Inserts the currencies of the list and updates the name, numeric code and decimals
+/// of the ones already stored. Symbols aren't part of ISO 4217, new currencies take
+/// their code as symbol until staff sets one. Entries without a minor unit are left
+/// out, they aren't money an account can hold
+pub fn import_iso4217(
+    entries: Vec<Iso4217Entry>,
+    conn: &mut PgConnection,
+) -> Result<BulkReport, AppError> {
+    conn.transaction(|conn| {
+        let mut report = BulkReport::default();
+        for entry in entries {
+            let Some(decimals) = entry.decimals else {
+                continue;
+            };
+            let mut currency = CurrencyPayload {
+                name: entry.name,
+                symbol: entry.code.clone(),
+                alphabetic_code: entry.code,
+                numeric_code: entry.number,
+                decimals,
+            };
+            if let Err(err) = currency.validate() {
+                report.reject(entry.row, err);
+                continue;
+            }
+            // Each entry gets a savepoint so one whose numeric code another currency
+            // has doesn't abort the rest
+            let stored = conn.transaction(|conn| {
+                let id: Option<i64> = currencies::table
+                    .filter(currencies::alphabetic_code.eq(&currency.alphabetic_code))
+                    .select(currencies::id)
+                    .first(conn)
+                    .optional()?;
+                match id {
+                    Some(id) => {
+                        diesel::update(currencies::table.find(id))
+                            .set((
+                                currencies::name.eq(&currency.name),
+                                currencies::numeric_code.eq(&currency.numeric_code),
+                                currencies::decimals.eq(currency.decimals),
+                                currencies::updated_at.eq(diesel::dsl::now),
+                            ))
+                            .execute(conn)?;
+                        Ok(false)
+                    }
+                    None => {
+                        diesel::insert_into(currencies::table)
+                            .values(&currency)
+                            .execute(conn)?;
+                        Ok(true)
+                    }
+                }
+            });
+            match stored {
+                Ok(true) => report.inserted += 1,
+                Ok(false) => report.updated += 1,
+                Err(err) => report.reject(entry.row, codes_taken(&currency, err)),
+            }
+        }
+        Ok(report)
+    })
+}
+
+/// Imports a copy of the ISO 4217 list, the one bundled with the server by default
+pub async fn ingest_iso4217(
+    state: &AppState,
+    path: Option<PathBuf>,
+) -> Result<BulkReport, AppError> {
+    let body = match path {
+        Some(path) => tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|err| AppError::ValidationError(format!("{}: {err}", path.display())))?,
+        None => ISO_4217_LIST.to_string(),
+    };
+    let entries = parse_iso4217(&body)?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| import_iso4217(entries, conn))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+}
+
+#[utoipa::path(
+    post,
+    path = "currencies/iso4217",
+    request_body(content = String, description = "ISO 4217 list one as XML, the list bundled with the server when the body is empty", content_type = "application/xml"),
+    responses(
+        (status = 200, body = BulkReport, description = "How many currencies of the list were inserted, updated or rejected, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn import_iso4217_list(
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    body: Bytes,
+) -> AppResult<BulkReport> {
+    current_user.require_staff()?;
+    let entries = if body.is_empty() {
+        parse_iso4217(ISO_4217_LIST)?
+    } else {
+        let body = std::str::from_utf8(&body)
+            .map_err(|err| AppError::ValidationError(format!("ISO 4217 list: {err}")))?;
+        parse_iso4217(body)?
+    };
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| import_iso4217(entries, conn))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
@@ -0,0 +1,265 @@
+use bigdecimal::{BigDecimal, One};
+use chrono::{Duration, NaiveDate};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{ToResponse, ToSchema};
+
+use super::{currency_decimals, querysets::get_currency_id, Money, SourcePrecedence};
+use crate::{
+    db::schema::{currencies, exchange_rates},
+    server::AppError,
+};
+
+/// Currencies tried, in order, to cross two currencies without a pair between them
+const PIVOTS: [&str; 2] = ["EUR", "USD"];
+
+/// Days a rate keeps applying when no newer one was published, long enough to cover
+/// weekends and bank holidays
+const LOOKBACK_DAYS: i64 = 7;
+
+/// Multiplier from one currency into another, see [`super::RateSeries`] for how the
+/// stored rates read
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Rate {
+    pub rate: BigDecimal,
+    /// Publication date of the rate, the oldest of both legs when crossed
+    pub date: NaiveDate,
+    /// Significant digits the rate is good for
+    #[serde(skip)]
+    precision: u64,
+    /// Currency both legs were crossed through
+    pub via: Option<String>,
+}
+
+impl Rate {
+    fn identity(date: NaiveDate) -> Self {
+        Self {
+            rate: BigDecimal::one(),
+            date,
+            precision: 0,
+            via: None,
+        }
+    }
+
+    /// A stored rate, `direct` when its base is the currency amounts are converted
+    /// into. Inverses keep the significant digits the rate was published with
+    fn stored(rate: BigDecimal, direct: bool, date: NaiveDate) -> Self {
+        let precision = rate.digits();
+        Self {
+            rate: if direct {
+                rate
+            } else {
+                rate.inverse().with_prec(precision)
+            },
+            date,
+            precision,
+            via: None,
+        }
+    }
+
+    fn cross(self, next: Rate, via: &str) -> Self {
+        let precision = self.precision.min(next.precision);
+        Self {
+            rate: (self.rate * next.rate).with_prec(precision),
+            date: self.date.min(next.date),
+            precision,
+            via: Some(via.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct Conversion {
+    pub from: String,
+    pub to: String,
+    pub amount: BigDecimal,
+    /// Rounded to the decimals of the `to` currency
+    pub converted: BigDecimal,
+    #[serde(flatten)]
+    pub rate: Rate,
+}
+
+/// Rates stored for a pair, one per day and oldest first
+pub(super) struct PairRates(Vec<Rate>);
+
+impl PairRates {
+    /// Rates of the pair between both dates, of a single source when given. Between
+    /// the rates of a day the best source wins and, for the same source, the direct
+    /// pair wins over the inverse of the reverse one
+    pub(super) fn load(
+        from_id: i64,
+        to_id: i64,
+        start: NaiveDate,
+        end: NaiveDate,
+        source: Option<&str>,
+        precedence: &SourcePrecedence,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Self> {
+        let mut query = exchange_rates::table
+            .filter(
+                (exchange_rates::base_id
+                    .eq(to_id)
+                    .and(exchange_rates::target_id.eq(from_id)))
+                .or(exchange_rates::base_id
+                    .eq(from_id)
+                    .and(exchange_rates::target_id.eq(to_id))),
+            )
+            .filter(exchange_rates::date.between(start, end))
+            .into_boxed();
+        if let Some(source) = source {
+            query = query.filter(exchange_rates::source.eq(source));
+        }
+        let rows: Vec<(i64, NaiveDate, Option<String>, BigDecimal)> = query
+            .order((exchange_rates::date, exchange_rates::id.desc()))
+            .select((
+                exchange_rates::base_id,
+                exchange_rates::date,
+                exchange_rates::source,
+                exchange_rates::conversion_rate,
+            ))
+            .load(conn)?;
+
+        let mut rates: Vec<((usize, String, bool), Rate)> = Vec::with_capacity(rows.len());
+        for (base_id, date, source, rate) in rows {
+            let (position, source) = precedence.rank(to_id, from_id, source.as_deref());
+            let rank = (position, source.to_string(), base_id != to_id);
+            let rate = Rate::stored(rate, base_id == to_id, date);
+            match rates.last_mut() {
+                Some((last_rank, last)) if last.date == date => {
+                    if rank < *last_rank {
+                        *last_rank = rank;
+                        *last = rate;
+                    }
+                }
+                _ => rates.push((rank, rate)),
+            }
+        }
+        Ok(Self(rates.into_iter().map(|(_, rate)| rate).collect()))
+    }
+
+    /// Rate of the date or of the closest day before it, up to [`LOOKBACK_DAYS`] old
+    fn on(&self, date: NaiveDate) -> Option<&Rate> {
+        let index = self.0.partition_point(|rate| rate.date <= date);
+        index
+            .checked_sub(1)
+            .map(|index| &self.0[index])
+            .filter(|rate| date - rate.date <= Duration::days(LOOKBACK_DAYS))
+    }
+
+    pub(super) fn into_rates(self) -> Vec<Rate> {
+        self.0
+    }
+}
+
+/// Rates between two currencies over a range of days. The days without a rate for
+/// the pair cross it through EUR or USD
+pub(super) struct RateLookup {
+    identity: bool,
+    direct: PairRates,
+    /// Pivot with the rates from the currency into it and from it into the other one
+    crosses: Vec<(&'static str, PairRates, PairRates)>,
+}
+
+impl RateLookup {
+    pub(super) fn load(
+        from_id: i64,
+        to_id: i64,
+        start: NaiveDate,
+        end: NaiveDate,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Self> {
+        let mut lookup = Self {
+            identity: from_id == to_id,
+            direct: PairRates(Vec::new()),
+            crosses: Vec::new(),
+        };
+        if lookup.identity {
+            return Ok(lookup);
+        }
+        let precedence = SourcePrecedence::load(conn)?;
+        // Rates published before the start still apply on its first days
+        let lookback = start - Duration::days(LOOKBACK_DAYS);
+        lookup.direct = PairRates::load(from_id, to_id, lookback, end, None, &precedence, conn)?;
+        let covered = start
+            .iter_days()
+            .take_while(|day| *day <= end)
+            .all(|day| lookup.direct.on(day).is_some());
+        if covered {
+            return Ok(lookup);
+        }
+        for pivot in PIVOTS {
+            let pivot_id: Option<i64> = currencies::table
+                .filter(currencies::alphabetic_code.eq(pivot))
+                .select(currencies::id)
+                .first(conn)
+                .optional()?;
+            let Some(pivot_id) = pivot_id.filter(|id| *id != from_id && *id != to_id) else {
+                continue;
+            };
+            lookup.crosses.push((
+                pivot,
+                PairRates::load(from_id, pivot_id, lookback, end, None, &precedence, conn)?,
+                PairRates::load(pivot_id, to_id, lookback, end, None, &precedence, conn)?,
+            ));
+        }
+        Ok(lookup)
+    }
+
+    /// Rate of the pair on the date or, without one, crossed through the first pivot
+    /// with both legs
+    pub(super) fn on(&self, date: NaiveDate) -> Option<Rate> {
+        if self.identity {
+            return Some(Rate::identity(date));
+        }
+        if let Some(rate) = self.direct.on(date) {
+            return Some(rate.clone());
+        }
+        self.crosses.iter().find_map(|(pivot, first, second)| {
+            Some(
+                first
+                    .on(date)?
+                    .clone()
+                    .cross(second.on(date)?.clone(), pivot),
+            )
+        })
+    }
+}
+
+/// Rate to convert amounts of one currency into another on a date. Falls back to the
+/// last rate published before it, inverts the reverse pair and crosses through EUR
+/// or USD when there's no pair between both currencies. When several sources publish
+/// the pair, the one first in [`SourcePrecedence`] is used
+pub fn exchange_rate(
+    from_id: i64,
+    to_id: i64,
+    date: NaiveDate,
+    conn: &mut PgConnection,
+) -> Result<Option<Rate>, AppError> {
+    Ok(RateLookup::load(from_id, to_id, date, date, conn)?.on(date))
+}
+
+/// Converts an amount between two currencies given by their codes, rounded to the
+/// decimals of the one it's converted into
+pub fn convert(
+    amount: &BigDecimal,
+    from: &str,
+    to: &str,
+    date: NaiveDate,
+    conn: &mut PgConnection,
+) -> Result<Conversion, AppError> {
+    let from = from.trim().to_uppercase();
+    let to = to.trim().to_uppercase();
+    let from_id = get_currency_id(&from, conn)?;
+    let to_id = get_currency_id(&to, conn)?;
+    let decimals = currency_decimals(to_id, conn)?;
+    let rate = exchange_rate(from_id, to_id, date, conn)?.ok_or(AppError::DoesNotExist)?;
+    Ok(Conversion {
+        converted: Money::new(amount * &rate.rate, to_id)
+            .round(decimals)
+            .amount,
+        amount: amount.clone(),
+        from,
+        to,
+        rate,
+    })
+}
@@ -0,0 +1,306 @@
+//! Euro foreign exchange reference rates of the European Central Bank
+use std::{path::PathBuf, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use futures_util::future::BoxFuture;
+
+use super::ingestion::{store_rates, ObservedRate};
+use crate::server::{AppError, AppState, BulkReport};
+
+pub const ECB_SOURCE: &str = "ECB";
+
+/// Every ECB rate is quoted against the euro
+const EURO: &str = "EUR";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcbFeed {
+    /// Rates of the last working day
+    Daily,
+    /// Rates of the last 90 days, enough to catch up after a few missed runs
+    LastNinetyDays,
+    /// Every rate since 1999
+    Historical,
+}
+
+impl EcbFeed {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "daily" => Some(Self::Daily),
+            "90d" => Some(Self::LastNinetyDays),
+            "historical" => Some(Self::Historical),
+            _ => None,
+        }
+    }
+
+    fn url(self) -> &'static str {
+        match self {
+            Self::Daily => "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-daily.xml",
+            Self::LastNinetyDays => {
+                "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-hist-90d.xml"
+            }
+            Self::Historical => "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-hist.xml",
+        }
+    }
+}
+
+/// Where the rates are read from, a local file is read the same way as a download
+#[derive(Debug, Clone)]
+pub enum EcbInput {
+    Feed(EcbFeed),
+    File(PathBuf),
+}
+
+fn source_error(err: impl ToString) -> AppError {
+    AppError::SourceError(format!("ECB: {}", err.to_string()))
+}
+
+fn observation(currency: &str, date: NaiveDate, rate: &str) -> Option<ObservedRate> {
+    Some(ObservedRate {
+        source: ECB_SOURCE.to_string(),
+        base: currency.trim().to_uppercase(),
+        target: EURO.to_string(),
+        date,
+        rate: BigDecimal::from_str(rate.trim()).ok()?,
+    })
+}
+
+/// `<Cube time="2024-10-11"><Cube currency="USD" rate="1.0939"/></Cube>`, the layout
+/// of the daily and historical XML files
+fn parse_xml(body: &str) -> Result<Vec<ObservedRate>, AppError> {
+    let document = roxmltree::Document::parse(body).map_err(source_error)?;
+    let mut rates = Vec::new();
+    for day in document
+        .descendants()
+        .filter(|node| node.has_tag_name("Cube"))
+    {
+        let Some(time) = day.attribute("time") else {
+            continue;
+        };
+        let date = NaiveDate::parse_from_str(time, "%Y-%m-%d").map_err(source_error)?;
+        rates.extend(
+            day.children()
+                .filter(|node| node.has_tag_name("Cube"))
+                .filter_map(|node| {
+                    observation(node.attribute("currency")?, date, node.attribute("rate")?)
+                }),
+        );
+    }
+    Ok(rates)
+}
+
+/// A column per currency and a row per day, `N/A` when there's no rate. Dates are
+/// ISO in the historical file and spelled out (11 October 2024) in the daily one
+fn parse_wide_csv(mut reader: csv::Reader<&[u8]>) -> Result<Vec<ObservedRate>, AppError> {
+    let header = reader.headers().map_err(source_error)?.clone();
+    let mut rates = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(source_error)?;
+        let Some(day) = record.get(0).filter(|day| !day.is_empty()) else {
+            continue;
+        };
+        let date = NaiveDate::parse_from_str(day, "%Y-%m-%d")
+            .or_else(|_| NaiveDate::parse_from_str(day, "%d %B %Y"))
+            .map_err(source_error)?;
+        rates.extend(
+            header
+                .iter()
+                .zip(record.iter())
+                .skip(1)
+                .filter(|(currency, rate)| !currency.is_empty() && *rate != "N/A")
+                .filter_map(|(currency, rate)| observation(currency, date, rate)),
+        );
+    }
+    Ok(rates)
+}
+
+/// SDMX CSV of the ECB data portal, an observation per row. Only daily series are
+/// kept, the portal also serves monthly and annual averages
+fn parse_sdmx_csv(mut reader: csv::Reader<&[u8]>) -> Result<Vec<ObservedRate>, AppError> {
+    let header = reader.headers().map_err(source_error)?.clone();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|column| column == name)
+            .ok_or_else(|| source_error(format!("column {name} is missing")))
+    };
+    let (frequency, currency, denominator, period, value) = (
+        column("FREQ")?,
+        column("CURRENCY")?,
+        column("CURRENCY_DENOM")?,
+        column("TIME_PERIOD")?,
+        column("OBS_VALUE")?,
+    );
+    let mut rates = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(source_error)?;
+        let field = |index: usize| record.get(index).unwrap_or_default();
+        if field(frequency) != "D" {
+            continue;
+        }
+        let Ok(date) = NaiveDate::parse_from_str(field(period), "%Y-%m-%d") else {
+            continue;
+        };
+        if let Some(mut rate) = observation(field(currency), date, field(value)) {
+            rate.target = field(denominator).to_uppercase();
+            rates.push(rate);
+        }
+    }
+    Ok(rates)
+}
+
+/// Reads any of the formats the ECB publishes its rates in
+pub fn parse_ecb(body: &str) -> Result<Vec<ObservedRate>, AppError> {
+    let body = body.trim_start_matches('\u{feff}').trim_start();
+    if body.starts_with('<') {
+        return parse_xml(body);
+    }
+    let reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(body.as_bytes());
+    if body
+        .lines()
+        .next()
+        .is_some_and(|header| header.contains("OBS_VALUE"))
+    {
+        parse_sdmx_csv(reader)
+    } else {
+        parse_wide_csv(reader)
+    }
+}
+
+async fn read_input(input: &EcbInput) -> Result<String, AppError> {
+    match input {
+        EcbInput::File(path) => tokio::fs::read_to_string(path)
+            .await
+            .map_err(|err| source_error(format!("{}: {err}", path.display()))),
+        EcbInput::Feed(feed) => reqwest::get(feed.url())
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(source_error)?
+            .text()
+            .await
+            .map_err(source_error),
+    }
+}
+
+/// Stores the rates of a feed or a file with `source = "ECB"`. Observations are
+/// numbered in the order of the file
+pub async fn ingest_ecb(state: &AppState, input: EcbInput) -> Result<BulkReport, AppError> {
+    let body = read_input(&input).await?;
+    let rates: Vec<(usize, ObservedRate)> = (1..).zip(parse_ecb(&body)?).collect();
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| store_rates(rates, conn))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+}
+
+/// Loads the rates of the last working day
+pub fn sync_ecb(state: AppState) -> BoxFuture<'static, Result<(), AppError>> {
+    Box::pin(async move {
+        let report = ingest_ecb(&state, EcbInput::Feed(EcbFeed::Daily)).await?;
+        info!(
+            "ECB rates: {} inserted, {} updated, {} rejected",
+            report.inserted, report.updated, report.rejected
+        );
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap()
+    }
+
+    fn rates(body: &str) -> Vec<(String, String, NaiveDate, BigDecimal)> {
+        parse_ecb(body)
+            .unwrap()
+            .into_iter()
+            .map(|rate| {
+                assert_eq!(rate.source, ECB_SOURCE);
+                (rate.base, rate.target, rate.date, rate.rate)
+            })
+            .collect()
+    }
+
+    fn expected(rows: &[(&str, &str, &str)]) -> Vec<(String, String, NaiveDate, BigDecimal)> {
+        rows.iter()
+            .map(|(currency, day, rate)| {
+                (
+                    currency.to_string(),
+                    EURO.to_string(),
+                    date(day),
+                    BigDecimal::from_str(rate).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    const DAILY: &[(&str, &str, &str)] = &[
+        ("USD", "2024-10-11", "1.0939"),
+        ("JPY", "2024-10-11", "163.45"),
+        ("GBP", "2024-10-11", "0.83713"),
+        ("CHF", "2024-10-11", "0.9384"),
+        ("MXN", "2024-10-11", "21.2378"),
+    ];
+
+    #[test]
+    fn parses_daily_xml() {
+        let body = include_str!("../../fixtures/exchange_rates/ecb-daily.xml");
+        assert_eq!(rates(body), expected(DAILY));
+    }
+
+    #[test]
+    fn parses_daily_csv_with_spelled_out_dates() {
+        let body = include_str!("../../fixtures/exchange_rates/ecb-daily.csv");
+        assert_eq!(rates(body), expected(DAILY));
+    }
+
+    #[test]
+    fn parses_historical_csv_skipping_missing_rates() {
+        let body = include_str!("../../fixtures/exchange_rates/ecb-hist.csv");
+        let parsed = rates(body);
+        assert_eq!(parsed.len(), 4 * 5 + 1);
+        assert_eq!(
+            parsed[..5],
+            expected(DAILY)[..],
+            "first row, without the N/A of CYP"
+        );
+        assert_eq!(
+            parsed[5..10],
+            expected(&[
+                ("USD", "2024-10-10", "1.0933"),
+                ("JPY", "2024-10-10", "162.89"),
+                ("GBP", "2024-10-10", "0.83705"),
+                ("CHF", "2024-10-10", "0.9378"),
+                ("MXN", "2024-10-10", "21.3289"),
+            ])[..]
+        );
+        assert_eq!(
+            parsed.last(),
+            expected(&[("CYP", "2007-12-31", "0.585274")]).first()
+        );
+        assert!(parsed
+            .iter()
+            .all(|(currency, _, day, _)| currency != "CYP" || *day == date("2007-12-31")));
+    }
+
+    #[test]
+    fn parses_sdmx_csv_keeping_daily_series() {
+        let body = include_str!("../../fixtures/exchange_rates/ecb-sdmx.csv");
+        assert_eq!(
+            rates(body),
+            expected(&[
+                ("USD", "2024-10-10", "1.0933"),
+                ("USD", "2024-10-11", "1.0939"),
+                ("GBP", "2024-10-11", "0.83713"),
+            ])
+        );
+    }
+}
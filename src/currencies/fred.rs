@@ -0,0 +1,287 @@
+//! Daily exchange rates of the Federal Reserve (H.10) served by FRED, the USD pairs
+//! the ECB doesn't publish
+use std::{path::PathBuf, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveDate, Utc};
+use futures_util::future::BoxFuture;
+use menva::get_env;
+use serde::Deserialize;
+
+use super::ingestion::{store_rates, ObservedRate};
+use crate::server::{AppError, AppState, BulkReport};
+
+pub const FRED_SOURCE: &str = "FRED";
+
+const OBSERVATIONS_URL: &str = "https://api.stlouisfed.org/fred/series/observations";
+
+/// Value FRED gives to the days without an observation
+const MISSING_VALUE: &str = ".";
+
+/// Days the scheduled job loads again, FRED publishes the H.10 rates once a week
+const SYNC_WINDOW_DAYS: i64 = 30;
+
+/// Series loaded when `FRED_SERIES` isn't set, with the currency each one quotes
+/// and the one it's quoted against. DEXMXUS is pesos per dollar: one USD (target) is
+/// worth the value in MXN (base)
+const KNOWN_SERIES: [(&str, &str, &str); 22] = [
+    ("DEXMXUS", "MXN", "USD"),
+    ("DEXJPUS", "JPY", "USD"),
+    ("DEXCAUS", "CAD", "USD"),
+    ("DEXCHUS", "CNY", "USD"),
+    ("DEXSZUS", "CHF", "USD"),
+    ("DEXINUS", "INR", "USD"),
+    ("DEXKOUS", "KRW", "USD"),
+    ("DEXBZUS", "BRL", "USD"),
+    ("DEXSDUS", "SEK", "USD"),
+    ("DEXNOUS", "NOK", "USD"),
+    ("DEXDNUS", "DKK", "USD"),
+    ("DEXHKUS", "HKD", "USD"),
+    ("DEXSIUS", "SGD", "USD"),
+    ("DEXTAUS", "TWD", "USD"),
+    ("DEXTHUS", "THB", "USD"),
+    ("DEXMAUS", "MYR", "USD"),
+    ("DEXSFUS", "ZAR", "USD"),
+    ("DEXSLUS", "LKR", "USD"),
+    ("DEXUSEU", "USD", "EUR"),
+    ("DEXUSUK", "USD", "GBP"),
+    ("DEXUSAL", "USD", "AUD"),
+    ("DEXUSNZ", "USD", "NZD"),
+];
+
+/// A FRED series and the pair it quotes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FredSeries {
+    pub id: String,
+    pub base: String,
+    pub target: String,
+}
+
+impl FredSeries {
+    /// A known series id as `DEXMXUS`, or any series with its pair as
+    /// `DEXMXUS=MXN/USD`
+    pub fn parse(spec: &str) -> Result<Self, AppError> {
+        let spec = spec.trim();
+        if let Some((id, pair)) = spec.split_once('=') {
+            let Some((base, target)) = pair.split_once('/') else {
+                return Err(AppError::ValidationError(format!(
+                    "FRED series {spec} must look like SERIES=BASE/TARGET"
+                )));
+            };
+            return Ok(Self {
+                id: id.trim().to_uppercase(),
+                base: base.trim().to_uppercase(),
+                target: target.trim().to_uppercase(),
+            });
+        }
+        KNOWN_SERIES
+            .iter()
+            .find(|(id, _, _)| id.eq_ignore_ascii_case(spec))
+            .map(|(id, base, target)| Self {
+                id: id.to_string(),
+                base: base.to_string(),
+                target: target.to_string(),
+            })
+            .ok_or_else(|| {
+                AppError::ValidationError(format!(
+                    "Unknown FRED series {spec}, give its pair as {spec}=BASE/TARGET"
+                ))
+            })
+    }
+
+    /// Series of `FRED_SERIES`, comma separated, or every known one
+    pub fn configured() -> Result<Vec<Self>, AppError> {
+        let configured = get_env("FRED_SERIES");
+        if configured.trim().is_empty() {
+            return KNOWN_SERIES
+                .iter()
+                .map(|(id, _, _)| Self::parse(id))
+                .collect();
+        }
+        configured
+            .split(',')
+            .filter(|spec| !spec.trim().is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+}
+
+/// Where the observations are read from. A file holds the response of the
+/// observations endpoint for one series
+#[derive(Debug, Clone)]
+pub enum FredInput {
+    Api {
+        series: Vec<FredSeries>,
+        since: Option<NaiveDate>,
+    },
+    File {
+        series: FredSeries,
+        path: PathBuf,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct Observations {
+    observations: Vec<Observation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Observation {
+    date: NaiveDate,
+    value: String,
+}
+
+fn source_error(err: impl ToString) -> AppError {
+    AppError::SourceError(format!("FRED: {}", err.to_string()))
+}
+
+/// Reads the JSON of the observations endpoint, leaving out the missing days
+pub fn parse_fred(series: &FredSeries, body: &str) -> Result<Vec<ObservedRate>, AppError> {
+    let observations: Observations = serde_json::from_str(body).map_err(source_error)?;
+    Ok(observations
+        .observations
+        .into_iter()
+        .filter(|observation| observation.value.trim() != MISSING_VALUE)
+        .filter_map(|observation| {
+            Some(ObservedRate {
+                source: FRED_SOURCE.to_string(),
+                base: series.base.clone(),
+                target: series.target.clone(),
+                date: observation.date,
+                rate: BigDecimal::from_str(observation.value.trim()).ok()?,
+            })
+        })
+        .collect())
+}
+
+async fn fetch(series: &FredSeries, since: Option<NaiveDate>) -> Result<String, AppError> {
+    let api_key = get_env("FRED_API_KEY");
+    if api_key.is_empty() {
+        return Err(source_error("FRED_API_KEY isn't set"));
+    }
+    let mut query = vec![
+        ("series_id", series.id.clone()),
+        ("api_key", api_key),
+        ("file_type", "json".to_string()),
+    ];
+    if let Some(since) = since {
+        query.push(("observation_start", since.to_string()));
+    }
+    reqwest::Client::new()
+        .get(OBSERVATIONS_URL)
+        .query(&query)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| source_error(err.without_url()))?
+        .text()
+        .await
+        .map_err(source_error)
+}
+
+async fn read_input(input: &FredInput) -> Result<Vec<ObservedRate>, AppError> {
+    match input {
+        FredInput::File { series, path } => {
+            let body = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|err| source_error(format!("{}: {err}", path.display())))?;
+            parse_fred(series, &body)
+        }
+        FredInput::Api { series, since } => {
+            let mut rates = Vec::new();
+            for series in series {
+                rates.extend(parse_fred(series, &fetch(series, *since).await?)?);
+            }
+            Ok(rates)
+        }
+    }
+}
+
+/// Stores the observations of the series with `source = "FRED"`. Observations are
+/// numbered in the order they're read, series after series
+pub async fn ingest_fred(state: &AppState, input: FredInput) -> Result<BulkReport, AppError> {
+    let rates: Vec<(usize, ObservedRate)> = (1..).zip(read_input(&input).await?).collect();
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| store_rates(rates, conn))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+}
+
+/// Loads the last weeks of the configured series, skipped when there's no API key
+pub fn sync_fred(state: AppState) -> BoxFuture<'static, Result<(), AppError>> {
+    Box::pin(async move {
+        if get_env("FRED_API_KEY").is_empty() {
+            return Ok(());
+        }
+        let input = FredInput::Api {
+            series: FredSeries::configured()?,
+            since: Some(Utc::now().date_naive() - Duration::days(SYNC_WINDOW_DAYS)),
+        };
+        let report = ingest_fred(&state, input).await?;
+        info!(
+            "FRED rates: {} inserted, {} updated, {} rejected",
+            report.inserted, report.updated, report.rejected
+        );
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn parses_observations_leaving_out_missing_days() {
+        let series = FredSeries::parse("DEXMXUS").unwrap();
+        let body = include_str!("../../fixtures/exchange_rates/fred-DEXMXUS.json");
+        let rates = parse_fred(&series, body).unwrap();
+
+        assert_eq!(rates.len(), 9);
+        assert!(rates
+            .iter()
+            .all(|rate| rate.source == FRED_SOURCE && rate.base == "MXN" && rate.target == "USD"));
+        assert!(rates.iter().all(|rate| rate.date != date("2024-10-14")));
+        assert_eq!(rates[0].date, date("2024-10-01"));
+        assert_eq!(rates[0].rate, BigDecimal::from_str("19.7010").unwrap());
+        assert_eq!(rates[8].date, date("2024-10-11"));
+        assert_eq!(rates[8].rate, BigDecimal::from_str("19.3270").unwrap());
+    }
+
+    #[test]
+    fn parses_series_with_their_pair() {
+        assert_eq!(
+            FredSeries::parse("X=ABC/DEF").unwrap(),
+            FredSeries {
+                id: "X".to_string(),
+                base: "ABC".to_string(),
+                target: "DEF".to_string(),
+            }
+        );
+        assert_eq!(
+            FredSeries::parse(" dexjpus ").unwrap(),
+            FredSeries {
+                id: "DEXJPUS".to_string(),
+                base: "JPY".to_string(),
+                target: "USD".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_series_and_pairs_without_target() {
+        assert!(matches!(
+            FredSeries::parse("DEXXXUS"),
+            Err(AppError::ValidationError(_))
+        ));
+        assert!(matches!(
+            FredSeries::parse("X=ABC"),
+            Err(AppError::ValidationError(_))
+        ));
+    }
+}
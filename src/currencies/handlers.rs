@@ -1,36 +1,76 @@
+use super::{
+    admin::{
+        create_currency, delete_currency, import_iso4217_list, link_currency_country,
+        list_currency_countries, unlink_currency_country, update_currency, CountryLink,
+        CountryLinkPayload, CurrencyPayload,
+    },
+    check_rate, convert, deserialize_rate, get_currency_id,
+    ingestion::{store_rates, ObservedRate},
+    series::{exchange_rate_series, PairSeries, SeriesInterval, SeriesPoint},
+    sources::{check_consistency, RateDiscrepancy, SourceRate},
+    Conversion, Rate,
+};
 use crate::{
-    db::schema::{currencies, exchange_rates},
-    server::{AppError, AppResult, AppState},
+    db::{
+        schema::{currencies, exchange_rates},
+        Paginate,
+    },
+    server::{
+        parse_rows, AppError, AppResult, AppState, BulkFormat, BulkReport, JWTUserRequest,
+        RejectedRow,
+    },
+};
+use axum::{
+    body::Bytes,
+    extract::Query,
+    http::HeaderMap,
+    routing::{get, post, put},
+    Extension, Json, Router,
 };
-use axum::{routing::get, Json, Router};
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDate, Utc};
 
 use diesel::prelude::*;
 
 use serde::{Deserialize, Serialize};
-use utoipa::{self, OpenApi, ToResponse, ToSchema};
+use utoipa::{self, IntoParams, OpenApi, ToResponse, ToSchema};
 
 #[derive(OpenApi)]
 #[openapi(
-    paths(list_exchange_rates, list_currencies),
-    components(schemas(Currency, ExchangeRate),
-    responses(Currency, ExchangeRate)),
+    paths(list_exchange_rates, list_currencies, super::admin::create_currency, super::admin::update_currency, super::admin::delete_currency, super::admin::import_iso4217_list, super::admin::list_currency_countries, super::admin::link_currency_country, super::admin::unlink_currency_country, convert_amount, bulk_upsert_exchange_rates, super::series::exchange_rate_series, super::sources::check_consistency),
+    components(schemas(Currency, CurrencyPayload, CountryLink, CountryLinkPayload, ExchangeRate, ExchangeRatesResponse, ExchangeRatePayload, Conversion, Rate, BulkReport, RejectedRow, PairSeries, SeriesPoint, SeriesInterval, RateDiscrepancy, SourceRate),
+    responses(Currency, CountryLink, ExchangeRate, ExchangeRatesResponse, Conversion, PairSeries, RateDiscrepancy)),
     security(("token_jwt" = []))
 )]
 pub struct ApiDoc;
 
 pub fn routes(_state: AppState) -> Router<AppState> {
     Router::new()
-        .route("/currencies", get(list_currencies))
+        .route("/currencies", get(list_currencies).post(create_currency))
+        .route("/currencies/iso4217", post(import_iso4217_list))
+        .route(
+            "/currencies/:code",
+            put(update_currency).delete(delete_currency),
+        )
+        .route("/currencies/:code/countries", get(list_currency_countries))
+        .route(
+            "/currencies/:code/countries/:country",
+            put(link_currency_country).delete(unlink_currency_country),
+        )
+        .route("/convert", get(convert_amount))
         .route(
             "/exchange_rates",
             get(list_exchange_rates).post(create_exchange_rate),
         )
+        .route("/exchange_rates/bulk", post(bulk_upsert_exchange_rates))
+        .route("/exchange_rates/series", get(exchange_rate_series))
+        .route("/exchange_rates/consistency", get(check_consistency))
 }
 
 #[derive(Queryable, Serialize, Selectable, Deserialize, ToSchema, ToResponse)]
 #[diesel(table_name = currencies)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
-struct Currency {
+pub(super) struct Currency {
     id: i64,
     name: String,
     alphabetic_code: String,
@@ -45,23 +85,67 @@ struct Currency {
 struct ExchangeRate {
     base_id: i64,
     target_id: i64,
-    conversion_rate: String,
-    precision: i32,
-    scale: i32,
+    conversion_rate: BigDecimal,
     date: chrono::NaiveDate,
+    source: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+struct ExchangeRatesQuery {
+    /// Currency code
+    base: Option<String>,
+    /// Currency code
+    target: Option<String>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    source: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, ToResponse)]
+struct ExchangeRatesResponse {
+    data: Vec<ExchangeRate>,
+    total_pages: i64,
+}
+
+/// Id of a currency given in a filter, an unknown code is a client error
+pub(super) fn filter_currency_id(code: &str, conn: &mut PgConnection) -> Result<i64, AppError> {
+    get_currency_id(code.trim(), conn).map_err(|err| match err {
+        AppError::DoesNotExist => AppError::ValidationError(format!("Unknown currency {code}")),
+        err => err,
+    })
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
 struct ExchangeRatePayload {
     base: String,
     target: String,
-    conversion_rate: String,
-    precision: i32,
-    scale: i32,
+    /// How many `base` units one `target` unit is worth. Send it as a string to keep
+    /// more than 15 significant digits
+    #[serde(deserialize_with = "deserialize_rate")]
+    #[schema(value_type = String)]
+    conversion_rate: BigDecimal,
     date: chrono::NaiveDate,
     source: String,
 }
 
+impl ExchangeRatePayload {
+    fn validate(&self) -> Result<(), AppError> {
+        if self.base.trim().eq_ignore_ascii_case(self.target.trim()) {
+            return Err(AppError::ValidationError(
+                "base and target must be different currencies".into(),
+            ));
+        }
+        if self.date > Utc::now().date_naive() {
+            return Err(AppError::ValidationError(
+                "date can't be in the future".into(),
+            ));
+        }
+        check_rate(&self.conversion_rate).map_err(AppError::ValidationError)
+    }
+}
+
 #[utoipa::path(
     post,
     path = "exchange_rates",
@@ -76,22 +160,14 @@ async fn create_exchange_rate(
     state: AppState,
     Json(payload): Json<ExchangeRatePayload>,
 ) -> AppResult<ExchangeRate> {
+    payload.validate()?;
     Ok(Json(
         state
             .db_write()
             .await?
             .interact(move |conn| {
-                let target_id: i64 = currencies::table
-                    .filter(currencies::alphabetic_code.eq(payload.target))
-                    .select(currencies::id)
-                    .first(conn)
-                    .map_err(AppError::DatabaseQueryError)?;
-
-                let base_id: i64 = currencies::table
-                    .filter(currencies::alphabetic_code.eq(payload.base))
-                    .select(currencies::id)
-                    .first(conn)
-                    .map_err(AppError::DatabaseQueryError)?;
+                let target_id = get_currency_id(payload.target.trim(), conn)?;
+                let base_id = get_currency_id(payload.base.trim(), conn)?;
 
                 diesel::insert_into(exchange_rates::table)
                     .values((
@@ -99,8 +175,6 @@ async fn create_exchange_rate(
                         exchange_rates::base_id.eq(base_id),
                         exchange_rates::target_id.eq(target_id),
                         exchange_rates::date.eq(payload.date),
-                        exchange_rates::precision.eq(payload.precision),
-                        exchange_rates::scale.eq(payload.scale),
                         exchange_rates::source.eq(payload.source),
                     ))
                     .returning(ExchangeRate::as_returning())
@@ -112,24 +186,134 @@ async fn create_exchange_rate(
     ))
 }
 
+#[utoipa::path(
+    post,
+    path = "exchange_rates/bulk",
+    request_body(content = String, description = "Exchange rates as a JSON array, NDJSON or CSV with a header line, each with base, target, conversion_rate, date and source. A rate replaces the one of the same pair, date and source.", content_type = "application/json"),
+    responses(
+        (status = 200, body = BulkReport, description = "How many exchange rates were inserted, updated or rejected and why, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn bulk_upsert_exchange_rates(
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<BulkReport> {
+    current_user.require_staff()?;
+    let format = BulkFormat::from_headers(&headers)?;
+    let mut report = BulkReport::default();
+    let mut rates = Vec::new();
+    for (number, row) in parse_rows::<ExchangeRatePayload>(format, &body)? {
+        let payload = match row {
+            Ok(payload) => payload,
+            Err(reason) => {
+                report.reject(number, reason);
+                continue;
+            }
+        };
+        if let Err(err) = payload.validate() {
+            report.reject(number, err);
+            continue;
+        }
+        rates.push((
+            number,
+            ObservedRate {
+                source: payload.source,
+                base: payload.base,
+                target: payload.target,
+                date: payload.date,
+                rate: payload.conversion_rate,
+            },
+        ));
+    }
+    let stored = state
+        .db_write()
+        .await?
+        .interact(move |conn| store_rates(rates, conn))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)??;
+    report.inserted = stored.inserted;
+    report.updated = stored.updated;
+    report.rejected += stored.rejected;
+    report.errors.extend(stored.errors);
+    report.errors.sort_by_key(|error| error.row);
+    Ok(Json(report))
+}
+
 #[utoipa::path(
     get,
     path = "exchange_rates",
+    params(ExchangeRatesQuery),
     responses(
-            (status = 200, body = Vec<ExchangeRate>, description = "A paginated result of exchange_rates with key information"),
+            (status = 200, body = ExchangeRatesResponse, description = "A paginated result of exchange_rates with key information, newest first"),
             (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
             (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
         )
 )]
-async fn list_exchange_rates(state: AppState) -> AppResult<Vec<ExchangeRate>> {
+async fn list_exchange_rates(
+    Query(query_params): Query<ExchangeRatesQuery>,
+    state: AppState,
+) -> AppResult<ExchangeRatesResponse> {
+    let (data, total_pages) = state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let mut query = exchange_rates::table.into_boxed();
+            if let Some(base) = query_params.base {
+                query = query.filter(exchange_rates::base_id.eq(filter_currency_id(&base, conn)?));
+            }
+            if let Some(target) = query_params.target {
+                query =
+                    query.filter(exchange_rates::target_id.eq(filter_currency_id(&target, conn)?));
+            }
+            if let Some(from) = query_params.from {
+                query = query.filter(exchange_rates::date.ge(from));
+            }
+            if let Some(to) = query_params.to {
+                query = query.filter(exchange_rates::date.le(to));
+            }
+            if let Some(source) = query_params.source {
+                query = query.filter(exchange_rates::source.eq(source));
+            }
+            query
+                .order((
+                    exchange_rates::date.desc(),
+                    exchange_rates::base_id,
+                    exchange_rates::target_id,
+                ))
+                .select(ExchangeRate::as_select())
+                .paginate(query_params.page.unwrap_or(1))
+                .per_page(query_params.per_page.unwrap_or(25))
+                .load_and_count_pages::<ExchangeRate>(conn)
+                .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)??;
+
+    Ok(Json(ExchangeRatesResponse { data, total_pages }))
+}
+
+#[utoipa::path(
+    get,
+    path = "currencies",
+    responses(
+            (status = 200, body = Vec<Currency>, description = "A paginated result of currencies with key information"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn list_currencies(state: AppState) -> AppResult<Vec<Currency>> {
     Ok(Json(
         state
             .db_write()
             .await?
             .interact(move |conn| {
-                exchange_rates::table
-                    .select(ExchangeRate::as_select())
-                    .load::<ExchangeRate>(conn)
+                currencies::table
+                    .select(Currency::as_select())
+                    .load::<Currency>(conn)
                     .map_err(AppError::DatabaseQueryError)
             })
             .await
@@ -137,25 +321,45 @@ async fn list_exchange_rates(state: AppState) -> AppResult<Vec<ExchangeRate>> {
     ))
 }
 
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct ConvertQuery {
+    /// Currency code of the amount
+    pub from: String,
+    /// Currency code to convert into
+    pub to: String,
+    #[param(value_type = String)]
+    pub amount: BigDecimal,
+    /// Today by default
+    pub date: Option<NaiveDate>,
+}
+
 #[utoipa::path(
     get,
-    path = "currencies",
+    path = "convert",
+    params(ConvertQuery),
     responses(
-            (status = 200, body = Vec<Currency>, description = "A paginated result of currencies with key information"),
+            (status = 200, body = Conversion, description = "Converts an amount with the exchange rate of the date or the last one before it"),
             (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
             (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
         )
 )]
-async fn list_currencies(state: AppState) -> AppResult<Vec<Currency>> {
+async fn convert_amount(
+    state: AppState,
+    Query(query_params): Query<ConvertQuery>,
+) -> AppResult<Conversion> {
+    let date = query_params.date.unwrap_or(Utc::now().date_naive());
     Ok(Json(
         state
             .db_write()
             .await?
             .interact(move |conn| {
-                currencies::table
-                    .select(Currency::as_select())
-                    .load::<Currency>(conn)
-                    .map_err(AppError::DatabaseQueryError)
+                convert(
+                    &query_params.amount,
+                    &query_params.from,
+                    &query_params.to,
+                    date,
+                    conn,
+                )
             })
             .await
             .map_err(AppError::DatabaseConnectionInteractError)??,
@@ -0,0 +1,172 @@
+//! Storage of the exchange rates published by external sources
+use std::collections::{HashMap, HashSet};
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use diesel::{pg::upsert::excluded, prelude::*};
+
+use super::{check_rate, get_currency_id};
+use crate::{
+    db::schema::exchange_rates,
+    server::{AppError, BulkReport},
+};
+
+/// Rows upserted per transaction
+const BATCH_SIZE: usize = 1000;
+
+/// What `exchange_rates` is unique on, (base, target, date, source)
+type RateKey = (i64, i64, NaiveDate, String);
+
+/// A rate as a source publishes it: one `target` unit is worth `rate` `base` units
+#[derive(Debug, Clone)]
+pub struct ObservedRate {
+    pub source: String,
+    pub base: String,
+    pub target: String,
+    pub date: NaiveDate,
+    pub rate: BigDecimal,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = exchange_rates)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct NewExchangeRate {
+    base_id: i64,
+    target_id: i64,
+    date: NaiveDate,
+    source: String,
+    conversion_rate: BigDecimal,
+}
+
+/// Looks the currency up once per code, None when it's unknown
+fn currency_id(
+    cache: &mut HashMap<String, Option<i64>>,
+    code: &str,
+    conn: &mut PgConnection,
+) -> Result<Option<i64>, AppError> {
+    let code = code.trim().to_uppercase();
+    if let Some(id) = cache.get(&code) {
+        return Ok(*id);
+    }
+    let id = match get_currency_id(&code, conn) {
+        Err(AppError::DoesNotExist) => None,
+        result => Some(result?),
+    };
+    cache.insert(code, id);
+    Ok(id)
+}
+
+/// Inserts the rates or updates the ones their source already published, so loading
+/// the same file twice leaves the table as it was. Rows are numbered from 1 in the
+/// report, each batch of rows is stored in its own transaction.
+pub fn store_rates(
+    rates: Vec<(usize, ObservedRate)>,
+    conn: &mut PgConnection,
+) -> Result<BulkReport, AppError> {
+    let mut report = BulkReport::default();
+    let mut currencies: HashMap<String, Option<i64>> = HashMap::new();
+    // The last row of a source, pair and date wins
+    let mut rows: HashMap<RateKey, (usize, NewExchangeRate)> = HashMap::new();
+
+    for (number, rate) in rates {
+        if let Err(reason) = check_rate(&rate.rate) {
+            report.reject(number, reason);
+            continue;
+        }
+        let Some(base_id) = currency_id(&mut currencies, &rate.base, conn)? else {
+            report.reject(number, format!("Unknown currency {}", rate.base));
+            continue;
+        };
+        let Some(target_id) = currency_id(&mut currencies, &rate.target, conn)? else {
+            report.reject(number, format!("Unknown currency {}", rate.target));
+            continue;
+        };
+        let source = rate.source.trim().to_string();
+        if source.is_empty() {
+            report.reject(number, "source is missing");
+            continue;
+        }
+        if base_id == target_id {
+            report.reject(number, "base and target must be different currencies");
+            continue;
+        }
+        let row = NewExchangeRate {
+            base_id,
+            target_id,
+            date: rate.date,
+            source: source.clone(),
+            conversion_rate: rate.rate,
+        };
+        let key = (base_id, target_id, rate.date, source);
+        if let Some((replaced, _)) = rows.insert(key, (number, row)) {
+            report.reject(
+                replaced,
+                format!(
+                    "{}/{} {} from {} is repeated in a later row",
+                    rate.base, rate.target, rate.date, rate.source
+                ),
+            );
+        }
+    }
+
+    let mut rows: Vec<NewExchangeRate> = rows.into_values().map(|(_, row)| row).collect();
+    rows.sort_by(|a, b| {
+        (a.date, a.base_id, a.target_id, &a.source).cmp(&(
+            b.date,
+            b.base_id,
+            b.target_id,
+            &b.source,
+        ))
+    });
+    for batch in rows.chunks(BATCH_SIZE) {
+        let (Some(first), Some(last)) = (batch.first(), batch.last()) else {
+            continue;
+        };
+        let sources: HashSet<&str> = batch.iter().map(|row| row.source.as_str()).collect();
+        let updated = conn.transaction(|conn| {
+            let existing: HashSet<RateKey> = exchange_rates::table
+                .filter(exchange_rates::source.eq_any(sources))
+                .filter(exchange_rates::date.between(first.date, last.date))
+                .select((
+                    exchange_rates::base_id,
+                    exchange_rates::target_id,
+                    exchange_rates::date,
+                    exchange_rates::source.assume_not_null(),
+                ))
+                .load::<RateKey>(conn)?
+                .into_iter()
+                .collect();
+            diesel::insert_into(exchange_rates::table)
+                .values(batch)
+                .on_conflict((
+                    exchange_rates::base_id,
+                    exchange_rates::target_id,
+                    exchange_rates::date,
+                    exchange_rates::source,
+                ))
+                .do_update()
+                .set((
+                    exchange_rates::conversion_rate.eq(excluded(exchange_rates::conversion_rate)),
+                    exchange_rates::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(conn)?;
+            Ok::<_, diesel::result::Error>(
+                batch
+                    .iter()
+                    .filter(|row| {
+                        existing.contains(&(
+                            row.base_id,
+                            row.target_id,
+                            row.date,
+                            row.source.clone(),
+                        ))
+                    })
+                    .count(),
+            )
+        })?;
+        report.updated += updated;
+        report.inserted += batch.len() - updated;
+    }
+    report.errors.sort_by_key(|error| error.row);
+    Ok(report)
+}
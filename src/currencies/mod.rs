@@ -1,5 +1,21 @@
+mod admin;
+mod convert;
+mod ecb;
+mod fred;
 mod handlers;
+mod ingestion;
+mod money;
 mod querysets;
+mod rates;
+mod series;
+mod sources;
 
+pub use admin::ingest_iso4217;
+pub use convert::{convert, exchange_rate, Conversion, Rate};
+pub use ecb::{ingest_ecb, sync_ecb, EcbFeed, EcbInput};
+pub use fred::{ingest_fred, sync_fred, FredInput, FredSeries};
 pub use handlers::{routes, ApiDoc};
-pub use querysets::get_currency_from_country;
+pub use money::{currency_decimals, Money};
+pub use querysets::{get_currency_from_country, get_currency_id};
+pub use rates::{check_rate, deserialize_rate, RateSeries};
+pub use sources::SourcePrecedence;
@@ -0,0 +1,113 @@
+//! Amounts that know their currency
+use bigdecimal::{BigDecimal, RoundingMode};
+use diesel::{
+    deserialize,
+    dsl::Eq,
+    pg::Pg,
+    prelude::*,
+    sql_types::{BigInt, Numeric},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    db::schema::{accounts, currencies},
+    server::AppError,
+};
+
+/// Digits of the minor unit of the currency, 2 for the cents of the euro
+pub fn currency_decimals(currency_id: i64, conn: &mut PgConnection) -> Result<i32, AppError> {
+    currencies::table
+        .find(currency_id)
+        .select(currencies::decimals)
+        .first(conn)
+        .optional()?
+        .ok_or(AppError::DoesNotExist)
+}
+
+/// An amount in a currency. Amounts of different currencies can't be added, they
+/// have to be converted first.
+///
+/// Loads from any pair of an amount and a currency column, as in
+/// `.select((accounts::amount, accounts::currency_id))`, and inserts into `accounts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Money {
+    pub amount: BigDecimal,
+    /// Id of the currency, `currencyId` as accounts have always returned it
+    #[serde(rename = "currencyId", alias = "currency_id")]
+    pub currency: i64,
+}
+
+impl Money {
+    pub fn new(amount: BigDecimal, currency: i64) -> Self {
+        Self { amount, currency }
+    }
+
+    /// Rounds half to even to the given digits of the minor unit
+    pub fn round(self, decimals: i32) -> Self {
+        Self {
+            amount: self
+                .amount
+                .with_scale_round(i64::from(decimals), RoundingMode::HalfEven),
+            currency: self.currency,
+        }
+    }
+
+    /// Rounds to the decimals of its currency
+    pub fn rounded(self, conn: &mut PgConnection) -> Result<Self, AppError> {
+        let decimals = currency_decimals(self.currency, conn)?;
+        Ok(self.round(decimals))
+    }
+
+    fn same_currency(&self, other: &Self) -> Result<(), AppError> {
+        if self.currency != other.currency {
+            return Err(AppError::CurrencyMismatch(self.currency, other.currency));
+        }
+        Ok(())
+    }
+
+    pub fn checked_add(&self, other: &Self) -> Result<Self, AppError> {
+        self.same_currency(other)?;
+        Ok(Self::new(&self.amount + &other.amount, self.currency))
+    }
+}
+
+/// Drops the currency, for the tables where it's the one of the account
+impl From<Money> for BigDecimal {
+    fn from(money: Money) -> Self {
+        money.amount
+    }
+}
+
+impl Queryable<(Numeric, BigInt), Pg> for Money {
+    type Row = (BigDecimal, i64);
+
+    fn build((amount, currency): Self::Row) -> deserialize::Result<Self> {
+        Ok(Self::new(amount, currency))
+    }
+}
+
+type AccountMoney = (
+    Eq<accounts::amount, BigDecimal>,
+    Eq<accounts::currency_id, i64>,
+);
+
+impl Insertable<accounts::table> for Money {
+    type Values = <AccountMoney as Insertable<accounts::table>>::Values;
+
+    fn values(self) -> Self::Values {
+        (
+            accounts::amount.eq(self.amount),
+            accounts::currency_id.eq(self.currency),
+        )
+            .values()
+    }
+}
+
+impl Insertable<accounts::table> for &Money {
+    type Values = <AccountMoney as Insertable<accounts::table>>::Values;
+
+    fn values(self) -> Self::Values {
+        self.clone().values()
+    }
+}
@@ -1,14 +1,32 @@
-use crate::{db::schema::currencies_countries_m2m, server::AppError};
+use crate::{
+    db::schema::{currencies, currencies_countries_m2m},
+    server::AppError,
+};
 
 use diesel::prelude::*;
 
+/// Primary currency of the country, or the one linked first when none is flagged
 pub fn get_currency_from_country(
     country_id: i64,
     conn: &mut PgConnection,
 ) -> Result<i64, AppError> {
     currencies_countries_m2m::table
         .filter(currencies_countries_m2m::country_id.eq(country_id))
+        .order((
+            currencies_countries_m2m::is_primary.desc(),
+            currencies_countries_m2m::id,
+        ))
         .select(currencies_countries_m2m::currency_id)
         .first(conn)
         .map_err(AppError::DatabaseQueryError)
 }
+
+pub fn get_currency_id(alphabetic_code: &str, conn: &mut PgConnection) -> Result<i64, AppError> {
+    currencies::table
+        .filter(currencies::alphabetic_code.eq(alphabetic_code.to_uppercase()))
+        .select(currencies::id)
+        .first(conn)
+        .optional()
+        .map_err(AppError::DatabaseQueryError)?
+        .ok_or(AppError::DoesNotExist)
+}
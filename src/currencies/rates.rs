@@ -0,0 +1,145 @@
+use std::{fmt, str::FromStr};
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use serde::{de, Deserializer};
+
+use super::convert::RateLookup;
+
+/// Bounds a stored rate has to fall within, wide enough for the reverse pair of any
+/// currency still traded
+const MIN_RATE: &str = "0.00000001";
+const MAX_RATE: &str = "100000000";
+
+/// Rejects rates that are zero, negative or too far off to be a real quote
+pub fn check_rate(rate: &BigDecimal) -> Result<(), String> {
+    let min = BigDecimal::from_str(MIN_RATE).unwrap_or_default();
+    let max = BigDecimal::from_str(MAX_RATE).unwrap_or_default();
+    if *rate < min || *rate > max {
+        return Err(format!(
+            "conversion_rate {rate} is out of range, it must be between {MIN_RATE} and {MAX_RATE}"
+        ));
+    }
+    Ok(())
+}
+
+struct RateVisitor;
+
+impl<'de> de::Visitor<'de> for RateVisitor {
+    type Value = BigDecimal;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a decimal number, as a string to keep every digit")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<BigDecimal, E> {
+        BigDecimal::from_str(value.trim()).map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<BigDecimal, E> {
+        Ok(BigDecimal::from(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<BigDecimal, E> {
+        Ok(BigDecimal::from(value))
+    }
+
+    /// JSON numbers and CSV fields that look like one arrive as f64, the shortest text
+    /// that reads back as the same f64 is the rate as written, not its binary expansion
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<BigDecimal, E> {
+        BigDecimal::from_str(&value.to_string()).map_err(E::custom)
+    }
+}
+
+/// Deserializes a rate keeping the digits it was sent with, `BigDecimal`'s own
+/// implementation stores every digit of the f64 a JSON number goes through
+pub fn deserialize_rate<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BigDecimal, D::Error> {
+    deserializer.deserialize_any(RateVisitor)
+}
+
+/// Daily rates to convert amounts from one currency into another, as floats for
+/// figures that are floats themselves.
+///
+/// `exchange_rates` stores rates the way the ECB publishes them: `conversion_rate` is
+/// how many `base` units one `target` unit is worth (base USD, target EUR, 1.08). A
+/// row with `base` = to and `target` = from gives the multiplier directly, the
+/// reverse pair gives its inverse. Rates are looked up like [`super::exchange_rate`]
+/// does, crossing through EUR or USD on the days without a rate for the pair
+pub struct RateSeries {
+    lookup: RateLookup,
+}
+
+impl RateSeries {
+    pub fn load(
+        from_id: i64,
+        to_id: i64,
+        start: NaiveDate,
+        end: NaiveDate,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Self> {
+        Ok(Self {
+            lookup: RateLookup::load(from_id, to_id, start, end, conn)?,
+        })
+    }
+
+    /// Rate of the date or of the closest day before it
+    pub fn on(&self, date: NaiveDate) -> Option<f64> {
+        self.lookup.on(date).and_then(|rate| rate.rate.to_f64())
+    }
+
+    /// Mean of the rates published between both dates, the rate of the end date when
+    /// there are none
+    pub fn average(&self, start: NaiveDate, end: NaiveDate) -> Option<f64> {
+        let rates: Vec<f64> = start
+            .iter_days()
+            .take_while(|day| *day <= end)
+            .filter_map(|day| self.lookup.on(day).filter(|rate| rate.date == day))
+            .filter_map(|rate| rate.rate.to_f64())
+            .collect();
+        if rates.is_empty() {
+            return self.on(end);
+        }
+        Some(rates.iter().sum::<f64>() / rates.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Row {
+        #[serde(deserialize_with = "deserialize_rate")]
+        rate: BigDecimal,
+    }
+
+    fn rate(digits: &str) -> BigDecimal {
+        BigDecimal::from_str(digits).unwrap()
+    }
+
+    #[test]
+    fn keeps_the_digits_of_json_rates() {
+        let number: Row = serde_json::from_str(r#"{"rate": 1.0939}"#).unwrap();
+        assert_eq!(number.rate.to_string(), "1.0939");
+        let text: Row = serde_json::from_str(r#"{"rate": "1.09390000000000000001"}"#).unwrap();
+        assert_eq!(text.rate, rate("1.09390000000000000001"));
+        let integer: Row = serde_json::from_str(r#"{"rate": 150}"#).unwrap();
+        assert_eq!(integer.rate, rate("150"));
+        assert!(serde_json::from_str::<Row>(r#"{"rate": "abc"}"#).is_err());
+    }
+
+    #[test]
+    fn keeps_the_digits_of_csv_rates() {
+        let rows: Vec<Row> = csv::Reader::from_reader("rate\n1.0939\n0.000123\n".as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows[0].rate.to_string(), "1.0939");
+        assert_eq!(rows[1].rate, rate("0.000123"));
+    }
+}
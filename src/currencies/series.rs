@@ -0,0 +1,230 @@
+//! Dense exchange-rate series for charts, one point per day, week or month
+use axum::{extract::Query, Json};
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use chrono::{Datelike, Duration, NaiveDate, Utc, Weekday};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{self, IntoParams, ToResponse, ToSchema};
+
+use super::{convert::PairRates, handlers::filter_currency_id, SourcePrecedence};
+use crate::server::{AppError, AppResult, AppState};
+
+/// Pairs and days a single request can ask for
+const MAX_PAIRS: usize = 10;
+const MAX_DAYS: i64 = 366 * 20;
+
+/// Days before the start loaded to forward-fill its first days
+const FILL_LOOKBACK_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SeriesInterval {
+    #[default]
+    Daily,
+    /// Mean of the days of each ISO week, dated on its Monday
+    Weekly,
+    /// Mean of the days of each month, dated on its first day
+    Monthly,
+}
+
+impl SeriesInterval {
+    fn bucket(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Daily => date,
+            Self::Weekly => date - Duration::days(i64::from(date.weekday().num_days_from_monday())),
+            Self::Monthly => date.with_day(1).unwrap_or(date),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct SeriesQuery {
+    /// Comma separated pairs of currency codes as BASE/TARGET, like USD/EUR
+    pub pairs: String,
+    /// A year before `to` by default
+    pub from: Option<NaiveDate>,
+    /// Today by default
+    pub to: Option<NaiveDate>,
+    /// Only the rates of a source, the best source of each day by default
+    pub source: Option<String>,
+    /// Carry the last rate over the days without one, like weekends
+    pub fill: Option<bool>,
+    pub interval: Option<SeriesInterval>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SeriesPoint {
+    pub date: NaiveDate,
+    /// Null on the days without a rate when the series isn't filled
+    pub rate: Option<BigDecimal>,
+    /// Whether the rate was carried over from an earlier day
+    pub filled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct PairSeries {
+    pub base: String,
+    pub target: String,
+    pub points: Vec<SeriesPoint>,
+    /// Weekdays of the range without a rate
+    pub missing_dates: Vec<NaiveDate>,
+}
+
+/// Rates of the pair by day, inverting the reverse pair. The best source of the day
+/// wins and, for the same source, the direct pair wins over the reverse one
+fn daily_rates(
+    base_id: i64,
+    target_id: i64,
+    from: NaiveDate,
+    to: NaiveDate,
+    source: Option<&str>,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<(NaiveDate, BigDecimal)>> {
+    let precedence = SourcePrecedence::load(conn)?;
+    // A stored rate is how many base units a target unit is worth, the multiplier
+    // from the target into the base
+    let rates = PairRates::load(target_id, base_id, from, to, source, &precedence, conn)?;
+    Ok(rates
+        .into_rates()
+        .into_iter()
+        .map(|rate| (rate.date, rate.rate))
+        .collect())
+}
+
+fn mean(rates: &[BigDecimal]) -> Option<BigDecimal> {
+    let scale = rates
+        .iter()
+        .map(|rate| rate.fractional_digit_count())
+        .max()?;
+    let sum = rates
+        .iter()
+        .fold(BigDecimal::zero(), |sum, rate| sum + rate);
+    Some(
+        (sum / BigDecimal::from(rates.len() as u64))
+            .with_scale_round(scale, RoundingMode::HalfEven),
+    )
+}
+
+fn build_series(
+    rates: &[(NaiveDate, BigDecimal)],
+    from: NaiveDate,
+    to: NaiveDate,
+    fill: bool,
+    interval: SeriesInterval,
+) -> (Vec<SeriesPoint>, Vec<NaiveDate>) {
+    let mut missing_dates = Vec::new();
+    let mut points: Vec<SeriesPoint> = Vec::new();
+    let mut bucket_rates: Vec<BigDecimal> = Vec::new();
+    // Last observed rate, rates before `from` only feed the fill
+    let mut next = rates.partition_point(|(date, _)| *date < from);
+    let mut last = next.checked_sub(1).map(|index| rates[index].1.clone());
+
+    let mut day = from;
+    while day <= to {
+        let observed = match rates.get(next) {
+            Some((date, rate)) if *date == day => {
+                next += 1;
+                Some(rate.clone())
+            }
+            _ => None,
+        };
+        if observed.is_none() && !matches!(day.weekday(), Weekday::Sat | Weekday::Sun) {
+            missing_dates.push(day);
+        }
+        if let Some(rate) = &observed {
+            last = Some(rate.clone());
+            bucket_rates.push(rate.clone());
+        }
+
+        let bucket = interval.bucket(day);
+        let bucket_ends = day == to || interval.bucket(day + Duration::days(1)) != bucket;
+        if bucket_ends {
+            let rate = mean(&bucket_rates);
+            let filled = rate.is_none() && fill && last.is_some();
+            points.push(SeriesPoint {
+                date: bucket,
+                rate: rate.or_else(|| last.clone().filter(|_| fill)),
+                filled,
+            });
+            bucket_rates.clear();
+        }
+        day += Duration::days(1);
+    }
+    (points, missing_dates)
+}
+
+#[utoipa::path(
+    get,
+    path = "exchange_rates/series",
+    params(SeriesQuery),
+    responses(
+            (status = 200, body = Vec<PairSeries>, description = "A point per day, week or month of each pair with the weekdays that have no rate"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+pub(super) async fn exchange_rate_series(
+    Query(query_params): Query<SeriesQuery>,
+    state: AppState,
+) -> AppResult<Vec<PairSeries>> {
+    let to = query_params.to.unwrap_or(Utc::now().date_naive());
+    let from = query_params.from.unwrap_or(to - Duration::days(365));
+    if to < from {
+        return Err(AppError::ValidationError("to can't be before from".into()));
+    }
+    if (to - from).num_days() > MAX_DAYS {
+        return Err(AppError::ValidationError(format!(
+            "The range can't be longer than {MAX_DAYS} days"
+        )));
+    }
+    let pairs: Vec<(String, String)> = query_params
+        .pairs
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            pair.split_once('/')
+                .map(|(base, target)| (base.trim().to_uppercase(), target.trim().to_uppercase()))
+                .ok_or_else(|| {
+                    AppError::ValidationError(format!("Pair {pair} must look like BASE/TARGET"))
+                })
+        })
+        .collect::<Result<_, _>>()?;
+    if pairs.is_empty() || pairs.len() > MAX_PAIRS {
+        return Err(AppError::ValidationError(format!(
+            "Ask for between 1 and {MAX_PAIRS} pairs"
+        )));
+    }
+    let fill = query_params.fill.unwrap_or_default();
+    let interval = query_params.interval.unwrap_or_default();
+
+    Ok(Json(
+        state
+            .db_write()
+            .await?
+            .interact(move |conn| {
+                let mut series = Vec::with_capacity(pairs.len());
+                for (base, target) in pairs {
+                    let base_id = filter_currency_id(&base, conn)?;
+                    let target_id = filter_currency_id(&target, conn)?;
+                    let rates = daily_rates(
+                        base_id,
+                        target_id,
+                        from - Duration::days(FILL_LOOKBACK_DAYS),
+                        to,
+                        query_params.source.as_deref(),
+                        conn,
+                    )?;
+                    let (points, missing_dates) = build_series(&rates, from, to, fill, interval);
+                    series.push(PairSeries {
+                        base,
+                        target,
+                        points,
+                        missing_dates,
+                    });
+                }
+                Ok::<_, AppError>(series)
+            })
+            .await
+            .map_err(AppError::DatabaseConnectionInteractError)??,
+    ))
+}
@@ -0,0 +1,288 @@
+//! Which source wins when several publish a rate for the same pair and day, and the
+//! days they disagree on
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{extract::Query, Json};
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use chrono::{Duration, NaiveDate, Utc};
+use diesel::prelude::*;
+use menva::get_env;
+use serde::{Deserialize, Serialize};
+use utoipa::{self, IntoParams, ToResponse, ToSchema};
+
+use super::handlers::filter_currency_id;
+use crate::{
+    db::schema::{currencies, exchange_rates},
+    server::{AppError, AppResult, AppState},
+};
+
+/// Precedence used when `EXCHANGE_RATE_SOURCES` isn't set
+const DEFAULT_PRECEDENCE: &str = "ECB,FRED,manual";
+
+/// Spread between sources, in percent, flagged when the request doesn't give one
+const DEFAULT_THRESHOLD_PERCENT: &str = "0.5";
+
+/// Longest range a consistency check looks at
+const MAX_CHECK_DAYS: i64 = 366;
+
+/// Order in which the rates of each source are trusted, best first.
+///
+/// Read from `EXCHANGE_RATE_SOURCES` as a default list followed by rules for single
+/// pairs, separated by semicolons: `ECB,FRED,manual;MXN/USD=FRED,ECB`. A pair rule
+/// applies to both directions of the pair. Sources that aren't listed, and rows
+/// without a source, come after the listed ones, by name.
+#[derive(Debug, Clone)]
+pub struct SourcePrecedence {
+    default: Vec<String>,
+    /// Rules keyed by the ids of the pair, lowest id first
+    pairs: HashMap<(i64, i64), Vec<String>>,
+}
+
+fn pair_key(base_id: i64, target_id: i64) -> (i64, i64) {
+    (base_id.min(target_id), base_id.max(target_id))
+}
+
+fn parse_sources(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|source| source.trim().to_string())
+        .filter(|source| !source.is_empty())
+        .collect()
+}
+
+impl SourcePrecedence {
+    /// Reads the configured precedence, pair rules of unknown currencies are left out
+    pub fn load(conn: &mut PgConnection) -> QueryResult<Self> {
+        let configured = get_env("EXCHANGE_RATE_SOURCES");
+        let configured = if configured.trim().is_empty() {
+            DEFAULT_PRECEDENCE.to_string()
+        } else {
+            configured
+        };
+        let mut default = Vec::new();
+        let mut rules: Vec<(String, String, Vec<String>)> = Vec::new();
+        for rule in configured.split(';').filter(|rule| !rule.trim().is_empty()) {
+            let Some((pair, sources)) = rule.split_once('=') else {
+                default = parse_sources(rule);
+                continue;
+            };
+            let Some((base, target)) = pair.split_once('/') else {
+                warn!("EXCHANGE_RATE_SOURCES: {pair} must look like BASE/TARGET");
+                continue;
+            };
+            rules.push((
+                base.trim().to_uppercase(),
+                target.trim().to_uppercase(),
+                parse_sources(sources),
+            ));
+        }
+
+        let mut pairs = HashMap::new();
+        if !rules.is_empty() {
+            let codes: Vec<&str> = rules
+                .iter()
+                .flat_map(|(base, target, _)| [base.as_str(), target.as_str()])
+                .collect();
+            let ids: HashMap<String, i64> = currencies::table
+                .filter(currencies::alphabetic_code.eq_any(codes))
+                .select((currencies::alphabetic_code, currencies::id))
+                .load(conn)?
+                .into_iter()
+                .collect();
+            for (base, target, sources) in rules {
+                let (Some(base_id), Some(target_id)) = (ids.get(&base), ids.get(&target)) else {
+                    warn!("EXCHANGE_RATE_SOURCES: unknown currency in {base}/{target}");
+                    continue;
+                };
+                pairs.insert(pair_key(*base_id, *target_id), sources);
+            }
+        }
+        Ok(Self { default, pairs })
+    }
+
+    /// Sources of the pair, best first
+    pub fn sources(&self, base_id: i64, target_id: i64) -> &[String] {
+        self.pairs
+            .get(&pair_key(base_id, target_id))
+            .unwrap_or(&self.default)
+    }
+
+    /// Sort key of a source for the pair, lower is better
+    pub fn rank<'a>(
+        &self,
+        base_id: i64,
+        target_id: i64,
+        source: Option<&'a str>,
+    ) -> (usize, &'a str) {
+        let source = source.unwrap_or_default();
+        let sources = self.sources(base_id, target_id);
+        let position = sources
+            .iter()
+            .position(|listed| listed.eq_ignore_ascii_case(source))
+            .unwrap_or(sources.len());
+        (position, source)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct ConsistencyQuery {
+    /// A month before `to` by default
+    pub from: Option<NaiveDate>,
+    /// Today by default
+    pub to: Option<NaiveDate>,
+    /// Currency code, only pairs with this currency
+    pub currency: Option<String>,
+    /// Spread between the highest and the lowest rate of a day, in percent of the
+    /// lowest, above which the day is flagged. 0.5 by default
+    #[param(value_type = Option<String>)]
+    pub threshold: Option<BigDecimal>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SourceRate {
+    pub source: Option<String>,
+    pub rate: BigDecimal,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct RateDiscrepancy {
+    pub base: String,
+    pub target: String,
+    pub date: NaiveDate,
+    /// In percent of the lowest rate
+    pub spread: BigDecimal,
+    /// Rate of each source as `base` per `target`, the one used for conversions first
+    pub rates: Vec<SourceRate>,
+}
+
+/// Days between both dates where the sources of a pair publish rates further apart
+/// than the threshold, in percent. Reverse pairs are inverted so every source of a
+/// pair is compared the same way round.
+pub fn find_discrepancies(
+    from: NaiveDate,
+    to: NaiveDate,
+    currency_id: Option<i64>,
+    threshold: &BigDecimal,
+    conn: &mut PgConnection,
+) -> Result<Vec<RateDiscrepancy>, AppError> {
+    let precedence = SourcePrecedence::load(conn)?;
+    let mut query = exchange_rates::table
+        .filter(exchange_rates::date.between(from, to))
+        .into_boxed();
+    if let Some(currency_id) = currency_id {
+        query = query.filter(
+            exchange_rates::base_id
+                .eq(currency_id)
+                .or(exchange_rates::target_id.eq(currency_id)),
+        );
+    }
+    let rows: Vec<(i64, i64, NaiveDate, Option<String>, BigDecimal)> = query
+        .select((
+            exchange_rates::base_id,
+            exchange_rates::target_id,
+            exchange_rates::date,
+            exchange_rates::source,
+            exchange_rates::conversion_rate,
+        ))
+        .load(conn)?;
+
+    let mut days: BTreeMap<(NaiveDate, i64, i64), Vec<SourceRate>> = BTreeMap::new();
+    for (base_id, target_id, date, source, rate) in rows {
+        let (low, high) = pair_key(base_id, target_id);
+        let rate = if base_id == low {
+            rate
+        } else {
+            rate.inverse().with_prec(rate.digits())
+        };
+        days.entry((date, low, high))
+            .or_default()
+            .push(SourceRate { source, rate });
+    }
+
+    let codes: HashMap<i64, String> = currencies::table
+        .select((currencies::id, currencies::alphabetic_code))
+        .load(conn)?
+        .into_iter()
+        .collect();
+    let hundred = BigDecimal::from(100);
+    let mut discrepancies = Vec::new();
+    for ((date, base_id, target_id), mut rates) in days {
+        let (Some(lowest), Some(highest)) = (
+            rates.iter().map(|rate| &rate.rate).min(),
+            rates.iter().map(|rate| &rate.rate).max(),
+        ) else {
+            continue;
+        };
+        if rates.len() < 2 || lowest.is_zero() {
+            continue;
+        }
+        let spread =
+            ((highest - lowest) * &hundred / lowest).with_scale_round(4, RoundingMode::HalfEven);
+        if spread <= *threshold {
+            continue;
+        }
+        rates.sort_by(|a, b| {
+            precedence
+                .rank(base_id, target_id, a.source.as_deref())
+                .cmp(&precedence.rank(base_id, target_id, b.source.as_deref()))
+        });
+        discrepancies.push(RateDiscrepancy {
+            base: codes.get(&base_id).cloned().unwrap_or_default(),
+            target: codes.get(&target_id).cloned().unwrap_or_default(),
+            date,
+            spread,
+            rates,
+        });
+    }
+    Ok(discrepancies)
+}
+
+#[utoipa::path(
+    get,
+    path = "exchange_rates/consistency",
+    params(ConsistencyQuery),
+    responses(
+            (status = 200, body = Vec<RateDiscrepancy>, description = "Days where the sources of a pair disagree by more than the threshold, oldest first"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+pub(super) async fn check_consistency(
+    Query(query_params): Query<ConsistencyQuery>,
+    state: AppState,
+) -> AppResult<Vec<RateDiscrepancy>> {
+    let to = query_params.to.unwrap_or(Utc::now().date_naive());
+    let from = query_params.from.unwrap_or(to - Duration::days(30));
+    if to < from {
+        return Err(AppError::ValidationError("to can't be before from".into()));
+    }
+    if (to - from).num_days() > MAX_CHECK_DAYS {
+        return Err(AppError::ValidationError(format!(
+            "The range can't be longer than {MAX_CHECK_DAYS} days"
+        )));
+    }
+    let threshold = match query_params.threshold {
+        Some(threshold) if threshold < BigDecimal::zero() => {
+            return Err(AppError::ValidationError(
+                "threshold can't be negative".into(),
+            ))
+        }
+        Some(threshold) => threshold,
+        None => DEFAULT_THRESHOLD_PERCENT.parse().unwrap_or_default(),
+    };
+
+    Ok(Json(
+        state
+            .db_write()
+            .await?
+            .interact(move |conn| {
+                let currency_id = query_params
+                    .currency
+                    .as_deref()
+                    .map(|code| filter_currency_id(code, conn))
+                    .transpose()?;
+                find_discrepancies(from, to, currency_id, &threshold, conn)
+            })
+            .await
+            .map_err(AppError::DatabaseConnectionInteractError)??,
+    ))
+}
@@ -1,6 +1,7 @@
 mod database_pools;
 mod pagination;
 pub mod schema;
+pub mod views;
 
 pub use database_pools::{ConnectionConfig, DatabasePools};
 pub use pagination::Paginate;
@@ -133,6 +133,9 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     companies (id) {
         id -> Int8,
         #[max_length = 255]
@@ -171,6 +174,9 @@ diesel::table! {
         is_fund -> Bool,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        search_vector -> Tsvector,
+        #[max_length = 12]
+        figi -> Nullable<Varchar>,
     }
 }
 
@@ -196,6 +202,47 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    company_scores (id) {
+        id -> Int8,
+        company_id -> Int8,
+        period_id -> Int8,
+        date -> Date,
+        piotroski_f_score -> Nullable<Int4>,
+        piotroski_components -> Nullable<Jsonb>,
+        altman_z_score -> Nullable<Float8>,
+        altman_components -> Nullable<Jsonb>,
+        beneish_m_score -> Nullable<Float8>,
+        beneish_components -> Nullable<Jsonb>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    corporate_actions (id) {
+        id -> Int8,
+        company_id -> Int8,
+        #[max_length = 30]
+        kind -> Varchar,
+        ex_date -> Date,
+        announcement_date -> Nullable<Date>,
+        ratio_to -> Nullable<Float8>,
+        ratio_from -> Nullable<Float8>,
+        #[max_length = 255]
+        old_ticker -> Nullable<Varchar>,
+        #[max_length = 255]
+        new_ticker -> Nullable<Varchar>,
+        related_company_id -> Nullable<Int8>,
+        cash_amount -> Nullable<Numeric>,
+        currency_id -> Nullable<Int8>,
+        description -> Nullable<Text>,
+        applied_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     countries (id) {
         id -> Int8,
@@ -236,6 +283,7 @@ diesel::table! {
         country_id -> Int8,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        is_primary -> Bool,
     }
 }
 
@@ -252,6 +300,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    dcf_parameters (id) {
+        id -> Int8,
+        user_id -> Int8,
+        company_id -> Int8,
+        discount_rate -> Float8,
+        terminal_growth -> Float8,
+        growth_rate -> Nullable<Float8>,
+        projection_years -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     definitions (id) {
         id -> Int8,
@@ -310,6 +372,23 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    dividends (id) {
+        id -> Int8,
+        company_id -> Int8,
+        declaration_date -> Nullable<Date>,
+        ex_date -> Date,
+        record_date -> Nullable<Date>,
+        payment_date -> Nullable<Date>,
+        amount -> Numeric,
+        currency_id -> Int8,
+        #[max_length = 20]
+        frequency -> Varchar,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     efficiency_ratios (id) {
         id -> Int8,
@@ -357,15 +436,30 @@ diesel::table! {
         id -> Int8,
         base_id -> Int8,
         target_id -> Int8,
-        #[max_length = 255]
-        conversion_rate -> Varchar,
-        precision -> Int4,
-        scale -> Int4,
         date -> Date,
         #[max_length = 255]
         source -> Nullable<Varchar>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        conversion_rate -> Numeric,
+    }
+}
+
+diesel::table! {
+    exchange_rates_rejected (id) {
+        id -> Int8,
+        base_id -> Nullable<Int8>,
+        target_id -> Nullable<Int8>,
+        date -> Nullable<Date>,
+        #[max_length = 255]
+        conversion_rate -> Nullable<Varchar>,
+        precision -> Nullable<Int4>,
+        scale -> Nullable<Int4>,
+        #[max_length = 255]
+        source -> Nullable<Varchar>,
+        created_at -> Nullable<Timestamp>,
+        updated_at -> Nullable<Timestamp>,
+        transaction_ids -> Nullable<Array<Nullable<Int8>>>,
     }
 }
 
@@ -610,6 +704,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    prices (id) {
+        id -> Int8,
+        company_id -> Int8,
+        date -> Date,
+        open -> Float8,
+        high -> Float8,
+        low -> Float8,
+        close -> Float8,
+        volume -> Int8,
+        currency_id -> Nullable<Int8>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     profiles (id) {
         id -> Int8,
@@ -672,6 +782,23 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    statement_findings (id) {
+        id -> Int8,
+        company_id -> Int8,
+        period_id -> Nullable<Int8>,
+        date -> Nullable<Date>,
+        #[max_length = 64]
+        rule -> Varchar,
+        #[max_length = 64]
+        source_table -> Varchar,
+        message -> Text,
+        expected -> Nullable<Float8>,
+        actual -> Nullable<Float8>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     transactions (id) {
         id -> Int8,
@@ -685,6 +812,7 @@ diesel::table! {
         category -> Varchar,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        dividend_id -> Nullable<Int8>,
     }
 }
 
@@ -752,14 +880,21 @@ diesel::joinable!(companies -> sectors (sector_id));
 diesel::joinable!(company_growth -> companies (company_id));
 diesel::joinable!(company_growth -> currencies (reported_currency_id));
 diesel::joinable!(company_growth -> periods (period_id));
+diesel::joinable!(company_scores -> companies (company_id));
+diesel::joinable!(company_scores -> periods (period_id));
+diesel::joinable!(corporate_actions -> currencies (currency_id));
 diesel::joinable!(currencies_countries_m2m -> countries (country_id));
 diesel::joinable!(currencies_countries_m2m -> currencies (currency_id));
 diesel::joinable!(dashboard -> users (author_id));
+diesel::joinable!(dcf_parameters -> companies (company_id));
+diesel::joinable!(dcf_parameters -> users (user_id));
 diesel::joinable!(definitions -> users (author_id));
 diesel::joinable!(definitions_categories_m2m -> definitions (definition_id));
 diesel::joinable!(definitions_categories_m2m -> definitions_categories (category_id));
 diesel::joinable!(definitions_content -> definitions (definition_id));
 diesel::joinable!(definitions_content -> users (author_id));
+diesel::joinable!(dividends -> companies (company_id));
+diesel::joinable!(dividends -> currencies (currency_id));
 diesel::joinable!(efficiency_ratios -> companies (company_id));
 diesel::joinable!(efficiency_ratios -> currencies (reported_currency_id));
 diesel::joinable!(efficiency_ratios -> periods (period_id));
@@ -793,6 +928,8 @@ diesel::joinable!(per_share_values -> periods (period_id));
 diesel::joinable!(price_to_ratios -> companies (company_id));
 diesel::joinable!(price_to_ratios -> currencies (reported_currency_id));
 diesel::joinable!(price_to_ratios -> periods (period_id));
+diesel::joinable!(prices -> companies (company_id));
+diesel::joinable!(prices -> currencies (currency_id));
 diesel::joinable!(profiles -> countries (country_id));
 diesel::joinable!(profiles -> currencies (currency_id));
 diesel::joinable!(profiles -> users (user_id));
@@ -800,7 +937,10 @@ diesel::joinable!(rates_return -> accounts (account_id));
 diesel::joinable!(rentability_ratios -> companies (company_id));
 diesel::joinable!(rentability_ratios -> currencies (reported_currency_id));
 diesel::joinable!(rentability_ratios -> periods (period_id));
+diesel::joinable!(statement_findings -> companies (company_id));
+diesel::joinable!(statement_findings -> periods (period_id));
 diesel::joinable!(transactions -> accounts (account_id));
+diesel::joinable!(transactions -> dividends (dividend_id));
 diesel::joinable!(transactions -> exchange_rates (exchange_rate_id));
 diesel::joinable!(transactions -> transactions_details (details_id));
 diesel::joinable!(transactions -> users (user_id));
@@ -813,17 +953,22 @@ diesel::allow_tables_to_appear_in_same_query!(
     cashflow_statements,
     companies,
     company_growth,
+    company_scores,
+    corporate_actions,
     countries,
     currencies,
     currencies_countries_m2m,
     dashboard,
+    dcf_parameters,
     definitions,
     definitions_categories,
     definitions_categories_m2m,
     definitions_content,
+    dividends,
     efficiency_ratios,
     enterprise_value_ratios,
     exchange_rates,
+    exchange_rates_rejected,
     exchanges,
     fees,
     free_cashflow_ratios,
@@ -837,10 +982,12 @@ diesel::allow_tables_to_appear_in_same_query!(
     per_share_values,
     periods,
     price_to_ratios,
+    prices,
     profiles,
     rates_return,
     rentability_ratios,
     sectors,
+    statement_findings,
     transactions,
     transactions_details,
     users,
@@ -0,0 +1,43 @@
+//! Views aren't picked up by `diesel print-schema`, so they are declared by hand here.
+
+diesel::table! {
+    sector_aggregates (sector_id, period_id) {
+        sector_id -> Int8,
+        period_id -> Int8,
+        year -> Int4,
+        period -> Int4,
+        companies -> Int8,
+        market_capitalization_by_currency -> Nullable<Jsonb>,
+        median_gross_margin -> Nullable<Float8>,
+        median_net_income_margin -> Nullable<Float8>,
+        median_free_cash_flow_margin -> Nullable<Float8>,
+        median_revenue_growth -> Nullable<Float8>,
+        median_net_income_growth -> Nullable<Float8>,
+        median_earnings_per_share_growth -> Nullable<Float8>,
+        median_price_to_earnings -> Nullable<Float8>,
+        median_price_to_book_value -> Nullable<Float8>,
+        median_price_to_sales -> Nullable<Float8>,
+        median_enterprise_value_multiple -> Nullable<Float8>,
+    }
+}
+
+diesel::table! {
+    industry_aggregates (industry_id, period_id) {
+        industry_id -> Int8,
+        period_id -> Int8,
+        year -> Int4,
+        period -> Int4,
+        companies -> Int8,
+        market_capitalization_by_currency -> Nullable<Jsonb>,
+        median_gross_margin -> Nullable<Float8>,
+        median_net_income_margin -> Nullable<Float8>,
+        median_free_cash_flow_margin -> Nullable<Float8>,
+        median_revenue_growth -> Nullable<Float8>,
+        median_net_income_growth -> Nullable<Float8>,
+        median_earnings_per_share_growth -> Nullable<Float8>,
+        median_price_to_earnings -> Nullable<Float8>,
+        median_price_to_book_value -> Nullable<Float8>,
+        median_price_to_sales -> Nullable<Float8>,
+        median_enterprise_value_multiple -> Nullable<Float8>,
+    }
+}
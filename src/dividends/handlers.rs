@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query},
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use chrono::{Duration, NaiveDate, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{self, IntoParams, OpenApi, ToResponse, ToSchema};
+
+use super::income::{annual_dividend, annual_income};
+use crate::{
+    companies::get_company_id,
+    db::schema::{companies, dividends},
+    server::{AppError, AppResult, JWTUserRequest},
+    transactions::load_positions,
+    AppState,
+};
+
+/// Days shown by the calendar when no end is given
+const CALENDAR_DAYS: i64 = 30;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        list_dividends,
+        create_dividend,
+        delete_dividend,
+        dividends_calendar,
+        yield_on_cost
+    ),
+    components(schemas(Dividend, DividendPayload, Frequency, CalendarEntry, YieldOnCost),
+    responses(Dividend, CalendarEntry, YieldOnCost)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/companies/:ticker/dividends",
+            get(list_dividends).post(create_dividend),
+        )
+        .route("/dividends/calendar", get(dividends_calendar))
+        .route("/dividends/yield_on_cost", get(yield_on_cost))
+        .route("/dividends/:id", delete(delete_dividend))
+        .with_state(state)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Monthly,
+    Quarterly,
+    Semiannual,
+    Annual,
+    Irregular,
+    Special,
+}
+
+impl Frequency {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Frequency::Monthly => "monthly",
+            Frequency::Quarterly => "quarterly",
+            Frequency::Semiannual => "semiannual",
+            Frequency::Annual => "annual",
+            Frequency::Irregular => "irregular",
+            Frequency::Special => "special",
+        }
+    }
+
+    pub fn parse(frequency: &str) -> Option<Self> {
+        [
+            Frequency::Monthly,
+            Frequency::Quarterly,
+            Frequency::Semiannual,
+            Frequency::Annual,
+            Frequency::Irregular,
+            Frequency::Special,
+        ]
+        .into_iter()
+        .find(|known| known.as_str() == frequency)
+    }
+
+    pub fn payments_per_year(self) -> Option<u32> {
+        match self {
+            Frequency::Monthly => Some(12),
+            Frequency::Quarterly => Some(4),
+            Frequency::Semiannual => Some(2),
+            Frequency::Annual => Some(1),
+            Frequency::Irregular | Frequency::Special => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable, ToSchema, ToResponse)]
+#[diesel(table_name = dividends)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Dividend {
+    pub id: i64,
+    pub company_id: i64,
+    pub declaration_date: Option<NaiveDate>,
+    pub ex_date: NaiveDate,
+    pub record_date: Option<NaiveDate>,
+    pub payment_date: Option<NaiveDate>,
+    /// Per share as declared, before later splits
+    pub amount: BigDecimal,
+    pub currency_id: i64,
+    pub frequency: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable, ToSchema)]
+#[diesel(table_name = dividends)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DividendPayload {
+    #[serde(skip)]
+    company_id: i64,
+    declaration_date: Option<NaiveDate>,
+    ex_date: NaiveDate,
+    record_date: Option<NaiveDate>,
+    payment_date: Option<NaiveDate>,
+    amount: BigDecimal,
+    currency_id: i64,
+    /// monthly, quarterly, semiannual, annual, irregular or special
+    frequency: String,
+}
+
+impl DividendPayload {
+    fn validate(&mut self) -> Result<(), AppError> {
+        self.frequency = self.frequency.trim().to_lowercase();
+        if Frequency::parse(&self.frequency).is_none() {
+            return Err(AppError::ValidationError(format!(
+                "Unknown frequency {}, use monthly, quarterly, semiannual, annual, irregular or special",
+                self.frequency
+            )));
+        }
+        if self.amount <= BigDecimal::zero() {
+            return Err(AppError::ValidationError("amount must be positive".into()));
+        }
+        let dates = [
+            ("declaration_date", self.declaration_date, self.ex_date),
+            (
+                "ex_date",
+                Some(self.ex_date),
+                self.record_date.unwrap_or(self.ex_date),
+            ),
+            (
+                "ex_date",
+                Some(self.ex_date),
+                self.payment_date.unwrap_or(self.ex_date),
+            ),
+        ];
+        for (name, date, later) in dates {
+            if date.is_some_and(|date| date > later) {
+                return Err(AppError::ValidationError(format!(
+                    "{name} can't be after {later}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct DividendsQuery {
+    /// First ex-date
+    pub from: Option<NaiveDate>,
+    /// Last ex-date
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct CalendarQuery {
+    /// Today by default
+    pub from: Option<NaiveDate>,
+    /// 30 days after `from` by default
+    pub to: Option<NaiveDate>,
+    /// Comma separated tickers
+    pub tickers: Option<String>,
+    /// Only the companies the user holds
+    pub held: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct CalendarEntry {
+    pub ticker: String,
+    pub name: Option<String>,
+    pub dividend: Dividend,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct YieldOnCost {
+    pub account_id: i64,
+    pub ticker: String,
+    pub quantity: f64,
+    /// In the currency of the account
+    pub cost: f64,
+    pub currency_id: i64,
+    /// Expected over the next year in today's shares
+    pub annual_dividend_per_share: f64,
+    pub dividend_currency_id: i64,
+    /// Expected over the next year in the currency of the account, null without an
+    /// exchange rate
+    pub annual_income: Option<f64>,
+    pub yield_on_cost: Option<f64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/dividends",
+    params(("ticker", description = "Company's ticker"), DividendsQuery),
+    responses(
+        (status = 200, body = Vec<Dividend>, description = "Dividend history of a company, oldest first"),
+        (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+        (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+    )
+)]
+async fn list_dividends(
+    Path(ticker): Path<String>,
+    Query(query_params): Query<DividendsQuery>,
+    state: AppState,
+) -> AppResult<Vec<Dividend>> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            let mut query = dividends::table
+                .filter(dividends::company_id.eq(company_id))
+                .order(dividends::ex_date)
+                .select(Dividend::as_select())
+                .into_boxed();
+            if let Some(from) = query_params.from {
+                query = query.filter(dividends::ex_date.ge(from));
+            }
+            if let Some(to) = query_params.to {
+                query = query.filter(dividends::ex_date.le(to));
+            }
+            query.load(conn).map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "companies/{ticker}/dividends",
+    params(("ticker", description = "Company's ticker")),
+    request_body = DividendPayload,
+    responses(
+        (status = 200, body = Dividend, description = "Record a dividend, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn create_dividend(
+    Path(ticker): Path<String>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    Json(mut dividend): Json<DividendPayload>,
+) -> AppResult<Dividend> {
+    current_user.require_staff()?;
+    dividend.validate()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            dividend.company_id = get_company_id(&ticker, conn)?;
+            diesel::insert_into(dividends::table)
+                .values(&dividend)
+                .returning(Dividend::as_returning())
+                .get_result(conn)
+                .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    delete,
+    path = "dividends/{id}",
+    params(("id", description = "Dividend's id")),
+    responses(
+        (status = 200, body = Dividend, description = "Delete a dividend, staff only. Its transactions are unlinked"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn delete_dividend(
+    Path(id): Path<i64>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<Dividend> {
+    current_user.require_staff()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            diesel::delete(dividends::table.find(id))
+                .returning(Dividend::as_returning())
+                .get_result(conn)
+                .optional()?
+                .ok_or(AppError::DoesNotExist)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "dividends/calendar",
+    params(CalendarQuery),
+    responses(
+        (status = 200, body = Vec<CalendarEntry>, description = "Dividends going ex or paid between both dates, by payment date"),
+        (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+        (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+    )
+)]
+async fn dividends_calendar(
+    Query(query_params): Query<CalendarQuery>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<Vec<CalendarEntry>> {
+    let from = query_params.from.unwrap_or_else(|| Utc::now().date_naive());
+    let to = query_params
+        .to
+        .unwrap_or(from + Duration::days(CALENDAR_DAYS));
+    if to < from {
+        return Err(AppError::ValidationError("to can't be before from".into()));
+    }
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let mut query = dividends::table
+                .inner_join(companies::table)
+                .filter(
+                    dividends::ex_date
+                        .between(from, to)
+                        .or(dividends::payment_date.between(from, to)),
+                )
+                .select((companies::ticker, companies::name, Dividend::as_select()))
+                .into_boxed();
+            if let Some(tickers) = &query_params.tickers {
+                let tickers: Vec<&str> = tickers.split(',').map(str::trim).collect();
+                query = query.filter(companies::ticker.eq_any(tickers));
+            }
+            if query_params.held.unwrap_or_default() {
+                let held: Vec<i64> =
+                    load_positions(current_user.id, Utc::now().date_naive(), conn)?
+                        .into_iter()
+                        .map(|position| position.company_id)
+                        .collect();
+                query = query.filter(companies::id.eq_any(held));
+            }
+            let mut entries: Vec<CalendarEntry> = query
+                .load::<(String, Option<String>, Dividend)>(conn)?
+                .into_iter()
+                .map(|(ticker, name, dividend)| CalendarEntry {
+                    ticker,
+                    name,
+                    dividend,
+                })
+                .collect();
+            entries.sort_by_key(|entry| {
+                let dividend = &entry.dividend;
+                (
+                    dividend.payment_date.unwrap_or(dividend.ex_date),
+                    dividend.ex_date,
+                )
+            });
+            Ok(entries)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "dividends/yield_on_cost",
+    responses(
+        (status = 200, body = Vec<YieldOnCost>, description = "Expected dividend income of the positions of the user over their cost"),
+        (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+        (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+    )
+)]
+async fn yield_on_cost(
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<Vec<YieldOnCost>> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let positions = load_positions(current_user.id, Utc::now().date_naive(), conn)?;
+            let mut rates = HashMap::new();
+            let mut yields = Vec::new();
+            for position in positions {
+                let Some((per_share, dividend_currency_id)) =
+                    annual_dividend(position.company_id, conn)?
+                else {
+                    continue;
+                };
+                let ticker: String = companies::table
+                    .find(position.company_id)
+                    .select(companies::ticker)
+                    .first(conn)?;
+                let annual_income =
+                    annual_income(&position, per_share, dividend_currency_id, &mut rates, conn)?;
+                let cost = position.cost.amount.to_f64().unwrap_or_default();
+                yields.push(YieldOnCost {
+                    account_id: position.account_id,
+                    ticker,
+                    quantity: position.quantity,
+                    cost,
+                    currency_id: position.cost.currency,
+                    annual_dividend_per_share: per_share,
+                    dividend_currency_id,
+                    annual_income,
+                    yield_on_cost: annual_income
+                        .filter(|_| cost > 0.0)
+                        .map(|income| income / cost),
+                });
+            }
+            Ok(yields)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
@@ -0,0 +1,147 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use bigdecimal::ToPrimitive;
+use chrono::{Duration, NaiveDate, Utc};
+use diesel::prelude::*;
+
+use super::handlers::{Dividend, Frequency};
+use crate::{
+    corporate_actions::SplitFactors,
+    currencies::RateSeries,
+    db::schema::{
+        assets_details, dividends, investment_details, transactions, transactions_details,
+    },
+    transactions::Position,
+};
+
+/// Category of the transactions that pay a dividend
+pub const DIVIDEND_CATEGORY: &str = "dividend";
+
+/// Days between the payment date and the date a broker books the dividend
+const PAYMENT_WINDOW: i64 = 7;
+
+pub fn is_dividend(category: &str) -> bool {
+    category
+        .trim()
+        .to_lowercase()
+        .starts_with(DIVIDEND_CATEGORY)
+}
+
+/// Dividend of the company paid closest to the date, within a week of it. Dividends
+/// without a payment date are matched by their ex-date
+pub fn match_dividend(
+    company_id: i64,
+    date: NaiveDate,
+    conn: &mut PgConnection,
+) -> QueryResult<Option<i64>> {
+    let window = Duration::days(PAYMENT_WINDOW);
+    let candidates: Vec<(i64, NaiveDate, Option<NaiveDate>)> = dividends::table
+        .filter(dividends::company_id.eq(company_id))
+        .filter(dividends::ex_date.ge(date - window - Duration::days(90)))
+        .filter(dividends::ex_date.le(date + window))
+        .select((dividends::id, dividends::ex_date, dividends::payment_date))
+        .load(conn)?;
+    Ok(candidates
+        .into_iter()
+        .map(|(id, ex_date, payment_date)| {
+            (
+                id,
+                (payment_date.unwrap_or(ex_date) - date).num_days().abs(),
+            )
+        })
+        .filter(|(_, distance)| *distance <= PAYMENT_WINDOW)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(id, _)| id))
+}
+
+/// Links the dividend transactions booked before their dividend was loaded
+pub fn link_dividend_transactions(conn: &mut PgConnection) -> QueryResult<()> {
+    let unlinked: Vec<(i64, NaiveDate, Option<i64>)> = transactions::table
+        .inner_join(transactions_details::table)
+        .inner_join(
+            investment_details::table
+                .on(transactions_details::investment_details_id
+                    .eq(investment_details::id.nullable())),
+        )
+        .inner_join(assets_details::table.on(investment_details::asset_id.eq(assets_details::id)))
+        .filter(transactions::dividend_id.is_null())
+        .filter(transactions::category.ilike(format!("{DIVIDEND_CATEGORY}%")))
+        .select((
+            transactions::id,
+            transactions::date,
+            assets_details::company_id,
+        ))
+        .load(conn)?;
+    for (transaction_id, date, company_id) in unlinked {
+        let Some(company_id) = company_id else {
+            continue;
+        };
+        if let Some(dividend_id) = match_dividend(company_id, date, conn)? {
+            diesel::update(transactions::table.find(transaction_id))
+                .set(transactions::dividend_id.eq(dividend_id))
+                .execute(conn)?;
+        }
+    }
+    Ok(())
+}
+
+/// Dividend per share the company is expected to pay over a year in the shares lots
+/// are held in, the ones of the splits applied so far, and its currency. Regular
+/// dividends repeat the latest one, irregular ones add up the last twelve months.
+/// Special dividends are left out.
+pub(super) fn annual_dividend(
+    company_id: i64,
+    conn: &mut PgConnection,
+) -> QueryResult<Option<(f64, i64)>> {
+    let today = Utc::now().date_naive();
+    let history: Vec<Dividend> = dividends::table
+        .filter(dividends::company_id.eq(company_id))
+        .filter(dividends::frequency.ne(Frequency::Special.as_str()))
+        .order(dividends::ex_date.desc())
+        .select(Dividend::as_select())
+        .load(conn)?;
+    let Some(latest) = history.first() else {
+        return Ok(None);
+    };
+    let factors = SplitFactors::applied(company_id, conn)?;
+    let per_share = |dividend: &Dividend| {
+        dividend.amount.to_f64().unwrap_or_default() / factors.since(dividend.ex_date)
+    };
+    let annual = match Frequency::parse(&latest.frequency).and_then(Frequency::payments_per_year) {
+        Some(payments) => per_share(latest) * f64::from(payments),
+        None => history
+            .iter()
+            .filter(|dividend| dividend.ex_date > today - Duration::days(365))
+            .filter(|dividend| dividend.currency_id == latest.currency_id)
+            .map(per_share)
+            .sum(),
+    };
+    Ok(Some((annual, latest.currency_id)))
+}
+
+/// Income a position is expected to earn over a year in the currency of its account
+pub(super) fn annual_income(
+    position: &Position,
+    annual_dividend: f64,
+    dividend_currency_id: i64,
+    rates: &mut HashMap<(i64, i64), RateSeries>,
+    conn: &mut PgConnection,
+) -> QueryResult<Option<f64>> {
+    let income = annual_dividend * position.quantity;
+    if dividend_currency_id == position.cost.currency {
+        return Ok(Some(income));
+    }
+    let today = Utc::now().date_naive();
+    let pair = (dividend_currency_id, position.cost.currency);
+    let series = match rates.entry(pair) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(RateSeries::load(
+            dividend_currency_id,
+            position.cost.currency,
+            today,
+            today,
+            conn,
+        )?),
+    };
+    Ok(series.on(today).map(|rate| income * rate))
+}
@@ -0,0 +1,7 @@
+//! Dividends declared by companies, when they're paid and what they yield on the
+//! positions of each user
+mod handlers;
+mod income;
+
+pub use handlers::{routes, ApiDoc};
+pub use income::{is_dividend, link_dividend_transactions, match_dividend, DIVIDEND_CATEGORY};
@@ -0,0 +1,73 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use diesel::PgConnection;
+
+use super::tables::{Conversion, FundamentalsRecord, FundamentalsTable};
+use crate::{
+    currencies::{get_currency_id, RateSeries},
+    server::AppError,
+};
+
+/// Converts the monetary figures of the records into `currency`. Figures that can't
+/// be converted because a rate is missing become null instead of staying in the
+/// reported currency.
+pub fn convert_records(
+    table: &FundamentalsTable,
+    records: &mut [FundamentalsRecord],
+    currency: &str,
+    conn: &mut PgConnection,
+) -> Result<(), AppError> {
+    let currency = currency.to_uppercase();
+    let currency_id = match get_currency_id(&currency, conn) {
+        Err(AppError::DoesNotExist) => {
+            return Err(AppError::ValidationError(format!(
+                "Unknown currency {currency}"
+            )))
+        }
+        result => result?,
+    };
+    let converts = table
+        .fields
+        .iter()
+        .any(|(_, kind)| kind.conversion() != Conversion::Unchanged);
+    let start = records.iter().map(FundamentalsRecord::period_start).min();
+    let end = records.iter().map(|record| record.date).max();
+    let (true, Some(start), Some(end)) = (converts, start, end) else {
+        return Ok(());
+    };
+
+    let mut series: HashMap<i64, RateSeries> = HashMap::new();
+    for record in records.iter_mut() {
+        if record.currency_id == Some(currency_id) {
+            continue;
+        }
+        let rates: Option<&RateSeries> = match record.currency_id {
+            Some(from_id) => Some(match series.entry(from_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    RateSeries::load(from_id, currency_id, start, end, conn)
+                        .map_err(AppError::DatabaseQueryError)?,
+                ),
+            }),
+            None => None,
+        };
+        let opening = rates.and_then(|rates| rates.on(record.period_start()));
+        let closing = rates.and_then(|rates| rates.on(record.date));
+        let average = rates.and_then(|rates| rates.average(record.period_start(), record.date));
+
+        for (field, kind) in table.fields {
+            let rate = match kind.conversion() {
+                Conversion::Unchanged => continue,
+                Conversion::OpeningRate => opening,
+                Conversion::ClosingRate => closing,
+                Conversion::AverageRate => average,
+            };
+            if let Some(value) = record.figures.get_mut(*field) {
+                *value = value.zip(rate).map(|(value, rate)| value * rate);
+            }
+        }
+        record.currency = Some(currency.clone());
+        record.currency_id = Some(currency_id);
+    }
+    Ok(())
+}
@@ -0,0 +1,421 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{
+    extract::{Path, Query},
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, NaiveDate};
+use diesel::PgConnection;
+use polars::prelude::*;
+use rust_xlsxwriter::{Format, Workbook};
+use serde::{Deserialize, Serialize};
+use utoipa::{self, IntoParams, OpenApi, ToSchema};
+
+use super::{
+    handlers::FundamentalsQuery,
+    period_code,
+    tables::{find_table, FundamentalsRecord, FundamentalsTable, TABLES},
+};
+use crate::{companies::get_company_id, server::AppError, AppState};
+
+/// Most tickers exported at once
+const MAX_TICKERS: usize = 100;
+/// Rows of an XLSX worksheet, the header included
+const XLSX_MAX_ROWS: usize = 1_048_576;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(export_company, export_companies),
+    components(schemas(ExportFormat, ExportLayout)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/companies/export", get(export_companies))
+        .route("/companies/:ticker/export", get(export_company))
+        .with_state(state)
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+    Parquet,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+
+    fn write(self, df: &mut DataFrame) -> Result<Vec<u8>, AppError> {
+        let mut buffer = Vec::new();
+        match self {
+            ExportFormat::Csv => CsvWriter::new(&mut buffer)
+                .finish(df)
+                .map_err(export_error)?,
+            ExportFormat::Parquet => {
+                ParquetWriter::new(&mut buffer)
+                    .finish(df)
+                    .map_err(export_error)?;
+            }
+            ExportFormat::Xlsx => buffer = write_xlsx(df)?,
+        }
+        Ok(buffer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportLayout {
+    /// One row per company and period with a column per table and metric
+    #[default]
+    Wide,
+    /// One row per company, period, table and metric
+    Long,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct ExportQuery {
+    /// csv by default
+    pub format: Option<ExportFormat>,
+    /// wide by default
+    pub layout: Option<ExportLayout>,
+    /// Comma separated tables to export, every statement and ratio by default
+    pub tables: Option<String>,
+    /// Alphabetic code of the currency to convert the monetary figures into, the
+    /// reported currency by default
+    pub currency: Option<String>,
+    /// FY for fiscal years, Q for quarters, both by default
+    pub period: Option<String>,
+    pub from_year: Option<i32>,
+    pub to_year: Option<i32>,
+    /// Restate share counts and per-share figures in today's shares
+    pub adjust_splits: Option<bool>,
+}
+
+impl ExportQuery {
+    fn tables(&self) -> Result<Vec<&'static FundamentalsTable>, AppError> {
+        let Some(tables) = &self.tables else {
+            return Ok(TABLES.iter().collect());
+        };
+        tables
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                find_table(name)
+                    .ok_or_else(|| AppError::ValidationError(format!("Unknown table {name}")))
+            })
+            .collect()
+    }
+
+    fn filters(&self) -> FundamentalsQuery {
+        FundamentalsQuery {
+            currency: self.currency.clone(),
+            period: self.period.clone(),
+            from_year: self.from_year,
+            to_year: self.to_year,
+            adjust_splits: self.adjust_splits,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct TickersQuery {
+    /// Comma separated tickers
+    pub tickers: String,
+}
+
+struct TableHistory {
+    ticker: String,
+    table: &'static FundamentalsTable,
+    records: Vec<FundamentalsRecord>,
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/export",
+    params(("ticker", description = "Company's ticker"), ExportQuery),
+    responses(
+            (status = 200, content_type = "application/octet-stream", description = "The statement and ratio history of the company as a CSV, XLSX or Parquet file"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn export_company(
+    Path(ticker): Path<String>,
+    Query(query_params): Query<ExportQuery>,
+    state: AppState,
+) -> Result<Response, AppError> {
+    export(vec![ticker], query_params, state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/export",
+    params(TickersQuery, ExportQuery),
+    responses(
+            (status = 200, content_type = "application/octet-stream", description = "The statement and ratio history of every company as a single CSV, XLSX or Parquet file"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn export_companies(
+    Query(tickers): Query<TickersQuery>,
+    Query(query_params): Query<ExportQuery>,
+    state: AppState,
+) -> Result<Response, AppError> {
+    let mut unique = Vec::new();
+    for ticker in tickers.tickers.split(',').map(str::trim) {
+        if !ticker.is_empty() && !unique.iter().any(|seen: &String| seen == ticker) {
+            unique.push(ticker.to_string());
+        }
+    }
+    if unique.is_empty() || unique.len() > MAX_TICKERS {
+        return Err(AppError::ValidationError(format!(
+            "Export between 1 and {MAX_TICKERS} tickers"
+        )));
+    }
+    export(unique, query_params, state).await
+}
+
+async fn export(
+    tickers: Vec<String>,
+    query_params: ExportQuery,
+    state: AppState,
+) -> Result<Response, AppError> {
+    let tables = query_params.tables()?;
+    let filters = query_params.filters();
+    let filename = match tickers.as_slice() {
+        [ticker] => ticker.clone(),
+        _ => "companies".to_string(),
+    };
+    let format = query_params.format.unwrap_or_default();
+    let layout = query_params.layout.unwrap_or_default();
+    // Building and writing the file is as blocking as the queries
+    let body = state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let history = load_history(&tickers, &tables, &filters, conn)?;
+            let mut df = match layout {
+                ExportLayout::Wide => wide_frame(&history),
+                ExportLayout::Long => long_frame(&history),
+            }
+            .map_err(export_error)?;
+            format.write(&mut df)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)??;
+
+    Ok((
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{filename}_fundamentals.{}\"",
+                    format.extension()
+                ),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn load_history(
+    tickers: &[String],
+    tables: &[&'static FundamentalsTable],
+    filters: &FundamentalsQuery,
+    conn: &mut PgConnection,
+) -> Result<Vec<TableHistory>, AppError> {
+    let mut history = Vec::with_capacity(tickers.len() * tables.len());
+    for ticker in tickers {
+        let company_id = get_company_id(ticker, conn)?;
+        for table in tables {
+            history.push(TableHistory {
+                ticker: ticker.clone(),
+                table,
+                records: filters.load(table, company_id, conn)?,
+            });
+        }
+    }
+    Ok(history)
+}
+
+/// Columns shared by both layouts, one value per row
+#[derive(Default)]
+struct KeyColumns {
+    ticker: Vec<String>,
+    year: Vec<Option<i32>>,
+    period: Vec<Option<String>>,
+    date: Vec<NaiveDate>,
+    is_ttm: Vec<bool>,
+    currency: Vec<Option<String>>,
+}
+
+impl KeyColumns {
+    fn push(&mut self, ticker: &str, record: &FundamentalsRecord) {
+        self.ticker.push(ticker.to_string());
+        self.year.push(record.year);
+        self.period.push(record.period.map(period_code));
+        self.date.push(record.date);
+        self.is_ttm.push(record.is_ttm);
+        self.currency.push(record.currency.clone());
+    }
+
+    fn into_series(self) -> Vec<Series> {
+        vec![
+            Series::new("ticker", self.ticker),
+            Series::new("year", self.year),
+            Series::new("period", self.period),
+            DateChunked::from_naive_date("date", self.date).into_series(),
+            Series::new("is_ttm", self.is_ttm),
+            Series::new("currency", self.currency),
+        ]
+    }
+}
+
+fn long_frame(history: &[TableHistory]) -> PolarsResult<DataFrame> {
+    let mut keys = KeyColumns::default();
+    let mut tables = Vec::new();
+    let mut metrics = Vec::new();
+    let mut values = Vec::new();
+    for TableHistory {
+        ticker,
+        table,
+        records,
+    } in history
+    {
+        for record in records {
+            for (metric, value) in &record.figures {
+                keys.push(ticker, record);
+                tables.push(table.name);
+                metrics.push(metric.as_str());
+                values.push(*value);
+            }
+        }
+    }
+    let mut columns = keys.into_series();
+    columns.push(Series::new("table", tables));
+    columns.push(Series::new("metric", metrics));
+    columns.push(Series::new("value", values));
+    DataFrame::new(columns)
+}
+
+/// Ticker, year, period and whether it's a TTM figure
+type PeriodKey<'a> = (&'a str, Option<i32>, Option<i32>, bool);
+
+fn wide_frame(history: &[TableHistory]) -> PolarsResult<DataFrame> {
+    // The statements and ratios of a period share the row, whatever their date
+    let mut rows: BTreeMap<PeriodKey, (&FundamentalsRecord, usize)> = BTreeMap::new();
+    let mut figures: HashMap<(usize, String), Option<f64>> = HashMap::new();
+    let mut metrics: Vec<String> = Vec::new();
+    for TableHistory {
+        ticker,
+        table,
+        records,
+    } in history
+    {
+        for (metric, _) in table.fields {
+            let metric = format!("{}_{metric}", table.name);
+            if !metrics.contains(&metric) {
+                metrics.push(metric);
+            }
+        }
+        for record in records {
+            let key = (ticker.as_str(), record.year, record.period, record.is_ttm);
+            let next = rows.len();
+            let (first, row) = rows.entry(key).or_insert((record, next));
+            if first.currency.is_none() {
+                *first = record;
+            }
+            for (metric, value) in &record.figures {
+                figures.insert((*row, format!("{}_{metric}", table.name)), *value);
+            }
+        }
+    }
+
+    let mut rows: Vec<_> = rows.into_iter().collect();
+    rows.sort_by_key(|((ticker, _, period, _), (record, _))| (*ticker, record.date, *period));
+    let mut keys = KeyColumns::default();
+    for ((ticker, ..), (record, _)) in &rows {
+        keys.push(ticker, record);
+    }
+    let mut columns = keys.into_series();
+    for metric in metrics {
+        let values: Vec<Option<f64>> = rows
+            .iter()
+            .map(|(_, (_, row))| figures.get(&(*row, metric.clone())).copied().flatten())
+            .collect();
+        columns.push(Series::new(&metric, values));
+    }
+    DataFrame::new(columns)
+}
+
+fn write_xlsx(df: &DataFrame) -> Result<Vec<u8>, AppError> {
+    if df.height() >= XLSX_MAX_ROWS {
+        return Err(AppError::ValidationError(format!(
+            "{} rows don't fit in an XLSX worksheet, export fewer tickers, the wide layout or a CSV or Parquet file",
+            df.height()
+        )));
+    }
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Fundamentals").map_err(export_error)?;
+    let header = Format::new().set_bold();
+    let date_format = Format::new().set_num_format("yyyy-mm-dd");
+    let epoch = DateTime::UNIX_EPOCH.date_naive();
+
+    for (column, series) in df.get_columns().iter().enumerate() {
+        let column = column as u16;
+        worksheet
+            .write_string_with_format(0, column, series.name(), &header)
+            .map_err(export_error)?;
+        for (row, value) in series.iter().enumerate() {
+            let row = row as u32 + 1;
+            match value {
+                AnyValue::Float64(number) => worksheet.write_number(row, column, number),
+                AnyValue::Int32(number) => worksheet.write_number(row, column, number),
+                AnyValue::Boolean(boolean) => worksheet.write_boolean(row, column, boolean),
+                AnyValue::String(string) => worksheet.write_string(row, column, string),
+                AnyValue::Date(days) => worksheet.write_datetime_with_format(
+                    row,
+                    column,
+                    epoch + chrono::Duration::days(days.into()),
+                    &date_format,
+                ),
+                _ => continue,
+            }
+            .map_err(export_error)?;
+        }
+    }
+    workbook.save_to_buffer().map_err(export_error)
+}
+
+fn export_error(err: impl ToString) -> AppError {
+    AppError::ExportError(err.to_string())
+}
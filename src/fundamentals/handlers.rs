@@ -0,0 +1,141 @@
+use axum::{
+    extract::{Path, Query},
+    routing::get,
+    Json, Router,
+};
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::{self, IntoParams, OpenApi};
+
+use super::{
+    conversion::convert_records,
+    tables::{find_table, FundamentalsRecord, FundamentalsTable, TABLES},
+    FISCAL_YEAR,
+};
+use crate::{
+    companies::get_company_id,
+    corporate_actions::SplitFactors,
+    server::{AppError, AppResult},
+    AppState,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_fundamentals),
+    components(schemas(FundamentalsRecord),
+    responses(FundamentalsRecord)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/companies/:ticker/fundamentals/:table",
+            get(get_fundamentals),
+        )
+        .with_state(state)
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct FundamentalsQuery {
+    /// Alphabetic code of the currency to convert the monetary figures into, the
+    /// reported currency by default
+    pub currency: Option<String>,
+    /// FY for fiscal years, Q for quarters, both by default
+    pub period: Option<String>,
+    pub from_year: Option<i32>,
+    pub to_year: Option<i32>,
+    /// Restate share counts and per-share figures in today's shares
+    pub adjust_splits: Option<bool>,
+}
+
+impl FundamentalsQuery {
+    pub fn keeps(&self, record: &FundamentalsRecord) -> Result<bool, AppError> {
+        let period = match self.period.as_deref().map(str::to_uppercase).as_deref() {
+            None => true,
+            Some("FY") => record.period == Some(FISCAL_YEAR),
+            Some("Q") => record.period.is_some_and(|period| period != FISCAL_YEAR),
+            Some(period) => {
+                return Err(AppError::ValidationError(format!(
+                    "Unknown period {period}, use FY or Q"
+                )))
+            }
+        };
+        let year = record
+            .year
+            .unwrap_or_else(|| chrono::Datelike::year(&record.date));
+        Ok(period
+            && self.from_year.is_none_or(|from_year| year >= from_year)
+            && self.to_year.is_none_or(|to_year| year <= to_year))
+    }
+
+    /// Records of the table kept by the filters, split adjusted and converted as
+    /// requested
+    pub fn load(
+        &self,
+        table: &FundamentalsTable,
+        company_id: i64,
+        conn: &mut PgConnection,
+    ) -> Result<Vec<FundamentalsRecord>, AppError> {
+        let mut records = Vec::new();
+        for record in table
+            .load(company_id, conn)
+            .map_err(AppError::DatabaseQueryError)?
+        {
+            if self.keeps(&record)? {
+                records.push(record);
+            }
+        }
+        if self.adjust_splits.unwrap_or_default() {
+            let factors =
+                SplitFactors::load(company_id, conn).map_err(AppError::DatabaseQueryError)?;
+            table.adjust_splits(&mut records, &factors);
+        }
+        if let Some(currency) = &self.currency {
+            convert_records(table, &mut records, currency, conn)?;
+        }
+        Ok(records)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/fundamentals/{table}",
+    params(
+        ("ticker", description = "Company's ticker"),
+        ("table", description = "income, balance_sheet, cashflow, per_share, non_gaap, enterprise_value, free_cashflow, growth, efficiency, liquidity, margins, operation_risk, price_to or rentability"),
+        FundamentalsQuery
+    ),
+    responses(
+            (status = 200, body = Vec<FundamentalsRecord>, description = "The history of a company's statement or ratios, oldest first. Stocks are converted at the rate of the statement date, flows at the period average and ratios are left as reported. Split adjusted figures are in today's shares"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn get_fundamentals(
+    Path((ticker, table)): Path<(String, String)>,
+    Query(query_params): Query<FundamentalsQuery>,
+    state: AppState,
+) -> AppResult<Vec<FundamentalsRecord>> {
+    let table = find_table(&table).ok_or_else(|| {
+        AppError::ValidationError(format!(
+            "Unknown table {table}, use one of {}",
+            TABLES
+                .iter()
+                .map(|table| table.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    })?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            query_params.load(table, company_id, conn)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
@@ -0,0 +1,574 @@
+//! Bulk loading of income, balance sheet and cash flow statements for many companies
+use std::collections::{HashMap, HashSet};
+
+use axum::{body::Bytes, extract::Path, http::HeaderMap, routing::post, Extension, Json, Router};
+use chrono::NaiveDate;
+use diesel::{dsl::exists, prelude::*};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use utoipa::{self, OpenApi, ToSchema};
+
+use super::{nearly_equal, FISCAL_YEAR};
+use crate::{
+    companies::get_company_id,
+    db::schema::{
+        balance_sheet_statements, cashflow_statements, currencies, income_statements, periods,
+    },
+    server::{
+        parse_rows, AppError, AppResult, BulkFormat, BulkReport, JWTUserRequest, ParsedRows,
+        RejectedRow,
+    },
+    AppState,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(ingest_statements),
+    components(schemas(IncomeStatementRow, BalanceSheetRow, CashflowStatementRow, BulkReport, RejectedRow),
+    responses(BulkReport)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/statements/:statement/bulk", post(ingest_statements))
+        .with_state(state)
+}
+
+/// What identifies a statement in a bulk body
+struct StatementKey<'a> {
+    ticker: &'a str,
+    year: i32,
+    period: &'a str,
+    currency: &'a str,
+    date: NaiveDate,
+    is_ttm: bool,
+}
+
+struct StatementIds {
+    company_id: i64,
+    period_id: i64,
+    currency_id: i64,
+}
+
+trait StatementRow: DeserializeOwned + Send + 'static {
+    fn key(&self) -> StatementKey<'_>;
+
+    /// Checks that only need the row itself
+    fn check(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn set_ids(&mut self, ids: StatementIds);
+
+    /// Replaces the statement of the same company and period, returns whether there
+    /// was one
+    fn store(&self, conn: &mut PgConnection) -> Result<bool, AppError>;
+}
+
+fn date_taken_error(key: &StatementKey) -> AppError {
+    AppError::ValidationError(format!(
+        "{} already has another statement dated {}",
+        key.ticker, key.date
+    ))
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable, ToSchema)]
+#[diesel(table_name = income_statements)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct IncomeStatementRow {
+    #[diesel(skip_insertion)]
+    ticker: String,
+    #[diesel(skip_insertion)]
+    year: i32,
+    /// FY for the fiscal year or Q1 to Q4
+    #[diesel(skip_insertion)]
+    period: String,
+    /// Alphabetic code of the reported currency
+    #[diesel(skip_insertion)]
+    currency: String,
+    #[serde(skip)]
+    company_id: i64,
+    #[serde(skip)]
+    period_id: Option<i64>,
+    #[serde(skip)]
+    reported_currency_id: Option<i64>,
+    #[serde(default)]
+    is_ttm: bool,
+    #[serde(default)]
+    link: String,
+    #[serde(default)]
+    final_link: String,
+    date: NaiveDate,
+    cost_and_expenses: f64,
+    cost_of_revenue: f64,
+    depreciation_and_amortization: f64,
+    earnings_before_interest_taxes_depreciation_and_amortization: f64,
+    general_and_administrative_expenses: f64,
+    gross_profit: f64,
+    income_before_tax: f64,
+    income_tax_expenses: f64,
+    interest_expense: f64,
+    net_income: f64,
+    net_total_other_income_and_expenses: f64,
+    operating_expenses: f64,
+    operating_income: f64,
+    other_expenses: f64,
+    research_and_development_expenses: f64,
+    revenue: f64,
+    selling_and_marketing_expenses: f64,
+    selling_general_and_administrative_expenses: f64,
+    weighted_average_diluted_shares_outstanding: f64,
+    weighted_average_shares_outstanding: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable, ToSchema)]
+#[diesel(table_name = balance_sheet_statements)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct BalanceSheetRow {
+    #[diesel(skip_insertion)]
+    ticker: String,
+    #[diesel(skip_insertion)]
+    year: i32,
+    /// FY for the fiscal year or Q1 to Q4
+    #[diesel(skip_insertion)]
+    period: String,
+    /// Alphabetic code of the reported currency
+    #[diesel(skip_insertion)]
+    currency: String,
+    #[serde(skip)]
+    company_id: i64,
+    #[serde(skip)]
+    period_id: Option<i64>,
+    #[serde(skip)]
+    reported_currency_id: Option<i64>,
+    #[serde(default)]
+    is_ttm: bool,
+    #[serde(default)]
+    link: String,
+    #[serde(default)]
+    final_link: String,
+    date: NaiveDate,
+    accumulated_other_comprehensive_income_and_loss: f64,
+    accounts_payable: f64,
+    cash_and_cash_equivalents: f64,
+    cash_and_short_term_investments: f64,
+    common_stocks: f64,
+    deferred_revenue: f64,
+    deferred_revenue_non_current: f64,
+    deferred_tax_liabilities_non_current: f64,
+    goodwill: f64,
+    goodwill_and_intangible_assets: f64,
+    intangible_assets: f64,
+    inventory: f64,
+    long_term_debt: f64,
+    long_term_investments: f64,
+    net_debt: f64,
+    net_receivables: f64,
+    other_assets: f64,
+    other_current_assets: f64,
+    other_current_liabilities: f64,
+    other_liabilities: f64,
+    other_non_current_assets: f64,
+    other_non_current_liabilities: f64,
+    other_total_stockholders_equity: f64,
+    preferred_stocks: f64,
+    property_plant_and_equipment: f64,
+    retained_earnings: f64,
+    short_term_debt: f64,
+    short_term_investments: f64,
+    tax_assets: f64,
+    tax_payables: f64,
+    total_assets: f64,
+    total_current_assets: f64,
+    total_current_liabilities: f64,
+    total_debt: f64,
+    total_investments: f64,
+    total_liabilities: f64,
+    total_liabilities_and_total_equity: f64,
+    total_non_current_assets: f64,
+    total_non_current_liabilities: f64,
+    total_stockholders_equity: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable, ToSchema)]
+#[diesel(table_name = cashflow_statements)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct CashflowStatementRow {
+    #[diesel(skip_insertion)]
+    ticker: String,
+    #[diesel(skip_insertion)]
+    year: i32,
+    /// FY for the fiscal year or Q1 to Q4
+    #[diesel(skip_insertion)]
+    period: String,
+    /// Alphabetic code of the reported currency
+    #[diesel(skip_insertion)]
+    currency: String,
+    #[serde(skip)]
+    company_id: i64,
+    #[serde(skip)]
+    period_id: Option<i64>,
+    #[serde(skip)]
+    reported_currency_id: Option<i64>,
+    #[serde(default)]
+    is_ttm: bool,
+    #[serde(default)]
+    link: String,
+    #[serde(default)]
+    final_link: String,
+    date: NaiveDate,
+    acquisitions_net: f64,
+    accounts_payable: f64,
+    accounts_receivable: f64,
+    capital_expenditures: f64,
+    cash_beginning_period: f64,
+    cash_end_period: f64,
+    change_in_working_capital: f64,
+    common_stock_issued: f64,
+    common_stock_repurchased: f64,
+    debt_repayment: f64,
+    deferred_income_tax: f64,
+    depreciation_and_amortization: f64,
+    dividends_paid: f64,
+    effect_of_forex_exchange: f64,
+    financing_activities_cash_flow: f64,
+    free_cash_flow: f64,
+    inventory: f64,
+    investing_activities_cash_flow: f64,
+    investments_in_property_plant_and_equipment: f64,
+    net_change_in_cash: f64,
+    net_income: f64,
+    operating_activities_cash_flow: f64,
+    other_financing_activities: f64,
+    other_investing_activities: f64,
+    other_non_cash_items: f64,
+    other_working_capital: f64,
+    purchases_of_investments: f64,
+    sales_and_maturities_of_investments: f64,
+    stock_based_compensation: f64,
+}
+
+impl StatementRow for IncomeStatementRow {
+    fn key(&self) -> StatementKey<'_> {
+        StatementKey {
+            ticker: &self.ticker,
+            year: self.year,
+            period: &self.period,
+            currency: &self.currency,
+            date: self.date,
+            is_ttm: self.is_ttm,
+        }
+    }
+
+    fn set_ids(&mut self, ids: StatementIds) {
+        self.company_id = ids.company_id;
+        self.period_id = Some(ids.period_id);
+        self.reported_currency_id = Some(ids.currency_id);
+    }
+
+    fn store(&self, conn: &mut PgConnection) -> Result<bool, AppError> {
+        let replaced = diesel::delete(
+            income_statements::table
+                .filter(income_statements::company_id.eq(self.company_id))
+                .filter(income_statements::period_id.eq(self.period_id))
+                .filter(income_statements::is_ttm.eq(self.is_ttm)),
+        )
+        .execute(conn)?;
+        let date_taken = diesel::select(exists(
+            income_statements::table
+                .filter(income_statements::company_id.eq(self.company_id))
+                .filter(income_statements::date.eq(self.date))
+                .filter(income_statements::is_ttm.eq(self.is_ttm)),
+        ))
+        .get_result(conn)?;
+        if date_taken {
+            return Err(date_taken_error(&self.key()));
+        }
+        diesel::insert_into(income_statements::table)
+            .values(self)
+            .execute(conn)?;
+        Ok(replaced > 0)
+    }
+}
+
+impl StatementRow for BalanceSheetRow {
+    fn key(&self) -> StatementKey<'_> {
+        StatementKey {
+            ticker: &self.ticker,
+            year: self.year,
+            period: &self.period,
+            currency: &self.currency,
+            date: self.date,
+            is_ttm: self.is_ttm,
+        }
+    }
+
+    fn check(&self) -> Result<(), AppError> {
+        if nearly_equal(self.total_assets, self.total_liabilities_and_total_equity) {
+            Ok(())
+        } else {
+            Err(AppError::ValidationError(format!(
+                "total_assets {} doesn't match total_liabilities_and_total_equity {}",
+                self.total_assets, self.total_liabilities_and_total_equity
+            )))
+        }
+    }
+
+    fn set_ids(&mut self, ids: StatementIds) {
+        self.company_id = ids.company_id;
+        self.period_id = Some(ids.period_id);
+        self.reported_currency_id = Some(ids.currency_id);
+    }
+
+    fn store(&self, conn: &mut PgConnection) -> Result<bool, AppError> {
+        let replaced = diesel::delete(
+            balance_sheet_statements::table
+                .filter(balance_sheet_statements::company_id.eq(self.company_id))
+                .filter(balance_sheet_statements::period_id.eq(self.period_id))
+                .filter(balance_sheet_statements::is_ttm.eq(self.is_ttm)),
+        )
+        .execute(conn)?;
+        let date_taken = diesel::select(exists(
+            balance_sheet_statements::table
+                .filter(balance_sheet_statements::company_id.eq(self.company_id))
+                .filter(balance_sheet_statements::date.eq(self.date))
+                .filter(balance_sheet_statements::is_ttm.eq(self.is_ttm)),
+        ))
+        .get_result(conn)?;
+        if date_taken {
+            return Err(date_taken_error(&self.key()));
+        }
+        diesel::insert_into(balance_sheet_statements::table)
+            .values(self)
+            .execute(conn)?;
+        Ok(replaced > 0)
+    }
+}
+
+impl StatementRow for CashflowStatementRow {
+    fn key(&self) -> StatementKey<'_> {
+        StatementKey {
+            ticker: &self.ticker,
+            year: self.year,
+            period: &self.period,
+            currency: &self.currency,
+            date: self.date,
+            is_ttm: self.is_ttm,
+        }
+    }
+
+    fn set_ids(&mut self, ids: StatementIds) {
+        self.company_id = ids.company_id;
+        self.period_id = Some(ids.period_id);
+        self.reported_currency_id = Some(ids.currency_id);
+    }
+
+    fn store(&self, conn: &mut PgConnection) -> Result<bool, AppError> {
+        let replaced = diesel::delete(
+            cashflow_statements::table
+                .filter(cashflow_statements::company_id.eq(self.company_id))
+                .filter(cashflow_statements::period_id.eq(self.period_id))
+                .filter(cashflow_statements::is_ttm.eq(self.is_ttm)),
+        )
+        .execute(conn)?;
+        let date_taken = diesel::select(exists(
+            cashflow_statements::table
+                .filter(cashflow_statements::company_id.eq(self.company_id))
+                .filter(cashflow_statements::date.eq(self.date))
+                .filter(cashflow_statements::is_ttm.eq(self.is_ttm)),
+        ))
+        .get_result(conn)?;
+        if date_taken {
+            return Err(date_taken_error(&self.key()));
+        }
+        diesel::insert_into(cashflow_statements::table)
+            .values(self)
+            .execute(conn)?;
+        Ok(replaced > 0)
+    }
+}
+
+/// `periods.period` from its code, FY or Q1 to Q4
+fn parse_period(code: &str) -> Result<i32, AppError> {
+    match code.trim().to_uppercase().as_str() {
+        "FY" => Ok(FISCAL_YEAR),
+        "Q1" => Ok(1),
+        "Q2" => Ok(2),
+        "Q3" => Ok(3),
+        "Q4" => Ok(4),
+        _ => Err(AppError::ValidationError(format!(
+            "Unknown period {code}, use FY or Q1 to Q4"
+        ))),
+    }
+}
+
+/// Companies, currencies and periods already looked up during an ingestion
+#[derive(Default)]
+struct Lookups {
+    companies: HashMap<String, Option<i64>>,
+    currencies: HashMap<String, Option<i64>>,
+    periods: HashMap<(i32, i32), i64>,
+}
+
+impl Lookups {
+    fn company(&mut self, ticker: &str, conn: &mut PgConnection) -> Result<i64, AppError> {
+        if !self.companies.contains_key(ticker) {
+            let id = match get_company_id(ticker, conn) {
+                Ok(id) => Some(id),
+                Err(AppError::DoesNotExist) => None,
+                Err(err) => return Err(err),
+            };
+            self.companies.insert(ticker.to_string(), id);
+        }
+        self.companies[ticker]
+            .ok_or_else(|| AppError::ValidationError(format!("Unknown ticker {ticker}")))
+    }
+
+    fn currency(&mut self, code: &str, conn: &mut PgConnection) -> Result<i64, AppError> {
+        let code = code.trim().to_uppercase();
+        if !self.currencies.contains_key(&code) {
+            let id = currencies::table
+                .filter(currencies::alphabetic_code.eq(&code))
+                .select(currencies::id)
+                .first(conn)
+                .optional()?;
+            self.currencies.insert(code.clone(), id);
+        }
+        self.currencies[&code]
+            .ok_or_else(|| AppError::ValidationError(format!("Unknown currency {code}")))
+    }
+
+    /// Periods are created the first time a year and period show up
+    fn period(&mut self, year: i32, period: i32, conn: &mut PgConnection) -> Result<i64, AppError> {
+        if let Some(id) = self.periods.get(&(year, period)) {
+            return Ok(*id);
+        }
+        let existing = periods::table
+            .filter(periods::year.eq(year))
+            .filter(periods::period.eq(period))
+            .select(periods::id)
+            .first(conn)
+            .optional()?;
+        let id = match existing {
+            Some(id) => id,
+            None => diesel::insert_into(periods::table)
+                .values((periods::year.eq(year), periods::period.eq(period)))
+                .returning(periods::id)
+                .get_result(conn)?,
+        };
+        self.periods.insert((year, period), id);
+        Ok(id)
+    }
+}
+
+fn ingest_rows<R: StatementRow>(
+    rows: ParsedRows<R>,
+    conn: &mut PgConnection,
+) -> Result<BulkReport, AppError> {
+    let mut report = BulkReport::default();
+    let mut lookups = Lookups::default();
+    let mut seen_periods = HashSet::new();
+    let mut seen_dates = HashSet::new();
+    for (number, row) in rows {
+        let mut row = match row {
+            Ok(row) => row,
+            Err(reason) => {
+                report.reject(number, reason);
+                continue;
+            }
+        };
+        // Periods are created outside the row's savepoint, rolling a rejected row back
+        // would otherwise delete one the lookups keep for the rows that follow
+        let year = row.key().year;
+        let period = row
+            .check()
+            .and_then(|()| parse_period(row.key().period))
+            .and_then(|period| Ok((period, lookups.period(year, period, conn)?)));
+        let (period, period_id) = match period {
+            Ok(period) => period,
+            Err(err) => {
+                report.reject(number, err);
+                continue;
+            }
+        };
+        let stored = conn.transaction(|conn| {
+            let key = row.key();
+            let period_key = (key.ticker.to_string(), key.year, period, key.is_ttm);
+            let date_key = (key.ticker.to_string(), key.date, key.is_ttm);
+            if seen_periods.contains(&period_key) {
+                return Err(AppError::ValidationError(format!(
+                    "{} {} {} is repeated in the body",
+                    key.ticker, key.year, key.period
+                )));
+            }
+            if seen_dates.contains(&date_key) {
+                return Err(AppError::ValidationError(format!(
+                    "{} {} is repeated in the body",
+                    key.ticker, key.date
+                )));
+            }
+            let ids = StatementIds {
+                company_id: lookups.company(key.ticker, conn)?,
+                period_id,
+                currency_id: lookups.currency(key.currency, conn)?,
+            };
+            row.set_ids(ids);
+            let replaced = row.store(conn)?;
+            seen_periods.insert(period_key);
+            seen_dates.insert(date_key);
+            Ok::<_, AppError>(replaced)
+        });
+        match stored {
+            Ok(true) => report.updated += 1,
+            Ok(false) => report.inserted += 1,
+            Err(err) => report.reject(number, err),
+        }
+    }
+    Ok(report)
+}
+
+async fn ingest<R: StatementRow>(
+    state: AppState,
+    format: BulkFormat,
+    body: Bytes,
+) -> Result<BulkReport, AppError> {
+    let rows = parse_rows::<R>(format, &body)?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| conn.transaction(|conn| ingest_rows(rows, conn)))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+}
+
+#[utoipa::path(
+    post,
+    path = "statements/{statement}/bulk",
+    params(("statement", description = "income, balance_sheet or cashflow")),
+    request_body(content = String, description = "Statements of any company as a JSON array, NDJSON or CSV with a header line. A statement replaces the one of the same company and period.", content_type = "application/json"),
+    responses(
+        (status = 200, body = BulkReport, description = "How many statements were inserted, replaced or rejected and why, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn ingest_statements(
+    Path(statement): Path<String>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<BulkReport> {
+    current_user.require_staff()?;
+    let format = BulkFormat::from_headers(&headers)?;
+    match statement.as_str() {
+        "income" => ingest::<IncomeStatementRow>(state, format, body).await,
+        "balance_sheet" => ingest::<BalanceSheetRow>(state, format, body).await,
+        "cashflow" => ingest::<CashflowStatementRow>(state, format, body).await,
+        _ => Err(AppError::ValidationError(format!(
+            "Unknown statement {statement}, use income, balance_sheet or cashflow"
+        ))),
+    }
+    .map(Json)
+}
@@ -0,0 +1,33 @@
+mod conversion;
+mod export;
+mod handlers;
+mod ingestion;
+mod quality;
+mod querysets;
+mod tables;
+
+pub use export::{routes as export_routes, ApiDoc as ApiDocExport};
+pub use handlers::{routes, ApiDoc};
+pub use ingestion::{routes as ingestion_routes, ApiDoc as ApiDocIngestion};
+pub use quality::{refresh_findings, routes as quality_routes, ApiDoc as ApiDocQuality};
+pub use querysets::{free_cash_flow_growth_history, free_cash_flow_history, latest_diluted_shares};
+pub use tables::reported_in;
+
+/// `periods.period` value of a full fiscal year, quarters go from 1 to 4
+pub const FISCAL_YEAR: i32 = 5;
+
+/// Code of a `periods.period` value, FY or Q1 to Q4
+pub fn period_code(period: i32) -> String {
+    match period {
+        FISCAL_YEAR => "FY".to_string(),
+        quarter => format!("Q{quarter}"),
+    }
+}
+
+/// Relative difference tolerated between figures that should be equal, feeds round
+/// them
+pub const TOLERANCE: f64 = 0.001;
+
+pub fn nearly_equal(expected: f64, actual: f64) -> bool {
+    (expected - actual).abs() <= TOLERANCE * expected.abs().max(1.0)
+}
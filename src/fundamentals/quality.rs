@@ -0,0 +1,480 @@
+//! Data quality checks over the statement and ratio tables. Findings are recomputed
+//! per company so fixed feeds clear their own findings on the next scan.
+use std::collections::BTreeSet;
+
+use axum::{
+    extract::Query,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{self, IntoParams, OpenApi, ToResponse, ToSchema};
+
+use super::{nearly_equal, FISCAL_YEAR};
+use crate::{
+    companies::get_company_id,
+    db::{
+        schema::{
+            balance_sheet_statements, cashflow_statements, companies, income_statements,
+            liquidity_ratios, margin_ratios, operation_risk_ratios, periods, statement_findings,
+        },
+        Paginate,
+    },
+    server::{AppError, AppResult, JWTUserRequest},
+    AppState,
+};
+
+pub const UNBALANCED_BALANCE_SHEET: &str = "unbalanced_balance_sheet";
+pub const CASH_CHANGE_MISMATCH: &str = "cash_change_mismatch";
+pub const NET_INCOME_MISMATCH: &str = "net_income_mismatch";
+pub const IMPOSSIBLE_RATIO: &str = "impossible_ratio";
+pub const MISSING_PERIOD: &str = "missing_period";
+
+/// Findings inserted per statement, each takes 8 of the 65535 bind parameters
+const CHUNK_SIZE: usize = 1000;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(list_findings, scan_findings),
+    components(schemas(Finding, FindingsResponse),
+    responses(FindingsResponse)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/statements/findings", get(list_findings))
+        .route("/statements/findings/scan", post(scan_findings))
+        .with_state(state)
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = statement_findings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct NewFinding {
+    company_id: i64,
+    period_id: Option<i64>,
+    date: Option<NaiveDate>,
+    rule: &'static str,
+    source_table: &'static str,
+    message: String,
+    expected: Option<f64>,
+    actual: Option<f64>,
+}
+
+fn unbalanced_balance_sheets(
+    company_id: i64,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<NewFinding>> {
+    let rows: Vec<(Option<i64>, NaiveDate, f64, f64)> = balance_sheet_statements::table
+        .filter(balance_sheet_statements::company_id.eq(company_id))
+        .select((
+            balance_sheet_statements::period_id,
+            balance_sheet_statements::date,
+            balance_sheet_statements::total_assets,
+            balance_sheet_statements::total_liabilities_and_total_equity,
+        ))
+        .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .filter(|(_, _, assets, liabilities_and_equity)| {
+            !nearly_equal(*assets, *liabilities_and_equity)
+        })
+        .map(
+            |(period_id, date, assets, liabilities_and_equity)| NewFinding {
+                company_id,
+                period_id,
+                date: Some(date),
+                rule: UNBALANCED_BALANCE_SHEET,
+                source_table: "balance_sheet_statements",
+                message: "total_liabilities_and_total_equity doesn't match total_assets".into(),
+                expected: Some(assets),
+                actual: Some(liabilities_and_equity),
+            },
+        )
+        .collect())
+}
+
+fn cash_change_mismatches(
+    company_id: i64,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<NewFinding>> {
+    let rows: Vec<(Option<i64>, NaiveDate, f64, f64, f64)> = cashflow_statements::table
+        .filter(cashflow_statements::company_id.eq(company_id))
+        .select((
+            cashflow_statements::period_id,
+            cashflow_statements::date,
+            cashflow_statements::net_change_in_cash,
+            cashflow_statements::cash_beginning_period,
+            cashflow_statements::cash_end_period,
+        ))
+        .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .filter(|(_, _, change, beginning, end)| !nearly_equal(end - beginning, *change))
+        .map(|(period_id, date, change, beginning, end)| NewFinding {
+            company_id,
+            period_id,
+            date: Some(date),
+            rule: CASH_CHANGE_MISMATCH,
+            source_table: "cashflow_statements",
+            message: "net_change_in_cash doesn't match cash_end_period - cash_beginning_period"
+                .into(),
+            expected: Some(end - beginning),
+            actual: Some(change),
+        })
+        .collect())
+}
+
+fn net_income_mismatches(company_id: i64, conn: &mut PgConnection) -> QueryResult<Vec<NewFinding>> {
+    let rows: Vec<(Option<i64>, NaiveDate, f64, f64)> = income_statements::table
+        .inner_join(
+            cashflow_statements::table.on(cashflow_statements::company_id
+                .eq(income_statements::company_id)
+                .and(cashflow_statements::period_id.eq(income_statements::period_id))
+                .and(cashflow_statements::is_ttm.eq(income_statements::is_ttm))),
+        )
+        .filter(income_statements::company_id.eq(company_id))
+        .select((
+            income_statements::period_id,
+            income_statements::date,
+            income_statements::net_income,
+            cashflow_statements::net_income,
+        ))
+        .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .filter(|(_, _, income, cashflow)| !nearly_equal(*income, *cashflow))
+        .map(|(period_id, date, income, cashflow)| NewFinding {
+            company_id,
+            period_id,
+            date: Some(date),
+            rule: NET_INCOME_MISMATCH,
+            source_table: "cashflow_statements",
+            message: "net_income differs from the income statement".into(),
+            expected: Some(income),
+            actual: Some(cashflow),
+        })
+        .collect())
+}
+
+/// Ratios that can't be negative or that contradict another ratio of the same period
+fn impossible_ratios(company_id: i64, conn: &mut PgConnection) -> QueryResult<Vec<NewFinding>> {
+    let mut findings = Vec::new();
+    let mut check = |period_id, date, source_table, ratio: &str, value: f64, reason: &str| {
+        findings.push(NewFinding {
+            company_id,
+            period_id,
+            date: Some(date),
+            rule: IMPOSSIBLE_RATIO,
+            source_table,
+            message: format!("{ratio} {reason}"),
+            expected: None,
+            actual: Some(value),
+        })
+    };
+
+    let liquidity: Vec<(Option<i64>, NaiveDate, f64, f64, f64)> = liquidity_ratios::table
+        .filter(liquidity_ratios::company_id.eq(company_id))
+        .select((
+            liquidity_ratios::period_id,
+            liquidity_ratios::date,
+            liquidity_ratios::cash_ratio,
+            liquidity_ratios::current_ratio,
+            liquidity_ratios::quick_ratio,
+        ))
+        .load(conn)?;
+    for (period_id, date, cash_ratio, current_ratio, quick_ratio) in liquidity {
+        let ratios = [
+            ("cash_ratio", cash_ratio),
+            ("current_ratio", current_ratio),
+            ("quick_ratio", quick_ratio),
+        ];
+        for (ratio, value) in ratios {
+            if !value.is_finite() || value < 0.0 {
+                check(
+                    period_id,
+                    date,
+                    "liquidity_ratios",
+                    ratio,
+                    value,
+                    "must be a non negative number",
+                );
+            }
+        }
+        // The quick ratio leaves inventory out of the current assets
+        if quick_ratio > current_ratio && !nearly_equal(quick_ratio, current_ratio) {
+            check(
+                period_id,
+                date,
+                "liquidity_ratios",
+                "quick_ratio",
+                quick_ratio,
+                "can't exceed current_ratio",
+            );
+        }
+    }
+
+    let risk: Vec<(Option<i64>, NaiveDate, f64)> = operation_risk_ratios::table
+        .filter(operation_risk_ratios::company_id.eq(company_id))
+        .select((
+            operation_risk_ratios::period_id,
+            operation_risk_ratios::date,
+            operation_risk_ratios::debt_ratio,
+        ))
+        .load(conn)?;
+    for (period_id, date, debt_ratio) in risk {
+        if !debt_ratio.is_finite() || debt_ratio < 0.0 {
+            check(
+                period_id,
+                date,
+                "operation_risk_ratios",
+                "debt_ratio",
+                debt_ratio,
+                "must be a non negative number",
+            );
+        }
+    }
+
+    let margins: Vec<(Option<i64>, NaiveDate, f64, f64, f64)> = margin_ratios::table
+        .filter(margin_ratios::company_id.eq(company_id))
+        .select((
+            margin_ratios::period_id,
+            margin_ratios::date,
+            margin_ratios::gross_margin,
+            margin_ratios::net_income_margin,
+            margin_ratios::free_cash_flow_margin,
+        ))
+        .load(conn)?;
+    for (period_id, date, gross_margin, net_income_margin, free_cash_flow_margin) in margins {
+        let ratios = [
+            ("gross_margin", gross_margin),
+            ("net_income_margin", net_income_margin),
+            ("free_cash_flow_margin", free_cash_flow_margin),
+        ];
+        for (ratio, value) in ratios {
+            if !value.is_finite() {
+                check(
+                    period_id,
+                    date,
+                    "margin_ratios",
+                    ratio,
+                    value,
+                    "isn't a number",
+                );
+            }
+        }
+    }
+    Ok(findings)
+}
+
+/// Fiscal years missing from a statement table between the first and last fiscal year
+/// reported in any of them
+fn missing_periods(company_id: i64, conn: &mut PgConnection) -> QueryResult<Vec<NewFinding>> {
+    let income: BTreeSet<i32> = income_statements::table
+        .inner_join(periods::table)
+        .filter(income_statements::company_id.eq(company_id))
+        .filter(income_statements::is_ttm.eq(false))
+        .filter(periods::period.eq(FISCAL_YEAR))
+        .select(periods::year)
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+    let balance_sheet: BTreeSet<i32> = balance_sheet_statements::table
+        .inner_join(periods::table)
+        .filter(balance_sheet_statements::company_id.eq(company_id))
+        .filter(balance_sheet_statements::is_ttm.eq(false))
+        .filter(periods::period.eq(FISCAL_YEAR))
+        .select(periods::year)
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+    let cashflow: BTreeSet<i32> = cashflow_statements::table
+        .inner_join(periods::table)
+        .filter(cashflow_statements::company_id.eq(company_id))
+        .filter(cashflow_statements::is_ttm.eq(false))
+        .filter(periods::period.eq(FISCAL_YEAR))
+        .select(periods::year)
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+
+    let all_years = income.iter().chain(&balance_sheet).chain(&cashflow);
+    let (Some(first), Some(last)) = (all_years.clone().min(), all_years.max()) else {
+        return Ok(Vec::new());
+    };
+    let tables = [
+        ("income_statements", &income),
+        ("balance_sheet_statements", &balance_sheet),
+        ("cashflow_statements", &cashflow),
+    ];
+    Ok(tables
+        .into_iter()
+        .flat_map(|(source_table, years)| {
+            (*first..=*last)
+                .filter(|year| !years.contains(year))
+                .map(move |year| NewFinding {
+                    company_id,
+                    period_id: None,
+                    date: None,
+                    rule: MISSING_PERIOD,
+                    source_table,
+                    message: format!("Fiscal year {year} is missing"),
+                    expected: None,
+                    actual: None,
+                })
+        })
+        .collect())
+}
+
+/// Runs every check over a company and replaces its findings, returns how many were
+/// found
+pub fn scan_company(company_id: i64, conn: &mut PgConnection) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let mut findings = unbalanced_balance_sheets(company_id, conn)?;
+        findings.extend(cash_change_mismatches(company_id, conn)?);
+        findings.extend(net_income_mismatches(company_id, conn)?);
+        findings.extend(impossible_ratios(company_id, conn)?);
+        findings.extend(missing_periods(company_id, conn)?);
+
+        diesel::delete(
+            statement_findings::table.filter(statement_findings::company_id.eq(company_id)),
+        )
+        .execute(conn)?;
+        for chunk in findings.chunks(CHUNK_SIZE) {
+            diesel::insert_into(statement_findings::table)
+                .values(chunk)
+                .execute(conn)?;
+        }
+        Ok(findings.len())
+    })
+}
+
+pub fn refresh_findings(conn: &mut PgConnection) -> QueryResult<()> {
+    let company_ids: Vec<i64> = companies::table.select(companies::id).load(conn)?;
+    for company_id in company_ids {
+        scan_company(company_id, conn)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+struct FindingsQuery {
+    ticker: Option<String>,
+    /// unbalanced_balance_sheet, cash_change_mismatch, net_income_mismatch,
+    /// impossible_ratio or missing_period
+    rule: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(Debug, Queryable, Serialize, Deserialize, ToSchema)]
+struct Finding {
+    ticker: String,
+    year: Option<i32>,
+    period: Option<i32>,
+    date: Option<NaiveDate>,
+    rule: String,
+    source_table: String,
+    message: String,
+    expected: Option<f64>,
+    actual: Option<f64>,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+struct FindingsResponse {
+    data: Vec<Finding>,
+    total_pages: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "statements/findings",
+    params(FindingsQuery),
+    responses(
+            (status = 200, body = FindingsResponse, description = "A paginated result of the inconsistencies found in the statements and ratios, staff only"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn list_findings(
+    Query(query_params): Query<FindingsQuery>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<FindingsResponse> {
+    current_user.require_staff()?;
+    let (data, total_pages) = state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let mut query = statement_findings::table
+                .inner_join(companies::table)
+                .left_join(periods::table)
+                .into_boxed();
+            if let Some(ticker) = query_params.ticker {
+                query = query.filter(companies::ticker.eq(ticker));
+            }
+            if let Some(rule) = query_params.rule {
+                query = query.filter(statement_findings::rule.eq(rule));
+            }
+            query
+                .order((companies::ticker, statement_findings::date.desc()))
+                .select((
+                    companies::ticker,
+                    periods::year.nullable(),
+                    periods::period.nullable(),
+                    statement_findings::date,
+                    statement_findings::rule,
+                    statement_findings::source_table,
+                    statement_findings::message,
+                    statement_findings::expected,
+                    statement_findings::actual,
+                    statement_findings::created_at,
+                ))
+                .paginate(query_params.page.unwrap_or(1))
+                .per_page(query_params.per_page.unwrap_or(25))
+                .load_and_count_pages::<Finding>(conn)
+                .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)??;
+
+    Ok(Json(FindingsResponse { data, total_pages }))
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+struct ScanQuery {
+    ticker: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "statements/findings/scan",
+    params(ScanQuery),
+    responses(
+            (status = 200, body = usize, description = "Checks a company right away, returns how many findings it has, staff only"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn scan_findings(
+    Query(query_params): Query<ScanQuery>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<usize> {
+    current_user.require_staff()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&query_params.ticker, conn)?;
+            scan_company(company_id, conn).map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
@@ -0,0 +1,70 @@
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+use super::FISCAL_YEAR;
+use crate::db::schema::{
+    cashflow_statements, company_growth, free_cashflow_ratios, income_statements, periods,
+};
+
+/// Yearly free cash flow, most recent first. Falls back to the cash-flow statements when
+/// the ratios haven't been computed for the company.
+pub fn free_cash_flow_history(
+    company_id: i64,
+    years: i64,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<(NaiveDate, f64)>> {
+    let history = free_cashflow_ratios::table
+        .inner_join(periods::table)
+        .filter(free_cashflow_ratios::company_id.eq(company_id))
+        .filter(free_cashflow_ratios::is_ttm.eq(false))
+        .filter(periods::period.eq(FISCAL_YEAR))
+        .order(free_cashflow_ratios::date.desc())
+        .limit(years)
+        .select((
+            free_cashflow_ratios::date,
+            free_cashflow_ratios::free_cash_flow,
+        ))
+        .load(conn)?;
+    if !history.is_empty() {
+        return Ok(history);
+    }
+    cashflow_statements::table
+        .inner_join(periods::table)
+        .filter(cashflow_statements::company_id.eq(company_id))
+        .filter(cashflow_statements::is_ttm.eq(false))
+        .filter(periods::period.eq(FISCAL_YEAR))
+        .order(cashflow_statements::date.desc())
+        .limit(years)
+        .select((
+            cashflow_statements::date,
+            cashflow_statements::free_cash_flow,
+        ))
+        .load(conn)
+}
+
+/// Yearly free cash flow growth, most recent first
+pub fn free_cash_flow_growth_history(
+    company_id: i64,
+    years: i64,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<f64>> {
+    company_growth::table
+        .inner_join(periods::table)
+        .filter(company_growth::company_id.eq(company_id))
+        .filter(company_growth::is_ttm.eq(false))
+        .filter(periods::period.eq(FISCAL_YEAR))
+        .order(company_growth::date.desc())
+        .limit(years)
+        .select(company_growth::free_cash_flow_growth)
+        .load(conn)
+}
+
+pub fn latest_diluted_shares(company_id: i64, conn: &mut PgConnection) -> QueryResult<Option<f64>> {
+    income_statements::table
+        .filter(income_statements::company_id.eq(company_id))
+        .filter(income_statements::is_ttm.eq(false))
+        .order(income_statements::date.desc())
+        .select(income_statements::weighted_average_diluted_shares_outstanding)
+        .first(conn)
+        .optional()
+}
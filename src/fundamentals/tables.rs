@@ -0,0 +1,474 @@
+//! Every table with company fundamentals, declared once with the kind of each figure
+//! so conversions and exports don't need to know the tables one by one
+use std::collections::BTreeMap;
+
+use chrono::{Months, NaiveDate};
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Bool, Date, Integer, Jsonb, Nullable, Varchar},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{ToResponse, ToSchema};
+
+use super::FISCAL_YEAR;
+use crate::corporate_actions::SplitFactors;
+use FieldKind::*;
+
+/// How a figure behaves when it's converted to another currency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// Balance at the statement date, converted at the rate of that date
+    Stock,
+    /// Balance at the start of the period, converted at the rate of its first day
+    OpeningStock,
+    /// Accumulated over the period, converted at the period average rate
+    Flow,
+    /// Ratios, margins, growths and yields, never converted
+    Ratio,
+    /// Share counts, never converted
+    Shares,
+}
+
+/// Rate a figure is converted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+    Unchanged,
+    OpeningRate,
+    ClosingRate,
+    AverageRate,
+}
+
+impl FieldKind {
+    pub const fn conversion(self) -> Conversion {
+        match self {
+            Stock => Conversion::ClosingRate,
+            OpeningStock => Conversion::OpeningRate,
+            Flow => Conversion::AverageRate,
+            Ratio | Shares => Conversion::Unchanged,
+        }
+    }
+}
+
+pub struct FundamentalsTable {
+    /// Name used in the API
+    pub name: &'static str,
+    pub table: &'static str,
+    pub fields: &'static [(&'static str, FieldKind)],
+}
+
+pub const TABLES: &[FundamentalsTable] = &[
+    FundamentalsTable {
+        name: "income",
+        table: "income_statements",
+        fields: &[
+            ("cost_and_expenses", Flow),
+            ("cost_of_revenue", Flow),
+            ("depreciation_and_amortization", Flow),
+            (
+                "earnings_before_interest_taxes_depreciation_and_amortization",
+                Flow,
+            ),
+            ("general_and_administrative_expenses", Flow),
+            ("gross_profit", Flow),
+            ("income_before_tax", Flow),
+            ("income_tax_expenses", Flow),
+            ("interest_expense", Flow),
+            ("net_income", Flow),
+            ("net_total_other_income_and_expenses", Flow),
+            ("operating_expenses", Flow),
+            ("operating_income", Flow),
+            ("other_expenses", Flow),
+            ("research_and_development_expenses", Flow),
+            ("revenue", Flow),
+            ("selling_and_marketing_expenses", Flow),
+            ("selling_general_and_administrative_expenses", Flow),
+            ("weighted_average_diluted_shares_outstanding", Shares),
+            ("weighted_average_shares_outstanding", Shares),
+        ],
+    },
+    FundamentalsTable {
+        name: "balance_sheet",
+        table: "balance_sheet_statements",
+        fields: &[
+            ("accumulated_other_comprehensive_income_and_loss", Stock),
+            ("accounts_payable", Stock),
+            ("cash_and_cash_equivalents", Stock),
+            ("cash_and_short_term_investments", Stock),
+            ("common_stocks", Stock),
+            ("deferred_revenue", Stock),
+            ("deferred_revenue_non_current", Stock),
+            ("deferred_tax_liabilities_non_current", Stock),
+            ("goodwill", Stock),
+            ("goodwill_and_intangible_assets", Stock),
+            ("intangible_assets", Stock),
+            ("inventory", Stock),
+            ("long_term_debt", Stock),
+            ("long_term_investments", Stock),
+            ("net_debt", Stock),
+            ("net_receivables", Stock),
+            ("other_assets", Stock),
+            ("other_current_assets", Stock),
+            ("other_current_liabilities", Stock),
+            ("other_liabilities", Stock),
+            ("other_non_current_assets", Stock),
+            ("other_non_current_liabilities", Stock),
+            ("other_total_stockholders_equity", Stock),
+            ("preferred_stocks", Stock),
+            ("property_plant_and_equipment", Stock),
+            ("retained_earnings", Stock),
+            ("short_term_debt", Stock),
+            ("short_term_investments", Stock),
+            ("tax_assets", Stock),
+            ("tax_payables", Stock),
+            ("total_assets", Stock),
+            ("total_current_assets", Stock),
+            ("total_current_liabilities", Stock),
+            ("total_debt", Stock),
+            ("total_investments", Stock),
+            ("total_liabilities", Stock),
+            ("total_liabilities_and_total_equity", Stock),
+            ("total_non_current_assets", Stock),
+            ("total_non_current_liabilities", Stock),
+            ("total_stockholders_equity", Stock),
+        ],
+    },
+    FundamentalsTable {
+        name: "cashflow",
+        table: "cashflow_statements",
+        fields: &[
+            ("acquisitions_net", Flow),
+            ("accounts_payable", Flow),
+            ("accounts_receivable", Flow),
+            ("capital_expenditures", Flow),
+            ("cash_beginning_period", OpeningStock),
+            ("cash_end_period", Stock),
+            ("change_in_working_capital", Flow),
+            ("common_stock_issued", Flow),
+            ("common_stock_repurchased", Flow),
+            ("debt_repayment", Flow),
+            ("deferred_income_tax", Flow),
+            ("depreciation_and_amortization", Flow),
+            ("dividends_paid", Flow),
+            ("effect_of_forex_exchange", Flow),
+            ("financing_activities_cash_flow", Flow),
+            ("free_cash_flow", Flow),
+            ("inventory", Flow),
+            ("investing_activities_cash_flow", Flow),
+            ("investments_in_property_plant_and_equipment", Flow),
+            ("net_change_in_cash", Flow),
+            ("net_income", Flow),
+            ("operating_activities_cash_flow", Flow),
+            ("other_financing_activities", Flow),
+            ("other_investing_activities", Flow),
+            ("other_non_cash_items", Flow),
+            ("other_working_capital", Flow),
+            ("purchases_of_investments", Flow),
+            ("sales_and_maturities_of_investments", Flow),
+            ("stock_based_compensation", Flow),
+        ],
+    },
+    FundamentalsTable {
+        name: "per_share",
+        table: "per_share_values",
+        fields: &[
+            ("book_value_per_share", Stock),
+            ("capital_expenditure_per_share", Flow),
+            ("cash_per_share", Stock),
+            ("earnings_per_share", Flow),
+            ("free_cash_flow_per_share", Flow),
+            ("operating_cash_flow_per_share", Flow),
+            ("sales_per_share", Flow),
+            ("tangible_book_value_per_share", Stock),
+            ("total_assets_per_share", Stock),
+        ],
+    },
+    FundamentalsTable {
+        name: "non_gaap",
+        table: "non_gaap_figures",
+        fields: &[
+            ("average_accounts_payable", Flow),
+            ("average_inventory", Flow),
+            ("dividend_yield", Ratio),
+            ("earnings_yield", Ratio),
+            ("effective_tax_rate", Ratio),
+            ("free_cash_flow_yield", Ratio),
+            ("income_quality", Ratio),
+            ("invested_capital", Stock),
+            ("market_capitalization", Stock),
+            ("net_current_asset_value", Stock),
+            ("net_operating_profit_after_tax", Flow),
+            ("normalized_income", Flow),
+            ("payout_ratio", Ratio),
+            ("retention_ratio", Ratio),
+            ("tangible_assets", Stock),
+        ],
+    },
+    FundamentalsTable {
+        name: "enterprise_value",
+        table: "enterprise_value_ratios",
+        fields: &[
+            ("company_equity_multiplier", Ratio),
+            ("enterprise_value", Stock),
+            ("enterprise_value_to_free_cash_flow", Ratio),
+            ("enterprise_value_to_operating_cash_flow", Ratio),
+            ("enterprise_value_to_sales", Ratio),
+            ("enterprise_value_multiple", Ratio),
+            ("market_capitalization", Stock),
+        ],
+    },
+    FundamentalsTable {
+        name: "free_cashflow",
+        table: "free_cashflow_ratios",
+        fields: &[
+            ("free_cash_flow", Flow),
+            ("free_cash_flow_equity", Flow),
+            ("unlevered_free_cash_flow", Flow),
+            ("unlevered_free_cash_flow_ebit", Flow),
+            ("owners_earnings", Flow),
+        ],
+    },
+    FundamentalsTable {
+        name: "growth",
+        table: "company_growth",
+        fields: &[
+            ("capital_expenditure_growth", Ratio),
+            ("cost_of_revenue_growth", Ratio),
+            ("earnings_per_share_growth", Ratio),
+            ("free_cash_flow_growth", Ratio),
+            ("net_income_growth", Ratio),
+            ("operating_expenses_growth", Ratio),
+            ("owners_earnings_growth", Ratio),
+            ("research_and_development_expenses_growth", Ratio),
+            ("revenue_growth", Ratio),
+            ("shares_buyback", Ratio),
+        ],
+    },
+    FundamentalsTable {
+        name: "efficiency",
+        table: "efficiency_ratios",
+        fields: &[
+            ("accounts_payable_turnover", Ratio),
+            ("asset_turnover", Ratio),
+            ("cash_conversion_cycle", Ratio),
+            ("cash_conversion_ratio", Ratio),
+            ("days_inventory_outstanding", Ratio),
+            ("days_payables_outstanding", Ratio),
+            ("days_sales_outstanding", Ratio),
+            ("fixed_asset_turnover", Ratio),
+            ("free_cash_flow_to_operating_cash_flow", Ratio),
+            ("inventory_turnover", Ratio),
+            ("operating_cycle", Ratio),
+        ],
+    },
+    FundamentalsTable {
+        name: "liquidity",
+        table: "liquidity_ratios",
+        fields: &[
+            ("cash_ratio", Ratio),
+            ("current_ratio", Ratio),
+            ("debt_to_equity_ratio", Ratio),
+            ("operating_cash_flow_ratio", Ratio),
+            ("quick_ratio", Ratio),
+        ],
+    },
+    FundamentalsTable {
+        name: "margins",
+        table: "margin_ratios",
+        fields: &[
+            ("free_cash_flow_equity_to_net_income", Ratio),
+            ("free_cash_flow_margin", Ratio),
+            ("gross_margin", Ratio),
+            ("net_income_margin", Ratio),
+            ("owners_earnings_to_net_income", Ratio),
+            ("unlevered_free_cash_flow_to_net_income", Ratio),
+            ("unlevered_free_cash_flow_to_operating_income", Ratio),
+            ("unlevered_free_cash_flow_ebit_to_net_income", Ratio),
+        ],
+    },
+    FundamentalsTable {
+        name: "operation_risk",
+        table: "operation_risk_ratios",
+        fields: &[
+            ("asset_coverage_ratio", Ratio),
+            ("cash_coverage", Ratio),
+            ("cash_flow_coverage_ratios", Ratio),
+            ("debt_ratio", Ratio),
+            ("debt_service_coverage", Ratio),
+            ("interest_coverage", Ratio),
+            ("long_term_debt_to_capitalization", Ratio),
+            ("operating_cash_flow_ratio", Ratio),
+            ("total_debt_to_capitalization", Ratio),
+        ],
+    },
+    FundamentalsTable {
+        name: "price_to",
+        table: "price_to_ratios",
+        fields: &[
+            ("price_to_book_value", Ratio),
+            ("price_to_cash_flow", Ratio),
+            ("price_to_earnings", Ratio),
+            ("price_to_earnings_growth", Ratio),
+            ("price_to_free_cash_flow", Ratio),
+            ("price_to_operating_cash_flow", Ratio),
+            ("price_to_sales", Ratio),
+            ("price_to_tangible_assets", Ratio),
+            ("price_to_total_assets", Ratio),
+        ],
+    },
+    FundamentalsTable {
+        name: "rentability",
+        table: "rentability_ratios",
+        fields: &[
+            ("nopat_roic", Ratio),
+            ("return_on_assets", Ratio),
+            ("return_on_capital", Ratio),
+            ("return_on_common_equity", Ratio),
+            ("return_on_equity", Ratio),
+            ("return_on_invested_capital", Ratio),
+            ("return_on_tangible_assets", Ratio),
+            ("return_on_total_assets", Ratio),
+            ("rogic", Ratio),
+        ],
+    },
+];
+
+impl FundamentalsTable {
+    /// Restates share counts and per-share figures in the shares of today
+    pub fn adjust_splits(&self, records: &mut [FundamentalsRecord], factors: &SplitFactors) {
+        for record in records {
+            let factor = factors.since(record.date);
+            if factor == 1.0 {
+                continue;
+            }
+            for (field, kind) in self.fields {
+                let Some(Some(value)) = record.figures.get_mut(*field) else {
+                    continue;
+                };
+                if *kind == Shares {
+                    *value *= factor;
+                } else if field.ends_with("_per_share") {
+                    *value /= factor;
+                }
+            }
+        }
+    }
+}
+
+pub fn find_table(name: &str) -> Option<&'static FundamentalsTable> {
+    TABLES.iter().find(|table| table.name == name)
+}
+
+/// One row of a fundamentals table with its figures by field name
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct FundamentalsRecord {
+    pub year: Option<i32>,
+    pub period: Option<i32>,
+    pub date: NaiveDate,
+    pub is_ttm: bool,
+    /// Alphabetic code of the currency of the monetary figures
+    pub currency: Option<String>,
+    #[serde(skip)]
+    pub currency_id: Option<i64>,
+    #[schema(value_type = Object)]
+    pub figures: BTreeMap<String, Option<f64>>,
+}
+
+impl FundamentalsRecord {
+    /// First day of the period the record covers
+    pub fn period_start(&self) -> NaiveDate {
+        let months = match self.period {
+            Some(period) if period != FISCAL_YEAR && !self.is_ttm => 3,
+            _ => 12,
+        };
+        self.date - Months::new(months)
+    }
+}
+
+#[derive(QueryableByName)]
+struct RawRecord {
+    #[diesel(sql_type = Nullable<Integer>)]
+    year: Option<i32>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    period: Option<i32>,
+    #[diesel(sql_type = Date)]
+    date: NaiveDate,
+    #[diesel(sql_type = Bool)]
+    is_ttm: bool,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    currency_id: Option<i64>,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    currency: Option<String>,
+    #[diesel(sql_type = Jsonb)]
+    figures: Value,
+}
+
+#[derive(QueryableByName)]
+struct InUse {
+    #[diesel(sql_type = Bool)]
+    in_use: bool,
+}
+
+/// Whether any statement or ratio is reported in the currency
+pub fn reported_in(currency_id: i64, conn: &mut PgConnection) -> QueryResult<bool> {
+    let tables = TABLES
+        .iter()
+        .map(|table| {
+            format!(
+                "EXISTS (SELECT 1 FROM {} WHERE reported_currency_id = $1)",
+                table.table
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    diesel::sql_query(format!("SELECT {tables} AS in_use"))
+        .bind::<BigInt, _>(currency_id)
+        .get_result::<InUse>(conn)
+        .map(|row| row.in_use)
+}
+
+impl FundamentalsTable {
+    /// Every record of a company, oldest first
+    pub fn load(
+        &self,
+        company_id: i64,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<FundamentalsRecord>> {
+        // Names come from the declarations above, never from the request
+        let figures = self
+            .fields
+            .iter()
+            .map(|(field, _)| format!("'{field}', t.{field}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let query = format!(
+            "SELECT p.year, p.period, t.date, t.is_ttm, t.reported_currency_id AS currency_id, \
+             c.alphabetic_code AS currency, jsonb_build_object({figures}) AS figures \
+             FROM {} t \
+             LEFT JOIN periods p ON p.id = t.period_id \
+             LEFT JOIN currencies c ON c.id = t.reported_currency_id \
+             WHERE t.company_id = $1 \
+             ORDER BY t.date, p.period",
+            self.table
+        );
+        let records = diesel::sql_query(query)
+            .bind::<BigInt, _>(company_id)
+            .load::<RawRecord>(conn)?;
+        Ok(records
+            .into_iter()
+            .map(|record| FundamentalsRecord {
+                year: record.year,
+                period: record.period,
+                date: record.date,
+                is_ttm: record.is_ttm,
+                currency: record.currency,
+                currency_id: record.currency_id,
+                figures: self
+                    .fields
+                    .iter()
+                    .map(|(field, _)| (field.to_string(), record.figures[field].as_f64()))
+                    .collect(),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,112 @@
+//! Periodic tasks that run next to the HTTP server
+use std::time::Duration;
+
+use diesel::{PgConnection, QueryResult};
+use futures_util::future::BoxFuture;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::{
+    aggregates::refresh_aggregates,
+    corporate_actions::apply_due_actions,
+    currencies::{sync_ecb, sync_fred},
+    dividends::link_dividend_transactions,
+    fundamentals::refresh_findings,
+    scores::refresh_scores,
+    server::{AppError, AppState},
+};
+
+const AGGREGATES_REFRESH_PERIOD: Duration = Duration::from_secs(6 * 60 * 60);
+const SCORES_REFRESH_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+const FINDINGS_REFRESH_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+const CORPORATE_ACTIONS_PERIOD: Duration = Duration::from_secs(60 * 60);
+const DIVIDENDS_LINK_PERIOD: Duration = Duration::from_secs(6 * 60 * 60);
+const ECB_RATES_PERIOD: Duration = Duration::from_secs(6 * 60 * 60);
+const FRED_RATES_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+type Job = fn(&mut PgConnection) -> QueryResult<()>;
+
+/// Jobs that fetch data from outside before they touch the database
+type SourceJob = fn(AppState) -> BoxFuture<'static, Result<(), AppError>>;
+
+pub fn spawn_jobs(state: &AppState) {
+    tokio::spawn(run_periodically(
+        state.clone(),
+        "aggregates",
+        AGGREGATES_REFRESH_PERIOD,
+        refresh_aggregates,
+    ));
+    tokio::spawn(run_periodically(
+        state.clone(),
+        "scores",
+        SCORES_REFRESH_PERIOD,
+        refresh_scores,
+    ));
+    tokio::spawn(run_periodically(
+        state.clone(),
+        "findings",
+        FINDINGS_REFRESH_PERIOD,
+        refresh_findings,
+    ));
+    tokio::spawn(run_periodically(
+        state.clone(),
+        "corporate actions",
+        CORPORATE_ACTIONS_PERIOD,
+        apply_due_actions,
+    ));
+    tokio::spawn(run_periodically(
+        state.clone(),
+        "dividend transactions",
+        DIVIDENDS_LINK_PERIOD,
+        link_dividend_transactions,
+    ));
+    tokio::spawn(run_source_periodically(
+        state.clone(),
+        "ECB rates",
+        ECB_RATES_PERIOD,
+        sync_ecb,
+    ));
+    tokio::spawn(run_source_periodically(
+        state.clone(),
+        "FRED rates",
+        FRED_RATES_PERIOD,
+        sync_fred,
+    ));
+}
+
+async fn run_periodically(state: AppState, name: &'static str, period: Duration, job: Job) {
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match run(&state, job).await {
+            Ok(()) => info!("Job {name} finished"),
+            Err(err) => error!("Job {name} failed: {err:?}"),
+        }
+    }
+}
+
+async fn run_source_periodically(
+    state: AppState,
+    name: &'static str,
+    period: Duration,
+    job: SourceJob,
+) {
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match job(state.clone()).await {
+            Ok(()) => info!("Job {name} finished"),
+            Err(err) => error!("Job {name} failed: {err:?}"),
+        }
+    }
+}
+
+async fn run(state: &AppState, job: Job) -> Result<(), AppError> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| job(conn).map_err(AppError::DatabaseQueryError))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+}
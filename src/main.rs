@@ -1,14 +1,23 @@
+mod aggregates;
+mod commands;
 mod companies;
+mod corporate_actions;
 mod countries;
 mod currencies;
 mod db;
 mod dictionary;
+mod dividends;
 mod exchanges;
+mod fundamentals;
 mod industries;
+mod jobs;
+mod prices;
+mod scores;
 mod sectors;
 mod server;
 mod transactions;
 mod users;
+mod valuation;
 
 #[macro_use]
 extern crate tracing;
@@ -38,6 +47,20 @@ fn main() -> Result<(), i16> {
         builder.max_blocking_threads(threads);
     }
 
+    // Any argument runs a one-off command instead of the server
+    let mut args = std::env::args().skip(1);
+    if let Some(command) = args.next() {
+        let args: Vec<String> = args.collect();
+        return builder
+            .build()
+            .unwrap()
+            .block_on(commands::run(&state, &command, &args))
+            .map_err(|err| {
+                error!("Command {command} failed: {err}");
+                1
+            });
+    }
+
     let service = router.into_make_service_with_connect_info::<SocketAddr>();
 
     // Block the main thread until the server has shutdown
@@ -45,6 +68,7 @@ fn main() -> Result<(), i16> {
         .build()
         .unwrap()
         .block_on(async {
+            jobs::spawn_jobs(&state);
             let listener = TcpListener::bind((config.ip, config.port)).await?;
 
             axum::serve(listener, service)
@@ -0,0 +1,228 @@
+use axum::{
+    extract::{Path, Query},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{Datelike, NaiveDate};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{self, IntoParams, OpenApi, ToResponse, ToSchema};
+
+use super::loader::{bulk_load_company_prices, bulk_load_prices};
+use crate::{
+    companies::get_company_id,
+    corporate_actions::SplitFactors,
+    db::schema::prices,
+    server::{AppError, AppResult},
+    AppState,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        list_prices,
+        latest_quote,
+        super::loader::bulk_load_prices,
+        super::loader::bulk_load_company_prices
+    ),
+    components(schemas(PriceBar, Quote, Interval),
+    responses(PriceBar, Quote)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/prices/bulk", post(bulk_load_prices))
+        .route("/companies/:ticker/prices", get(list_prices))
+        .route(
+            "/companies/:ticker/prices/bulk",
+            post(bulk_load_company_prices),
+        )
+        .route("/companies/:ticker/quote", get(latest_quote))
+        .with_state(state)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    #[default]
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Interval {
+    /// Bars in the same bucket are merged into one
+    fn bucket(self, date: NaiveDate) -> (i32, u32) {
+        match self {
+            Interval::Daily => (date.year(), date.ordinal()),
+            Interval::Weekly => (date.iso_week().year(), date.iso_week().week()),
+            Interval::Monthly => (date.year(), date.month()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, ToSchema, ToResponse)]
+#[diesel(table_name = prices)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PriceBar {
+    /// Last trading day of the bar
+    pub date: NaiveDate,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: i64,
+}
+
+impl PriceBar {
+    /// Restates the bar in the shares of today
+    fn adjust(&mut self, factors: &SplitFactors) {
+        let factor = factors.since(self.date);
+        if factor == 1.0 {
+            return;
+        }
+        self.open /= factor;
+        self.high /= factor;
+        self.low /= factor;
+        self.close /= factor;
+        self.volume = (self.volume as f64 * factor).round() as i64;
+    }
+
+    fn merge(&mut self, next: PriceBar) {
+        self.date = next.date;
+        self.high = self.high.max(next.high);
+        self.low = self.low.min(next.low);
+        self.close = next.close;
+        self.volume += next.volume;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct Quote {
+    pub ticker: String,
+    #[serde(flatten)]
+    pub bar: PriceBar,
+    pub currency_id: Option<i64>,
+    /// Close of the trading day before, in the shares of `date`
+    pub previous_close: Option<f64>,
+    pub change: Option<f64>,
+    pub change_percent: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct PricesQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// daily by default
+    pub interval: Option<Interval>,
+    /// Restate prices and volumes in today's shares
+    pub adjusted: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/prices",
+    params(("ticker", description = "Company's ticker"), PricesQuery),
+    responses(
+        (status = 200, body = Vec<PriceBar>, description = "Price history of a company, oldest first"),
+        (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+        (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+    )
+)]
+async fn list_prices(
+    Path(ticker): Path<String>,
+    Query(query_params): Query<PricesQuery>,
+    state: AppState,
+) -> AppResult<Vec<PriceBar>> {
+    if let (Some(from), Some(to)) = (query_params.from, query_params.to) {
+        if to < from {
+            return Err(AppError::ValidationError("to can't be before from".into()));
+        }
+    }
+    let interval = query_params.interval.unwrap_or_default();
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            let mut query = prices::table
+                .filter(prices::company_id.eq(company_id))
+                .order(prices::date)
+                .select(PriceBar::as_select())
+                .into_boxed();
+            if let Some(from) = query_params.from {
+                query = query.filter(prices::date.ge(from));
+            }
+            if let Some(to) = query_params.to {
+                query = query.filter(prices::date.le(to));
+            }
+            let mut daily: Vec<PriceBar> = query.load(conn)?;
+            if query_params.adjusted.unwrap_or_default() {
+                let factors = SplitFactors::load(company_id, conn)?;
+                daily.iter_mut().for_each(|bar| bar.adjust(&factors));
+            }
+            if interval == Interval::Daily {
+                return Ok(daily);
+            }
+            let mut bars: Vec<PriceBar> = Vec::new();
+            for bar in daily {
+                match bars.last_mut() {
+                    Some(last) if interval.bucket(last.date) == interval.bucket(bar.date) => {
+                        last.merge(bar)
+                    }
+                    _ => bars.push(bar),
+                }
+            }
+            Ok(bars)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/quote",
+    params(("ticker", description = "Company's ticker")),
+    responses(
+        (status = 200, body = Quote, description = "Latest price of a company with its change from the day before"),
+        (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+        (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+    )
+)]
+async fn latest_quote(Path(ticker): Path<String>, state: AppState) -> AppResult<Quote> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            let latest: Vec<(PriceBar, Option<i64>)> = prices::table
+                .filter(prices::company_id.eq(company_id))
+                .order(prices::date.desc())
+                .select((PriceBar::as_select(), prices::currency_id))
+                .limit(2)
+                .load(conn)?;
+            let mut latest = latest.into_iter();
+            let (bar, currency_id) = latest.next().ok_or(AppError::DoesNotExist)?;
+            let factors = SplitFactors::load(company_id, conn)?;
+            let previous_close = latest.next().map(|(previous, _)| {
+                previous.close * factors.since(bar.date) / factors.since(previous.date)
+            });
+            let change = previous_close.map(|previous| bar.close - previous);
+            Ok(Quote {
+                ticker,
+                currency_id,
+                previous_close,
+                change,
+                change_percent: change
+                    .zip(previous_close)
+                    .map(|(change, previous)| change / previous * 100.0),
+                bar,
+            })
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
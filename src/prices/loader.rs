@@ -0,0 +1,328 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Cursor,
+};
+
+use axum::{
+    body::Bytes,
+    extract::Path,
+    http::{header::CONTENT_TYPE, HeaderMap},
+    Extension, Json,
+};
+use chrono::NaiveDate;
+use diesel::{pg::upsert::excluded, prelude::*};
+use polars::prelude::*;
+
+use crate::{
+    companies::get_company_id,
+    currencies::get_currency_id,
+    db::schema::prices,
+    server::{AppError, AppResult, BulkReport, JWTUserRequest},
+    AppState,
+};
+
+/// Rows upserted per statement
+const CHUNK_SIZE: usize = 1000;
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = prices)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct NewPrice {
+    company_id: i64,
+    date: NaiveDate,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: i64,
+    currency_id: Option<i64>,
+}
+
+/// A row of the file as read, before the ticker and currency are looked up
+struct PriceRow {
+    ticker: Option<String>,
+    date: Option<NaiveDate>,
+    open: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    close: Option<f64>,
+    volume: Option<i64>,
+    currency: Option<String>,
+}
+
+impl PriceRow {
+    fn check(&self) -> Result<(NaiveDate, [f64; 4]), String> {
+        let date = self.date.ok_or("date is missing or isn't a date")?;
+        let close = self.close.ok_or("close is missing")?;
+        let open = self.open.unwrap_or(close);
+        let high = self.high.unwrap_or(open.max(close));
+        let low = self.low.unwrap_or(open.min(close));
+        // NaN passes every comparison below, the database check included
+        if ![open, high, low, close].iter().all(|price| price.is_finite()) {
+            return Err(format!(
+                "prices must be finite, open {open}, high {high}, low {low}, close {close}"
+            ));
+        }
+        if low <= 0.0 || low > open.min(close) || high < open.max(close) {
+            return Err(format!(
+                "prices out of range, open {open}, high {high}, low {low}, close {close}"
+            ));
+        }
+        if self.volume.is_some_and(|volume| volume < 0) {
+            return Err("volume can't be negative".into());
+        }
+        Ok((date, [open, high, low, close]))
+    }
+}
+
+/// Reads a CSV with a header or a Parquet file, picked by the `Content-Type`
+fn read_frame(headers: &HeaderMap, body: Bytes) -> Result<DataFrame, AppError> {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let cursor = Cursor::new(body.to_vec());
+    let frame = if content_type.contains("csv") {
+        CsvReadOptions::default()
+            .with_has_header(true)
+            .map_parse_options(|options| options.with_try_parse_dates(true))
+            .into_reader_with_file_handle(cursor)
+            .finish()
+    } else if content_type.contains("parquet") || content_type.contains("octet-stream") {
+        ParquetReader::new(cursor).finish()
+    } else {
+        return Err(AppError::ValidationError(format!(
+            "Unsupported content type {content_type:?}, use text/csv or application/vnd.apache.parquet"
+        )));
+    };
+    frame.map_err(|err| AppError::ValidationError(err.to_string()))
+}
+
+fn column<T>(
+    frame: &DataFrame,
+    name: &str,
+    dtype: DataType,
+    values: impl Fn(&Series) -> PolarsResult<Vec<Option<T>>>,
+) -> Result<Vec<Option<T>>, AppError> {
+    match frame.column(name) {
+        Ok(series) => series
+            .cast(&dtype)
+            .and_then(|series| values(&series))
+            .map_err(|err| AppError::ValidationError(format!("Column {name}: {err}"))),
+        Err(_) => Ok((0..frame.height()).map(|_| None).collect()),
+    }
+}
+
+fn read_rows(frame: &DataFrame) -> Result<Vec<PriceRow>, AppError> {
+    let strings = |series: &Series| {
+        Ok(series
+            .str()?
+            .into_iter()
+            .map(|value| value.map(str::to_string))
+            .collect())
+    };
+    let floats = |series: &Series| Ok(series.f64()?.into_iter().collect());
+    let tickers = column(frame, "ticker", DataType::String, strings)?;
+    let dates = column(frame, "date", DataType::Date, |series| {
+        Ok(series.date()?.as_date_iter().collect())
+    })?;
+    let opens = column(frame, "open", DataType::Float64, floats)?;
+    let highs = column(frame, "high", DataType::Float64, floats)?;
+    let lows = column(frame, "low", DataType::Float64, floats)?;
+    let closes = column(frame, "close", DataType::Float64, floats)?;
+    let volumes = column(frame, "volume", DataType::Int64, |series| {
+        Ok(series.i64()?.into_iter().collect())
+    })?;
+    let currencies = column(frame, "currency", DataType::String, strings)?;
+
+    Ok((0..frame.height())
+        .map(|index| PriceRow {
+            ticker: tickers[index].clone(),
+            date: dates[index],
+            open: opens[index],
+            high: highs[index],
+            low: lows[index],
+            close: closes[index],
+            volume: volumes[index],
+            currency: currencies[index].clone(),
+        })
+        .collect())
+}
+
+/// Looks the id up once per key, None when there's none
+fn cached_id(
+    cache: &mut HashMap<String, Option<i64>>,
+    key: &str,
+    lookup: fn(&str, &mut PgConnection) -> Result<i64, AppError>,
+    conn: &mut PgConnection,
+) -> Result<Option<i64>, AppError> {
+    if let Some(id) = cache.get(key) {
+        return Ok(*id);
+    }
+    let id = match lookup(key, conn) {
+        Err(AppError::DoesNotExist) => None,
+        result => Some(result?),
+    };
+    cache.insert(key.to_string(), id);
+    Ok(id)
+}
+
+fn store_prices(
+    ticker: Option<String>,
+    rows: Vec<PriceRow>,
+    conn: &mut PgConnection,
+) -> Result<BulkReport, AppError> {
+    let mut report = BulkReport::default();
+    let mut companies: HashMap<String, Option<i64>> = HashMap::new();
+    let mut currencies: HashMap<String, Option<i64>> = HashMap::new();
+    // The last row of a company and date wins
+    let mut prices: HashMap<(i64, NaiveDate), (usize, NewPrice)> = HashMap::new();
+
+    for (row, number) in rows.into_iter().zip(1..) {
+        let Some(ticker) = ticker.clone().or(row.ticker.clone()) else {
+            report.reject(number, "ticker is missing");
+            continue;
+        };
+        let (date, [open, high, low, close]) = match row.check() {
+            Ok(checked) => checked,
+            Err(reason) => {
+                report.reject(number, reason);
+                continue;
+            }
+        };
+        let Some(company_id) = cached_id(&mut companies, &ticker, get_company_id, conn)? else {
+            report.reject(number, format!("Unknown company {ticker}"));
+            continue;
+        };
+        let currency_id = match row.currency {
+            None => None,
+            Some(currency) => {
+                let currency = currency.trim().to_uppercase();
+                let currency_id = cached_id(&mut currencies, &currency, get_currency_id, conn)?;
+                if currency_id.is_none() {
+                    report.reject(number, format!("Unknown currency {currency}"));
+                    continue;
+                }
+                currency_id
+            }
+        };
+        let price = NewPrice {
+            company_id,
+            date,
+            open,
+            high,
+            low,
+            close,
+            volume: row.volume.unwrap_or_default(),
+            currency_id,
+        };
+        if let Some((replaced, _)) = prices.insert((company_id, date), (number, price)) {
+            report.reject(
+                replaced,
+                format!("{ticker} {date} is repeated in a later row"),
+            );
+        }
+    }
+
+    let mut prices: Vec<NewPrice> = prices.into_values().map(|(_, price)| price).collect();
+    prices.sort_by_key(|price| (price.company_id, price.date));
+    conn.transaction(|conn| {
+        for chunk in prices.chunks(CHUNK_SIZE) {
+            let company_ids: HashSet<i64> = chunk.iter().map(|price| price.company_id).collect();
+            let dates = chunk.iter().map(|price| price.date);
+            let (Some(first), Some(last)) = (dates.clone().min(), dates.max()) else {
+                continue;
+            };
+            let existing: HashSet<(i64, NaiveDate)> = prices::table
+                .filter(prices::company_id.eq_any(company_ids))
+                .filter(prices::date.between(first, last))
+                .select((prices::company_id, prices::date))
+                .load::<(i64, NaiveDate)>(conn)?
+                .into_iter()
+                .collect();
+            let updated = chunk
+                .iter()
+                .filter(|price| existing.contains(&(price.company_id, price.date)))
+                .count();
+            diesel::insert_into(prices::table)
+                .values(chunk)
+                .on_conflict((prices::company_id, prices::date))
+                .do_update()
+                .set((
+                    prices::open.eq(excluded(prices::open)),
+                    prices::high.eq(excluded(prices::high)),
+                    prices::low.eq(excluded(prices::low)),
+                    prices::close.eq(excluded(prices::close)),
+                    prices::volume.eq(excluded(prices::volume)),
+                    prices::currency_id.eq(excluded(prices::currency_id)),
+                    prices::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(conn)?;
+            report.updated += updated;
+            report.inserted += chunk.len() - updated;
+        }
+        Ok::<_, diesel::result::Error>(())
+    })?;
+    report.errors.sort_by_key(|error| error.row);
+    Ok(report)
+}
+
+async fn load_prices(
+    ticker: Option<String>,
+    state: AppState,
+    current_user: JWTUserRequest,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<BulkReport> {
+    current_user.require_staff()?;
+    let rows = tokio::task::spawn_blocking(move || read_rows(&read_frame(&headers, body)?))
+        .await
+        .map_err(AppError::BlockingTaskError)??;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| store_prices(ticker, rows, conn))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "prices/bulk",
+    request_body(content = String, description = "A CSV with a header or a Parquet file with ticker, date, open, high, low, close, volume and currency columns. Only ticker, date and close are required", content_type = "text/csv"),
+    responses(
+        (status = 200, body = BulkReport, description = "Insert or update the daily prices of many companies, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn bulk_load_prices(
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<BulkReport> {
+    load_prices(None, state, current_user, headers, body).await
+}
+
+#[utoipa::path(
+    post,
+    path = "companies/{ticker}/prices/bulk",
+    params(("ticker", description = "Company's ticker")),
+    request_body(content = String, description = "A CSV with a header or a Parquet file with date, open, high, low, close, volume and currency columns. Only date and close are required", content_type = "text/csv"),
+    responses(
+        (status = 200, body = BulkReport, description = "Insert or update the daily prices of a company, staff only"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+pub(super) async fn bulk_load_company_prices(
+    Path(ticker): Path<String>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<BulkReport> {
+    load_prices(Some(ticker), state, current_user, headers, body).await
+}
@@ -0,0 +1,7 @@
+//! Daily OHLCV prices of companies, as traded, with split adjustment on request
+mod handlers;
+mod loader;
+mod querysets;
+
+pub use handlers::{routes, ApiDoc};
+pub use querysets::{close_on, Close};
@@ -0,0 +1,33 @@
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+use crate::db::schema::prices;
+
+/// Close of the date or of the last trading day before it, as traded
+#[derive(Debug, Clone, Copy)]
+pub struct Close {
+    pub date: NaiveDate,
+    pub close: f64,
+    pub currency_id: Option<i64>,
+}
+
+pub fn close_on(
+    company_id: i64,
+    date: NaiveDate,
+    conn: &mut PgConnection,
+) -> QueryResult<Option<Close>> {
+    prices::table
+        .filter(prices::company_id.eq(company_id))
+        .filter(prices::date.le(date))
+        .order(prices::date.desc())
+        .select((prices::date, prices::close, prices::currency_id))
+        .first::<(NaiveDate, f64, Option<i64>)>(conn)
+        .optional()
+        .map(|close| {
+            close.map(|(date, close, currency_id)| Close {
+                date,
+                close,
+                currency_id,
+            })
+        })
+}
@@ -0,0 +1,41 @@
+use serde_json::{json, Value};
+
+use super::statements::{ratio, FiscalYear};
+
+/// Altman Z-score for public companies, below 1.81 signals distress and above 2.99 safety
+pub fn altman_z_score(year: &FiscalYear) -> (Option<f64>, Value) {
+    let balance_sheet = &year.balance_sheet;
+    let total_assets = balance_sheet.total_assets;
+
+    let working_capital = ratio(
+        balance_sheet.total_current_assets - balance_sheet.total_current_liabilities,
+        total_assets,
+    );
+    let retained_earnings = ratio(balance_sheet.retained_earnings, total_assets);
+    let earnings_before_interest_and_taxes =
+        ratio(year.income_statement.operating_income, total_assets);
+    let market_value_of_equity = year
+        .market_capitalization
+        .and_then(|market_capitalization| {
+            ratio(market_capitalization, balance_sheet.total_liabilities)
+        });
+    let sales = ratio(year.income_statement.revenue, total_assets);
+
+    let score = (|| {
+        Some(
+            1.2 * working_capital?
+                + 1.4 * retained_earnings?
+                + 3.3 * earnings_before_interest_and_taxes?
+                + 0.6 * market_value_of_equity?
+                + sales?,
+        )
+    })();
+    let components = json!({
+        "working_capital_to_total_assets": working_capital,
+        "retained_earnings_to_total_assets": retained_earnings,
+        "ebit_to_total_assets": earnings_before_interest_and_taxes,
+        "market_value_of_equity_to_total_liabilities": market_value_of_equity,
+        "sales_to_total_assets": sales,
+    });
+    (score, components)
+}
@@ -0,0 +1,88 @@
+use serde_json::{json, Value};
+
+use super::statements::{ratio, FiscalYear};
+
+/// Beneish M-score with eight variables, above -1.78 flags a likely earnings manipulator
+pub fn beneish_m_score(current: &FiscalYear, previous: &FiscalYear) -> (Option<f64>, Value) {
+    let index =
+        |metric: &dyn Fn(&FiscalYear) -> Option<f64>| ratio(metric(current)?, metric(previous)?);
+
+    let days_sales_in_receivables = index(&|year| {
+        ratio(
+            year.balance_sheet.net_receivables,
+            year.income_statement.revenue,
+        )
+    });
+    // Inverted, a deteriorating margin raises the index
+    let gross_margin = (|| {
+        let margin = |year: &FiscalYear| {
+            ratio(
+                year.income_statement.revenue - year.income_statement.cost_of_revenue,
+                year.income_statement.revenue,
+            )
+        };
+        ratio(margin(previous)?, margin(current)?)
+    })();
+    let asset_quality = index(&|year| {
+        let balance_sheet = &year.balance_sheet;
+        ratio(
+            balance_sheet.total_current_assets + balance_sheet.property_plant_and_equipment,
+            balance_sheet.total_assets,
+        )
+        .map(|hard_assets| 1.0 - hard_assets)
+    });
+    let sales_growth = index(&|year| Some(year.income_statement.revenue));
+    let depreciation = (|| {
+        let rate = |year: &FiscalYear| {
+            let depreciation = year.income_statement.depreciation_and_amortization;
+            ratio(
+                depreciation,
+                depreciation + year.balance_sheet.property_plant_and_equipment,
+            )
+        };
+        ratio(rate(previous)?, rate(current)?)
+    })();
+    let sales_general_and_administrative = index(&|year| {
+        ratio(
+            year.income_statement
+                .selling_general_and_administrative_expenses,
+            year.income_statement.revenue,
+        )
+    });
+    let total_accruals_to_total_assets = ratio(
+        current.income_statement.net_income
+            - current.cashflow_statement.operating_activities_cash_flow,
+        current.balance_sheet.total_assets,
+    );
+    let leverage = index(&|year| {
+        ratio(
+            year.balance_sheet.total_current_liabilities + year.balance_sheet.long_term_debt,
+            year.balance_sheet.total_assets,
+        )
+    });
+
+    let score = (|| {
+        Some(
+            -4.84
+                + 0.92 * days_sales_in_receivables?
+                + 0.528 * gross_margin?
+                + 0.404 * asset_quality?
+                + 0.892 * sales_growth?
+                + 0.115 * depreciation?
+                - 0.172 * sales_general_and_administrative?
+                + 4.679 * total_accruals_to_total_assets?
+                - 0.327 * leverage?,
+        )
+    })();
+    let components = json!({
+        "days_sales_in_receivables_index": days_sales_in_receivables,
+        "gross_margin_index": gross_margin,
+        "asset_quality_index": asset_quality,
+        "sales_growth_index": sales_growth,
+        "depreciation_index": depreciation,
+        "sales_general_and_administrative_index": sales_general_and_administrative,
+        "total_accruals_to_total_assets": total_accruals_to_total_assets,
+        "leverage_index": leverage,
+    });
+    (score, components)
+}
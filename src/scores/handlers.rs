@@ -0,0 +1,247 @@
+use axum::{
+    extract::{Path, Query},
+    routing::get,
+    Json, Router,
+};
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{self, IntoParams, OpenApi, ToResponse, ToSchema};
+
+use super::{
+    altman::altman_z_score,
+    beneish::beneish_m_score,
+    piotroski::piotroski_f_score,
+    statements::{load_fiscal_years, FiscalYear},
+};
+use crate::{
+    companies::get_company_id,
+    db::{
+        schema::{companies, company_scores, periods},
+        Paginate,
+    },
+    server::{AppError, AppResult},
+    AppState,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(list_company_scores, screen_scores),
+    components(schemas(CompanyScore, ScreenedCompany, ScreenedCompaniesResponse),
+    responses(CompanyScore, ScreenedCompaniesResponse)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/companies/:ticker/scores", get(list_company_scores))
+        .route("/scores", get(screen_scores))
+        .with_state(state)
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = company_scores)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(treat_none_as_null = true)]
+struct NewCompanyScore {
+    company_id: i64,
+    period_id: i64,
+    date: NaiveDate,
+    piotroski_f_score: Option<i32>,
+    piotroski_components: Option<Value>,
+    altman_z_score: Option<f64>,
+    altman_components: Option<Value>,
+    beneish_m_score: Option<f64>,
+    beneish_components: Option<Value>,
+}
+
+/// Computes and stores the scores of every fiscal year of a company, returns how many
+/// years were scored
+pub fn compute_company_scores(company_id: i64, conn: &mut PgConnection) -> QueryResult<usize> {
+    let years = load_fiscal_years(company_id, conn)?;
+    let mut previous: Option<&FiscalYear> = None;
+    for year in &years {
+        let (altman_z_score, altman_components) = altman_z_score(year);
+        // Both scores compare a year with the one right before it, a gap leaves them out
+        let (piotroski, beneish) = match previous {
+            Some(previous) if previous.year == year.year - 1 => (
+                Some(piotroski_f_score(year, previous)),
+                Some(beneish_m_score(year, previous)),
+            ),
+            _ => (None, None),
+        };
+        let (piotroski_f_score, piotroski_components) = piotroski.unzip();
+        let (beneish_m_score, beneish_components) = match beneish {
+            Some((score, components)) => (score, Some(components)),
+            None => (None, None),
+        };
+        let score = NewCompanyScore {
+            company_id,
+            period_id: year.period_id,
+            date: year.balance_sheet.date,
+            piotroski_f_score,
+            piotroski_components,
+            altman_z_score,
+            altman_components: Some(altman_components),
+            beneish_m_score,
+            beneish_components,
+        };
+        diesel::insert_into(company_scores::table)
+            .values(&score)
+            .on_conflict((company_scores::company_id, company_scores::period_id))
+            .do_update()
+            .set((&score, company_scores::updated_at.eq(diesel::dsl::now)))
+            .execute(conn)?;
+        previous = Some(year);
+    }
+    Ok(years.len())
+}
+
+pub fn refresh_scores(conn: &mut PgConnection) -> QueryResult<()> {
+    let company_ids: Vec<i64> = companies::table.select(companies::id).load(conn)?;
+    for company_id in company_ids {
+        compute_company_scores(company_id, conn)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Queryable, Selectable, Serialize, Deserialize, ToSchema, ToResponse)]
+#[diesel(table_name = company_scores)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct CompanyScore {
+    date: NaiveDate,
+    piotroski_f_score: Option<i32>,
+    #[schema(value_type = Object)]
+    piotroski_components: Option<Value>,
+    altman_z_score: Option<f64>,
+    #[schema(value_type = Object)]
+    altman_components: Option<Value>,
+    beneish_m_score: Option<f64>,
+    #[schema(value_type = Object)]
+    beneish_components: Option<Value>,
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/scores",
+    params(("ticker", description = "Company's ticker")),
+    responses(
+            (status = 200, body = Vec<CompanyScore>, description = "Piotroski, Altman and Beneish scores per fiscal year with their components"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn list_company_scores(
+    Path(ticker): Path<String>,
+    state: AppState,
+) -> AppResult<Vec<CompanyScore>> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            company_scores::table
+                .filter(company_scores::company_id.eq(company_id))
+                .order(company_scores::date.desc())
+                .select(CompanyScore::as_select())
+                .load(conn)
+                .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+struct ScreenQuery {
+    /// Fiscal year to screen, the most recent one scored by default
+    year: Option<i32>,
+    min_piotroski_f_score: Option<i32>,
+    min_altman_z_score: Option<f64>,
+    max_beneish_m_score: Option<f64>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(Debug, Queryable, Serialize, Deserialize, ToSchema)]
+struct ScreenedCompany {
+    ticker: String,
+    name: Option<String>,
+    year: i32,
+    piotroski_f_score: Option<i32>,
+    altman_z_score: Option<f64>,
+    beneish_m_score: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+struct ScreenedCompaniesResponse {
+    data: Vec<ScreenedCompany>,
+    total_pages: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "scores",
+    params(ScreenQuery),
+    responses(
+            (status = 200, body = ScreenedCompaniesResponse, description = "A paginated result of companies whose scores pass the thresholds"),
+            (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+            (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+        )
+)]
+async fn screen_scores(
+    Query(query_params): Query<ScreenQuery>,
+    state: AppState,
+) -> AppResult<ScreenedCompaniesResponse> {
+    let (data, total_pages) = state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let year = match query_params.year {
+                Some(year) => Some(year),
+                None => company_scores::table
+                    .inner_join(periods::table)
+                    .select(diesel::dsl::max(periods::year))
+                    .first::<Option<i32>>(conn)
+                    .map_err(AppError::DatabaseQueryError)?,
+            };
+            let Some(year) = year else {
+                return Ok((Vec::new(), 0));
+            };
+
+            let mut query = company_scores::table
+                .inner_join(companies::table)
+                .inner_join(periods::table)
+                .filter(periods::year.eq(year))
+                .into_boxed();
+            if let Some(min) = query_params.min_piotroski_f_score {
+                query = query.filter(company_scores::piotroski_f_score.ge(min));
+            }
+            if let Some(min) = query_params.min_altman_z_score {
+                query = query.filter(company_scores::altman_z_score.ge(min));
+            }
+            if let Some(max) = query_params.max_beneish_m_score {
+                query = query.filter(company_scores::beneish_m_score.le(max));
+            }
+            query
+                .order(companies::ticker)
+                .select((
+                    companies::ticker,
+                    companies::name,
+                    periods::year,
+                    company_scores::piotroski_f_score,
+                    company_scores::altman_z_score,
+                    company_scores::beneish_m_score,
+                ))
+                .paginate(query_params.page.unwrap_or(1))
+                .per_page(query_params.per_page.unwrap_or(25))
+                .load_and_count_pages::<ScreenedCompany>(conn)
+                .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)??;
+
+    Ok(Json(ScreenedCompaniesResponse { data, total_pages }))
+}
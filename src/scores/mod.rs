@@ -0,0 +1,7 @@
+mod altman;
+mod beneish;
+mod handlers;
+mod piotroski;
+mod statements;
+
+pub use handlers::{refresh_scores, routes, ApiDoc};
@@ -0,0 +1,79 @@
+use serde_json::{json, Value};
+
+use super::statements::{ratio, FiscalYear};
+
+/// Piotroski F-score, one point per criterion met, from 0 to 9
+pub fn piotroski_f_score(current: &FiscalYear, previous: &FiscalYear) -> (i32, Value) {
+    let return_on_assets = |year: &FiscalYear| {
+        ratio(
+            year.income_statement.net_income,
+            year.balance_sheet.total_assets,
+        )
+    };
+    let leverage = |year: &FiscalYear| {
+        ratio(
+            year.balance_sheet.long_term_debt,
+            year.balance_sheet.total_assets,
+        )
+    };
+    let current_ratio = |year: &FiscalYear| {
+        ratio(
+            year.balance_sheet.total_current_assets,
+            year.balance_sheet.total_current_liabilities,
+        )
+    };
+    let gross_margin = |year: &FiscalYear| {
+        ratio(
+            year.income_statement.gross_profit,
+            year.income_statement.revenue,
+        )
+    };
+    let asset_turnover = |year: &FiscalYear| {
+        ratio(
+            year.income_statement.revenue,
+            year.balance_sheet.total_assets,
+        )
+    };
+    let increased = |metric: &dyn Fn(&FiscalYear) -> Option<f64>| {
+        metric(current)
+            .zip(metric(previous))
+            .is_some_and(|(now, before)| now > before)
+    };
+
+    let operating_cash_flow = current.cashflow_statement.operating_activities_cash_flow;
+    let criteria = [
+        (
+            "positive_return_on_assets",
+            return_on_assets(current).is_some_and(|value| value > 0.0),
+        ),
+        ("positive_operating_cash_flow", operating_cash_flow > 0.0),
+        ("higher_return_on_assets", increased(&return_on_assets)),
+        (
+            "cash_flow_above_net_income",
+            operating_cash_flow > current.income_statement.net_income,
+        ),
+        (
+            "lower_leverage",
+            leverage(current)
+                .zip(leverage(previous))
+                .is_some_and(|(now, before)| now < before),
+        ),
+        ("higher_current_ratio", increased(&current_ratio)),
+        (
+            "no_dilution",
+            current.income_statement.weighted_average_shares_outstanding
+                <= previous
+                    .income_statement
+                    .weighted_average_shares_outstanding,
+        ),
+        ("higher_gross_margin", increased(&gross_margin)),
+        ("higher_asset_turnover", increased(&asset_turnover)),
+    ];
+
+    let score = criteria.iter().filter(|(_, met)| *met).count() as i32;
+    let components: serde_json::Map<String, Value> = criteria
+        .into_iter()
+        .map(|(name, met)| (name.to_owned(), json!(met)))
+        .collect();
+    (score, Value::Object(components))
+}
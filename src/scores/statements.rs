@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+use crate::{
+    db::schema::{
+        balance_sheet_statements, cashflow_statements, income_statements, non_gaap_figures, periods,
+    },
+    fundamentals::FISCAL_YEAR,
+};
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = balance_sheet_statements)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BalanceSheet {
+    period_id: Option<i64>,
+    pub date: NaiveDate,
+    pub total_assets: f64,
+    pub total_current_assets: f64,
+    pub total_current_liabilities: f64,
+    pub total_liabilities: f64,
+    pub retained_earnings: f64,
+    pub long_term_debt: f64,
+    pub net_receivables: f64,
+    pub property_plant_and_equipment: f64,
+}
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = income_statements)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct IncomeStatement {
+    period_id: Option<i64>,
+    pub revenue: f64,
+    pub cost_of_revenue: f64,
+    pub gross_profit: f64,
+    pub operating_income: f64,
+    pub net_income: f64,
+    pub selling_general_and_administrative_expenses: f64,
+    pub depreciation_and_amortization: f64,
+    pub weighted_average_shares_outstanding: f64,
+}
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = cashflow_statements)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CashflowStatement {
+    period_id: Option<i64>,
+    pub operating_activities_cash_flow: f64,
+}
+
+/// The three statements of a fiscal year, plus the market value used by the Altman Z-score
+#[derive(Debug)]
+pub struct FiscalYear {
+    pub period_id: i64,
+    pub year: i32,
+    pub balance_sheet: BalanceSheet,
+    pub income_statement: IncomeStatement,
+    pub cashflow_statement: CashflowStatement,
+    pub market_capitalization: Option<f64>,
+}
+
+/// Fiscal years of a company with all three statements, oldest first
+pub fn load_fiscal_years(company_id: i64, conn: &mut PgConnection) -> QueryResult<Vec<FiscalYear>> {
+    let mut incomes: BTreeMap<i64, IncomeStatement> = income_statements::table
+        .inner_join(periods::table)
+        .filter(income_statements::company_id.eq(company_id))
+        .filter(income_statements::is_ttm.eq(false))
+        .filter(periods::period.eq(FISCAL_YEAR))
+        .select(IncomeStatement::as_select())
+        .load::<IncomeStatement>(conn)?
+        .into_iter()
+        .filter_map(|statement| Some((statement.period_id?, statement)))
+        .collect();
+    let mut cashflows: BTreeMap<i64, CashflowStatement> = cashflow_statements::table
+        .inner_join(periods::table)
+        .filter(cashflow_statements::company_id.eq(company_id))
+        .filter(cashflow_statements::is_ttm.eq(false))
+        .filter(periods::period.eq(FISCAL_YEAR))
+        .select(CashflowStatement::as_select())
+        .load::<CashflowStatement>(conn)?
+        .into_iter()
+        .filter_map(|statement| Some((statement.period_id?, statement)))
+        .collect();
+    let market_capitalizations: BTreeMap<i64, f64> = non_gaap_figures::table
+        .inner_join(periods::table)
+        .filter(non_gaap_figures::company_id.eq(company_id))
+        .filter(non_gaap_figures::is_ttm.eq(false))
+        .filter(periods::period.eq(FISCAL_YEAR))
+        .select((
+            non_gaap_figures::period_id,
+            non_gaap_figures::market_capitalization,
+        ))
+        .load::<(Option<i64>, f64)>(conn)?
+        .into_iter()
+        .filter_map(|(period_id, value)| Some((period_id?, value)))
+        .collect();
+
+    Ok(balance_sheet_statements::table
+        .inner_join(periods::table)
+        .filter(balance_sheet_statements::company_id.eq(company_id))
+        .filter(balance_sheet_statements::is_ttm.eq(false))
+        .filter(periods::period.eq(FISCAL_YEAR))
+        .order(balance_sheet_statements::date.asc())
+        .select((BalanceSheet::as_select(), periods::year))
+        .load::<(BalanceSheet, i32)>(conn)?
+        .into_iter()
+        .filter_map(|(balance_sheet, year)| {
+            let period_id = balance_sheet.period_id?;
+            Some(FiscalYear {
+                period_id,
+                year,
+                income_statement: incomes.remove(&period_id)?,
+                cashflow_statement: cashflows.remove(&period_id)?,
+                market_capitalization: market_capitalizations.get(&period_id).copied(),
+                balance_sheet,
+            })
+        })
+        .collect())
+}
+
+/// Division that refuses zero denominators instead of producing infinities
+pub fn ratio(numerator: f64, denominator: f64) -> Option<f64> {
+    (denominator != 0.0).then(|| numerator / denominator)
+}
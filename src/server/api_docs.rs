@@ -1,14 +1,21 @@
 use crate::{
+    aggregates::ApiDoc as ApiDocAggregates,
     companies::ApiDoc as ApiDocCompanies,
+    corporate_actions::ApiDoc as ApiDocCorporateActions,
     countries::ApiDoc as ApiDocCountries,
     currencies::ApiDoc as ApiDocCurrencies,
     dictionary::ApiDoc as ApiDocDictionary,
+    dividends::ApiDoc as ApiDocDividends,
     exchanges::ApiDoc as ApiDocExchanges,
+    fundamentals::{ApiDoc as ApiDocFundamentals, ApiDocExport, ApiDocIngestion, ApiDocQuality},
     industries::ApiDoc as ApiDocIndustries,
+    prices::ApiDoc as ApiDocPrices,
+    scores::ApiDoc as ApiDocScores,
     sectors::ApiDoc as ApiDocSectors,
     server::ErrorMessage,
-    transactions::{ApiDocAccounts, ApiDocTransactions},
+    transactions::{ApiDocAccounts, ApiDocPortfolio, ApiDocTransactions},
     users::ApiDoc as ApiDocUsers,
+    valuation::ApiDoc as ApiDocValuation,
 };
 
 use utoipa::{
@@ -34,12 +41,23 @@ use utoipa::{
         (path = "/", api = ApiDocCurrencies, tags = ["Currencies"]),
         (path = "/", api = ApiDocDictionary, tags = ["Dictionary"]),
         (path = "/", api = ApiDocCompanies, tags = ["Companies"]),
+        (path = "/", api = ApiDocCorporateActions, tags = ["Corporate actions"]),
+        (path = "/", api = ApiDocDividends, tags = ["Dividends"]),
+        (path = "/", api = ApiDocPrices, tags = ["Prices"]),
         (path = "/", api = ApiDocExchanges, tags = ["Exchanges"]),
         (path = "/", api = ApiDocIndustries, tags = ["Industries"]),
         (path = "/", api = ApiDocSectors, tags = ["Sectors"]),
+        (path = "/", api = ApiDocAggregates, tags = ["Aggregates"]),
+        (path = "/", api = ApiDocFundamentals, tags = ["Fundamentals"]),
+        (path = "/", api = ApiDocExport, tags = ["Fundamentals"]),
+        (path = "/", api = ApiDocIngestion, tags = ["Fundamentals"]),
+        (path = "/", api = ApiDocQuality, tags = ["Fundamentals"]),
         (path = "/", api = ApiDocCountries, tags = ["Countries"]),
         (path = "/", api = ApiDocTransactions, tags = ["Transactions"]),
         (path = "/", api = ApiDocAccounts, tags = ["Accounts"]),
+        (path = "/", api = ApiDocPortfolio, tags = ["Portfolio"]),
+        (path = "/", api = ApiDocValuation, tags = ["Valuation"]),
+        (path = "/", api = ApiDocScores, tags = ["Scores"]),
     ),
     components(
         schemas(ErrorMessage),
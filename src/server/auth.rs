@@ -90,6 +90,16 @@ impl JWTUserRequest {
     pub fn is_authorized(&self, role: &str) -> bool {
         self.role.eq(role)
     }
+    pub fn is_staff(&self) -> bool {
+        self.is_authorized("staff") || self.is_authorized("super")
+    }
+    pub fn require_staff(&self) -> Result<(), AppError> {
+        if self.is_staff() {
+            Ok(())
+        } else {
+            Err(AppError::RoleError)
+        }
+    }
     pub async fn get_user(&self, conn: &Object) -> Result<User, AppError> {
         let user_id = self.id;
         conn.interact(move |conn| {
@@ -0,0 +1,110 @@
+use axum::http::{header::CONTENT_TYPE, HeaderMap};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use utoipa::{ToResponse, ToSchema};
+
+use super::AppError;
+
+/// Rows of a bulk body by their number, each parsed or with the reason it wasn't
+pub type ParsedRows<T> = Vec<(usize, Result<T, String>)>;
+
+/// Body formats accepted by the bulk endpoints, picked from the `Content-Type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkFormat {
+    /// A JSON array of rows
+    Json,
+    /// One JSON row per line
+    Ndjson,
+    /// A CSV with a header line
+    Csv,
+}
+
+impl BulkFormat {
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, AppError> {
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if content_type.contains("csv") {
+            Ok(Self::Csv)
+        } else if content_type.contains("ndjson") || content_type.contains("jsonl") {
+            Ok(Self::Ndjson)
+        } else if content_type.contains("json") {
+            Ok(Self::Json)
+        } else {
+            Err(AppError::ValidationError(format!(
+                "Unsupported content type {content_type:?}, use application/json, application/x-ndjson or text/csv"
+            )))
+        }
+    }
+}
+
+/// A row of the body, numbered from 1, that couldn't be read or stored
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RejectedRow {
+    pub row: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct BulkReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub rejected: usize,
+    pub errors: Vec<RejectedRow>,
+}
+
+impl BulkReport {
+    pub fn reject(&mut self, row: usize, reason: impl ToString) {
+        self.rejected += 1;
+        self.errors.push(RejectedRow {
+            row,
+            reason: reason.to_string(),
+        });
+    }
+}
+
+/// Parses every row of a bulk body on its own so a malformed row doesn't discard the
+/// rest. Only a body that can't be split into rows at all is an error.
+pub fn parse_rows<T: DeserializeOwned>(
+    format: BulkFormat,
+    body: &[u8],
+) -> Result<ParsedRows<T>, AppError> {
+    match format {
+        BulkFormat::Json => {
+            let rows: Vec<serde_json::Value> = serde_json::from_slice(body)
+                .map_err(|err| AppError::ValidationError(err.to_string()))?;
+            Ok(rows
+                .into_iter()
+                .zip(1..)
+                .map(|(row, number)| {
+                    (
+                        number,
+                        serde_json::from_value(row).map_err(|err| err.to_string()),
+                    )
+                })
+                .collect())
+        }
+        BulkFormat::Ndjson => {
+            let body = std::str::from_utf8(body)
+                .map_err(|err| AppError::ValidationError(err.to_string()))?;
+            Ok(body
+                .lines()
+                .zip(1..)
+                .filter(|(line, _)| !line.trim().is_empty())
+                .map(|(line, number)| {
+                    (
+                        number,
+                        serde_json::from_str(line).map_err(|err| err.to_string()),
+                    )
+                })
+                .collect())
+        }
+        BulkFormat::Csv => Ok(csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(body)
+            .deserialize()
+            .zip(1..)
+            .map(|(row, number)| (number, row.map_err(|err: csv::Error| err.to_string())))
+            .collect()),
+    }
+}
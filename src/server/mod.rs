@@ -1,5 +1,6 @@
 mod api_docs;
 mod auth;
+mod bulk;
 mod config;
 mod responses;
 mod router;
@@ -9,6 +10,7 @@ mod ulid;
 mod versioning;
 
 pub use auth::{create_token, JWTUserRequest};
+pub use bulk::{parse_rows, BulkFormat, BulkReport, ParsedRows, RejectedRow};
 pub use config::{Config, EnvIs};
 pub use responses::{AppError, AppJson, AppResult, ErrorMessage};
 pub use router::get_router;
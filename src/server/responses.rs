@@ -62,12 +62,22 @@ pub enum AppError {
     DatabaseQueryError(diesel::result::Error),
     DatabaseConnectionInteractError(deadpool_diesel::InteractError),
     DatabasePoolError(deadpool_diesel::PoolError),
+    // A task moved off the async workers panicked or was cancelled
+    BlockingTaskError(tokio::task::JoinError),
     DoesNotExist,
     //
+    ValidationError(String),
+    //
     RoleError,
     //
     IpError(MaxMindDBError),
     IpDataNotFound,
+    //
+    ExportError(String),
+    // An external data source failed or sent something unreadable
+    SourceError(String),
+    // Amounts of two currencies, by id, were added without converting them
+    CurrencyMismatch(i64, i64),
 }
 
 #[derive(Serialize, ToResponse, ToSchema)]
@@ -106,16 +116,40 @@ impl IntoResponse for AppError {
             AppError::DatabasePoolError(err) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
             }
+            AppError::BlockingTaskError(err) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+            }
             AppError::DoesNotExist => (StatusCode::NOT_FOUND, "Not found".to_owned()),
 
+            AppError::ValidationError(message) => (StatusCode::UNPROCESSABLE_ENTITY, message),
+
             AppError::IpError(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
             AppError::IpDataNotFound => (StatusCode::INTERNAL_SERVER_ERROR, "Ip wrong".to_owned()),
+
+            AppError::ExportError(message) => (StatusCode::INTERNAL_SERVER_ERROR, message),
+            AppError::SourceError(message) => (StatusCode::BAD_GATEWAY, message),
+            AppError::CurrencyMismatch(left, right) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Amounts in currencies {left} and {right} can't be added"),
+            ),
         };
 
         (status, Json(ErrorMessage { message })).into_response()
     }
 }
 
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::DatabaseQueryError(err) => write!(f, "{err}"),
+            AppError::DoesNotExist => write!(f, "Not found"),
+            AppError::ValidationError(message) => write!(f, "{message}"),
+            AppError::RoleError => write!(f, "Not authorized"),
+            err => write!(f, "{err:?}"),
+        }
+    }
+}
+
 impl From<JsonRejection> for AppError {
     fn from(rejection: JsonRejection) -> Self {
         Self::JsonRejection(rejection)
@@ -34,15 +34,24 @@ use utoipa_scalar::{Scalar, Servable as ScalarServable};
 
 use super::{api_docs::ApiDoc, auth::jwt_middleware, AppState};
 use crate::{
+    aggregates::routes as aggregates_routes,
     companies::routes as companies_routes,
+    corporate_actions::routes as corporate_actions_routes,
     countries::routes as countries_routes,
     currencies::routes as currencies_routes,
     dictionary::routes as dictionary_routes,
+    dividends::routes as dividends_routes,
     exchanges::routes as exchanges_routes,
+    fundamentals::{
+        export_routes, ingestion_routes, quality_routes, routes as fundamentals_routes,
+    },
     industries::routes as industries_routes,
+    prices::routes as prices_routes,
+    scores::routes as scores_routes,
     sectors::routes as sectors_routes,
-    transactions::{accounts_routes, transactions_routes},
+    transactions::{accounts_routes, portfolio_routes, transactions_routes},
     users::routes as users_routes,
+    valuation::routes as valuation_routes,
 };
 
 pub fn get_router(state: AppState) -> Router<()> {
@@ -137,13 +146,24 @@ fn api_routes(state: AppState) -> Router<AppState> {
     Router::new()
         .merge(countries_routes(state.clone()))
         .merge(companies_routes(state.clone()))
+        .merge(corporate_actions_routes(state.clone()))
+        .merge(dividends_routes(state.clone()))
+        .merge(prices_routes(state.clone()))
         .merge(exchanges_routes(state.clone()))
         .merge(currencies_routes(state.clone()))
         .merge(industries_routes(state.clone()))
         .merge(sectors_routes(state.clone()))
+        .merge(aggregates_routes(state.clone()))
+        .merge(fundamentals_routes(state.clone()))
+        .merge(export_routes(state.clone()))
+        .merge(ingestion_routes(state.clone()))
+        .merge(quality_routes(state.clone()))
         .merge(transactions_routes(state.clone()))
         .merge(accounts_routes(state.clone()))
+        .merge(portfolio_routes(state.clone()))
         .merge(dictionary_routes(state.clone()))
+        .merge(valuation_routes(state.clone()))
+        .merge(scores_routes(state.clone()))
         .layer(from_fn_with_state(state.clone(), jwt_middleware))
         .merge(users_routes(state.clone())) //TODO: implement better auth
         .with_state(state)
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use utoipa::{self, OpenApi, ToSchema};
 
 use crate::{
+    currencies::{currency_decimals, Money},
     db::schema::{accounts, fees, rates_return},
     server::{AppError, AppResult, JWTUserRequest},
     AppState,
@@ -13,7 +14,7 @@ use crate::{
 #[derive(OpenApi)]
 #[openapi(
     paths(create_account, create_rate, list_accounts),
-    components(schemas(AccountRequest, Amount, Account, AccountCore)),
+    components(schemas(AccountRequest, Amount, Account, AccountCore, Money, Fee, FeeRequest)),
     security(("token_jwt" = []))
 )]
 pub struct ApiDoc;
@@ -25,7 +26,7 @@ pub fn routes(state: AppState) -> Router<AppState> {
         .with_state(state)
 }
 
-#[derive(Debug, Insertable, Serialize, Queryable, Selectable, Deserialize)]
+#[derive(Debug, Clone, Insertable, Serialize, ToSchema)]
 #[diesel(table_name = fees)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 struct Fee {
@@ -34,23 +35,37 @@ struct Fee {
     percentage: bool,
     account_id: i64,
     recurrence: String,
-    amount: BigDecimal,
+    /// In the currency of the account, a percentage when `percentage`
+    #[diesel(serialize_as = BigDecimal)]
+    #[serde(flatten)]
+    amount: Money,
 }
 
 impl Fee {
-    fn new(amount: &Amount, account_id: i64) -> Self {
+    fn new(amount: &Amount, account_id: i64, currency: i64, decimals: i32) -> Self {
         Self {
             account_id,
             active: true,
             percentage: amount.percentage,
             recurrence: amount.recurrence.clone(),
-            amount: amount.amount.clone(),
+            amount: amount.value(currency, decimals),
             description: amount.description.clone(),
         }
     }
 }
 
-#[derive(Debug, Insertable, Serialize, Deserialize)]
+/// A fee of an account, its amount is in the currency of the account
+#[derive(Debug, Deserialize, ToSchema)]
+struct FeeRequest {
+    description: Option<String>,
+    active: bool,
+    percentage: bool,
+    account_id: i64,
+    recurrence: String,
+    amount: BigDecimal,
+}
+
+#[derive(Debug, Insertable, Serialize)]
 #[diesel(table_name = rates_return)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 struct Rate {
@@ -59,17 +74,20 @@ struct Rate {
     percentage: bool,
     account_id: i64,
     recurrence: String,
-    amount: BigDecimal,
+    /// In the currency of the account, a percentage when `percentage`
+    #[diesel(serialize_as = BigDecimal)]
+    #[serde(flatten)]
+    amount: Money,
 }
 
 impl Rate {
-    fn new(amount: &Amount, account_id: i64) -> Self {
+    fn new(amount: &Amount, account_id: i64, currency: i64, decimals: i32) -> Self {
         Self {
             account_id,
             active: true,
             percentage: amount.percentage,
             recurrence: amount.recurrence.clone(),
-            amount: amount.amount.clone(),
+            amount: amount.value(currency, decimals),
             description: amount.description.clone(),
         }
     }
@@ -84,6 +102,22 @@ struct Amount {
     amount: BigDecimal,
 }
 
+impl Amount {
+    fn value(&self, currency: i64, decimals: i32) -> Money {
+        account_amount(self.amount.clone(), self.percentage, currency, decimals)
+    }
+}
+
+/// Fixed amounts are money of the account, rounded to its currency, percentages
+/// are kept as given
+fn account_amount(amount: BigDecimal, percentage: bool, currency: i64, decimals: i32) -> Money {
+    let amount = Money::new(amount, currency);
+    if percentage {
+        return amount;
+    }
+    amount.round(decimals)
+}
+
 #[derive(Debug, Selectable, Queryable, Serialize, Deserialize, ToSchema)]
 #[diesel(table_name = accounts)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -92,7 +126,10 @@ pub struct AccountCore {
     pub id: i64,
     name: String,
     company: String,
-    currency_id: i64,
+    #[diesel(select_expression = (accounts::amount, accounts::currency_id))]
+    #[diesel(select_expression_type = (accounts::amount, accounts::currency_id))]
+    #[serde(flatten)]
+    pub balance: Money,
 }
 
 #[derive(Debug, Insertable, Serialize, Deserialize, ToSchema)]
@@ -104,8 +141,9 @@ struct Account {
     category: String,
     company: String,
     description: Option<String>,
-    currency_id: i64,
-    amount: BigDecimal,
+    #[diesel(embed)]
+    #[serde(flatten)]
+    balance: Money,
 }
 
 #[derive(Debug, ToSchema, Serialize, Deserialize)]
@@ -117,10 +155,13 @@ pub struct AccountRequest {
 }
 
 pub fn create_account_from_request(
-    req: AccountRequest,
+    mut req: AccountRequest,
     current_user_id: i64,
     conn: &mut PgConnection,
 ) -> Result<AccountCore, AppError> {
+    let currency = req.account.balance.currency;
+    let decimals = currency_decimals(currency, conn)?;
+    req.account.balance = req.account.balance.round(decimals);
     let account = diesel::insert_into(accounts::table)
         .values((accounts::user_id.eq(current_user_id), req.account))
         .returning(AccountCore::as_returning())
@@ -131,7 +172,7 @@ pub fn create_account_from_request(
         let fees = req
             .fees
             .iter()
-            .map(|f| Fee::new(f, pk))
+            .map(|f| Fee::new(f, pk, currency, decimals))
             .collect::<Vec<Fee>>();
         diesel::insert_into(fees::table)
             .values(fees)
@@ -142,7 +183,7 @@ pub fn create_account_from_request(
         let rates = req
             .rates
             .iter()
-            .map(|f| Rate::new(f, pk))
+            .map(|f| Rate::new(f, pk, currency, decimals))
             .collect::<Vec<Rate>>();
         diesel::insert_into(rates_return::table)
             .values(rates)
@@ -209,24 +250,39 @@ async fn create_account(
 #[utoipa::path(
     post,
     path = "rates",
-    request_body = Fee,
+    request_body = FeeRequest,
     responses(
         (status = 200, body = Fee, description = "Add a new rate or fee to an account"),
         (status = "4XX", body = ErrorMessage, description = "Client error"),
         (status = "5XX", body = ErrorMessage, description = "Server error"),
     )
 )]
-async fn create_rate(state: AppState, Json(rate): Json<Fee>) -> AppResult<Fee> {
+async fn create_rate(state: AppState, Json(rate): Json<FeeRequest>) -> AppResult<Fee> {
     Ok(Json(
         state
             .db_write()
             .await?
             .interact(move |conn| {
+                let currency: i64 = accounts::table
+                    .find(rate.account_id)
+                    .select(accounts::currency_id)
+                    .first(conn)
+                    .optional()?
+                    .ok_or(AppError::DoesNotExist)?;
+                let decimals = currency_decimals(currency, conn)?;
+                let fee = Fee {
+                    description: rate.description,
+                    active: rate.active,
+                    percentage: rate.percentage,
+                    account_id: rate.account_id,
+                    recurrence: rate.recurrence,
+                    amount: account_amount(rate.amount, rate.percentage, currency, decimals),
+                };
                 diesel::insert_into(fees::table)
-                    .values(&rate)
-                    .returning(Fee::as_returning())
-                    .get_result(conn)
-                    .map_err(AppError::DatabaseQueryError)
+                    .values(fee.clone())
+                    .execute(conn)
+                    .map_err(AppError::DatabaseQueryError)?;
+                Ok::<_, AppError>(fee)
             })
             .await
             .map_err(AppError::DatabaseConnectionInteractError)??,
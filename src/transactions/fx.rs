@@ -0,0 +1,274 @@
+//! Splits the return of the holdings in a foreign currency into what the asset did
+//! in its own currency and what the moves of the exchange rate added or took away.
+//! Cash is left out, account balances aren't kept by date
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+
+use axum::{extract::Query, Extension, Json};
+use bigdecimal::ToPrimitive;
+use chrono::{NaiveDate, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{self, ToResponse, ToSchema};
+
+use super::{
+    portfolio::{company_info, price_currency, report_currency, CompanyInfo, ValuationQuery},
+    positions::{load_lots, Lot},
+};
+use crate::{
+    corporate_actions::SplitFactors,
+    currencies::exchange_rate,
+    db::schema::{accounts, currencies},
+    prices::close_on,
+    server::{AppError, AppResult, JWTUserRequest},
+    AppState,
+};
+
+/// Return in the report currency. `asset_return` is the move of the price in the
+/// currency it trades in, converted at the rate of the valuation date, and
+/// `currency_return` the move of the rate between acquisition and valuation on the
+/// cost. Both add up to `total_return`.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct FxReturn {
+    /// At the rates of the acquisition dates
+    pub cost: f64,
+    /// At the rate of the valuation date
+    pub market_value: f64,
+    pub asset_return: f64,
+    pub currency_return: f64,
+    pub total_return: f64,
+}
+
+impl FxReturn {
+    fn add(&mut self, other: &FxReturn) {
+        self.cost += other.cost;
+        self.market_value += other.market_value;
+        self.asset_return += other.asset_return;
+        self.currency_return += other.currency_return;
+        self.total_return += other.total_return;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FxPosition {
+    pub account_id: i64,
+    pub ticker: String,
+    pub name: Option<String>,
+    /// In today's shares
+    pub quantity: f64,
+    /// Date of the first lot
+    pub acquired: NaiveDate,
+    /// Currency the company trades in
+    pub price_currency: String,
+    #[serde(flatten)]
+    pub fx_return: FxReturn,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FxAccount {
+    pub account_id: i64,
+    pub name: String,
+    #[serde(flatten)]
+    pub fx_return: FxReturn,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FxGroup {
+    pub name: String,
+    #[serde(flatten)]
+    pub fx_return: FxReturn,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct FxReport {
+    pub date: NaiveDate,
+    pub currency: String,
+    pub totals: FxReturn,
+    pub positions: Vec<FxPosition>,
+    pub accounts: Vec<FxAccount>,
+    /// By the currency the holdings trade in
+    pub currencies: Vec<FxGroup>,
+    /// Tickers left out for lack of a price or an exchange rate
+    pub unpriced: Vec<String>,
+}
+
+/// Rate of the date, looking each pair and date up once
+fn rate_on(
+    from_id: i64,
+    to_id: i64,
+    date: NaiveDate,
+    rates: &mut HashMap<(i64, i64, NaiveDate), Option<f64>>,
+    conn: &mut PgConnection,
+) -> Result<Option<f64>, AppError> {
+    Ok(match rates.entry((from_id, to_id, date)) {
+        Entry::Occupied(entry) => *entry.get(),
+        Entry::Vacant(entry) => *entry
+            .insert(exchange_rate(from_id, to_id, date, conn)?.and_then(|rate| rate.rate.to_f64())),
+    })
+}
+
+/// Cost of the lots in the price currency, and in the report currency at the rates
+/// of their dates. None when a rate is missing
+fn lots_cost(
+    lots: &[Lot],
+    price_currency_id: i64,
+    currency_id: i64,
+    rates: &mut HashMap<(i64, i64, NaiveDate), Option<f64>>,
+    conn: &mut PgConnection,
+) -> Result<Option<(f64, f64)>, AppError> {
+    let (mut price_cost, mut cost) = (0.0, 0.0);
+    for lot in lots {
+        let to_price = rate_on(lot.cost.currency, price_currency_id, lot.date, rates, conn)?;
+        let to_report = rate_on(lot.cost.currency, currency_id, lot.date, rates, conn)?;
+        let (Some(to_price), Some(to_report)) = (to_price, to_report) else {
+            return Ok(None);
+        };
+        let lot_cost = lot.cost.amount.to_f64().unwrap_or_default();
+        price_cost += lot_cost * to_price;
+        cost += lot_cost * to_report;
+    }
+    Ok(Some((price_cost, cost)))
+}
+
+fn group_by(groups: &mut BTreeMap<String, FxGroup>, name: &str, fx_return: &FxReturn) {
+    groups
+        .entry(name.to_string())
+        .or_insert_with(|| FxGroup {
+            name: name.to_string(),
+            ..Default::default()
+        })
+        .fx_return
+        .add(fx_return);
+}
+
+fn fx_report_of(
+    user_id: i64,
+    date: NaiveDate,
+    currency: Option<String>,
+    conn: &mut PgConnection,
+) -> Result<FxReport, AppError> {
+    let (currency_id, currency) = report_currency(user_id, currency, conn)?;
+    let codes: HashMap<i64, String> = currencies::table
+        .select((currencies::id, currencies::alphabetic_code))
+        .load(conn)?
+        .into_iter()
+        .collect();
+    let code = |id: i64| codes.get(&id).cloned().unwrap_or_default();
+    let account_names: HashMap<i64, String> = accounts::table
+        .filter(accounts::user_id.eq(user_id))
+        .select((accounts::id, accounts::name))
+        .load(conn)?
+        .into_iter()
+        .collect();
+
+    let mut rates = HashMap::new();
+    let mut companies_info: HashMap<i64, CompanyInfo> = HashMap::new();
+    let mut positions = Vec::new();
+    let mut unpriced = Vec::new();
+    let lots = load_lots(user_id, date, conn)?;
+    for lots in lots.chunk_by(|a, b| a.account_id == b.account_id && a.company_id == b.company_id) {
+        let quantity: f64 = lots.iter().map(|lot| lot.quantity).sum();
+        if quantity == 0.0 {
+            continue;
+        }
+        let first = &lots[0];
+        let company = company_info(first.company_id, &mut companies_info, conn)?;
+        let Some(close) = close_on(first.company_id, date, conn)? else {
+            unpriced.push(company.ticker.clone());
+            continue;
+        };
+        let price_currency_id = price_currency(&close, company, first.cost.currency, conn);
+        // Lots are in the shares of the splits applied so far, the close in the ones
+        // of its date
+        let factor = SplitFactors::applied(first.company_id, conn)?.since(close.date);
+        let price_value = quantity * close.close / factor;
+
+        let costs = lots_cost(lots, price_currency_id, currency_id, &mut rates, conn)?;
+        let to_report = rate_on(price_currency_id, currency_id, date, &mut rates, conn)?;
+        let (Some((price_cost, cost)), Some(to_report)) = (costs, to_report) else {
+            unpriced.push(company.ticker.clone());
+            continue;
+        };
+        let market_value = price_value * to_report;
+        positions.push(FxPosition {
+            account_id: first.account_id,
+            ticker: company.ticker.clone(),
+            name: company.name.clone(),
+            quantity,
+            acquired: first.date,
+            price_currency: code(price_currency_id),
+            fx_return: FxReturn {
+                cost,
+                market_value,
+                asset_return: (price_value - price_cost) * to_report,
+                currency_return: price_cost * to_report - cost,
+                total_return: market_value - cost,
+            },
+        });
+    }
+
+    let mut totals = FxReturn::default();
+    let mut by_account: BTreeMap<i64, FxAccount> = BTreeMap::new();
+    let mut by_currency = BTreeMap::new();
+    for position in &positions {
+        totals.add(&position.fx_return);
+        by_account
+            .entry(position.account_id)
+            .or_insert_with(|| FxAccount {
+                account_id: position.account_id,
+                name: account_names
+                    .get(&position.account_id)
+                    .cloned()
+                    .unwrap_or_default(),
+                ..Default::default()
+            })
+            .fx_return
+            .add(&position.fx_return);
+        group_by(
+            &mut by_currency,
+            &position.price_currency,
+            &position.fx_return,
+        );
+    }
+    positions.sort_by(|a, b| {
+        b.fx_return
+            .currency_return
+            .abs()
+            .total_cmp(&a.fx_return.currency_return.abs())
+    });
+    unpriced.sort();
+    unpriced.dedup();
+    Ok(FxReport {
+        date,
+        currency,
+        totals,
+        positions,
+        accounts: by_account.into_values().collect(),
+        currencies: by_currency.into_values().collect(),
+        unpriced,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "portfolio/fx",
+    params(ValuationQuery),
+    responses(
+        (status = 200, body = FxReport, description = "Return of the holdings split into the move of the asset and the one of the exchange rate, by position, account and currency"),
+        (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+        (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+    )
+)]
+pub(super) async fn fx_report(
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    Query(query_params): Query<ValuationQuery>,
+) -> AppResult<FxReport> {
+    let date = query_params.date.unwrap_or(Utc::now().date_naive());
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| fx_report_of(current_user.id, date, query_params.currency, conn))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
@@ -1,6 +1,11 @@
 mod accounts;
 mod files_parsers;
+mod fx;
+mod portfolio;
+mod positions;
 mod transactions;
 
 pub use accounts::{routes as accounts_routes, ApiDoc as ApiDocAccounts};
+pub use portfolio::{routes as portfolio_routes, ApiDoc as ApiDocPortfolio};
+pub use positions::{load_positions, Position};
 pub use transactions::{routes as transactions_routes, ApiDoc as ApiDocTransactions};
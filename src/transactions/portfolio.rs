@@ -0,0 +1,328 @@
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+use bigdecimal::ToPrimitive;
+use chrono::{NaiveDate, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{self, IntoParams, OpenApi, ToResponse, ToSchema};
+
+use super::{
+    fx::{fx_report, FxAccount, FxGroup, FxPosition, FxReport, FxReturn},
+    positions::load_positions,
+};
+use crate::{
+    corporate_actions::SplitFactors,
+    currencies::{exchange_rate, get_currency_from_country, get_currency_id},
+    db::schema::{companies, countries, currencies, profiles, sectors},
+    prices::{close_on, Close},
+    server::{AppError, AppResult, JWTUserRequest},
+    AppState,
+};
+
+/// Name of the group of the positions without a sector or a country
+const UNCLASSIFIED: &str = "Unclassified";
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(portfolio_valuation, super::fx::fx_report),
+    components(schemas(PortfolioValuation, PositionValue, Allocation, FxReport, FxReturn, FxPosition, FxAccount, FxGroup),
+    responses(PortfolioValuation, FxReport)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/portfolio/valuation", get(portfolio_valuation))
+        .route("/portfolio/fx", get(fx_report))
+        .with_state(state)
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct ValuationQuery {
+    /// Today by default
+    pub date: Option<NaiveDate>,
+    /// Currency code to value the portfolio in, the one of the user's profile by default
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PositionValue {
+    pub account_id: i64,
+    pub ticker: String,
+    pub name: Option<String>,
+    pub sector: Option<String>,
+    pub country: Option<String>,
+    /// In today's shares
+    pub quantity: f64,
+    /// Trading day of the close, the valuation date or the last one before it
+    pub price_date: NaiveDate,
+    /// In the shares of the price date and the currency it trades in
+    pub close: f64,
+    pub price_currency_id: i64,
+    pub market_value: f64,
+    pub cost: f64,
+    pub unrealized_gain: f64,
+    pub unrealized_gain_percent: Option<f64>,
+    /// Share of the market value of the portfolio
+    pub weight: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Allocation {
+    pub name: String,
+    pub market_value: f64,
+    pub cost: f64,
+    pub unrealized_gain: f64,
+    pub weight: f64,
+}
+
+impl Allocation {
+    fn add(&mut self, position: &PositionValue) {
+        self.market_value += position.market_value;
+        self.cost += position.cost;
+        self.unrealized_gain += position.unrealized_gain;
+        self.weight += position.weight;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct PortfolioValuation {
+    pub date: NaiveDate,
+    pub currency: String,
+    pub market_value: f64,
+    pub cost: f64,
+    pub unrealized_gain: f64,
+    pub unrealized_gain_percent: Option<f64>,
+    pub positions: Vec<PositionValue>,
+    pub sectors: Vec<Allocation>,
+    pub countries: Vec<Allocation>,
+    /// Tickers left out for lack of a price or an exchange rate on the date
+    pub unpriced: Vec<String>,
+}
+
+pub(super) struct CompanyInfo {
+    pub ticker: String,
+    pub name: Option<String>,
+    pub sector: Option<String>,
+    pub country: Option<String>,
+    pub country_id: Option<i64>,
+}
+
+/// Ticker, name and classification of the company, looking each one up once
+pub(super) fn company_info<'a>(
+    company_id: i64,
+    companies_info: &'a mut HashMap<i64, CompanyInfo>,
+    conn: &mut PgConnection,
+) -> QueryResult<&'a CompanyInfo> {
+    Ok(match companies_info.entry(company_id) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => {
+            let (ticker, name, sector, country, country_id) = companies::table
+                .left_join(sectors::table)
+                .left_join(countries::table)
+                .filter(companies::id.eq(company_id))
+                .select((
+                    companies::ticker,
+                    companies::name,
+                    sectors::name.nullable(),
+                    countries::name.nullable(),
+                    companies::country_id,
+                ))
+                .first(conn)?;
+            entry.insert(CompanyInfo {
+                ticker,
+                name,
+                sector,
+                country,
+                country_id,
+            })
+        }
+    })
+}
+
+/// Currency a price trades in. Prices without one trade in the currency of the
+/// company's country, or of the account when the country has none
+pub(super) fn price_currency(
+    close: &Close,
+    company: &CompanyInfo,
+    account_currency_id: i64,
+    conn: &mut PgConnection,
+) -> i64 {
+    match (close.currency_id, company.country_id) {
+        (Some(currency_id), _) => currency_id,
+        (None, Some(country_id)) => {
+            get_currency_from_country(country_id, conn).unwrap_or(account_currency_id)
+        }
+        (None, None) => account_currency_id,
+    }
+}
+
+/// Id and code of the currency asked for, the one of the user's profile by default
+pub(super) fn report_currency(
+    user_id: i64,
+    currency: Option<String>,
+    conn: &mut PgConnection,
+) -> Result<(i64, String), AppError> {
+    let currency_id = match currency {
+        Some(code) => get_currency_id(&code, conn)?,
+        None => profiles::table
+            .filter(profiles::user_id.eq(user_id))
+            .select(profiles::currency_id)
+            .first(conn)?,
+    };
+    let code: String = currencies::table
+        .find(currency_id)
+        .select(currencies::alphabetic_code)
+        .first(conn)?;
+    Ok((currency_id, code))
+}
+
+/// Converts with the rate of the date, looking each pair up once
+fn convert(
+    amount: f64,
+    from_id: i64,
+    to_id: i64,
+    date: NaiveDate,
+    rates: &mut HashMap<(i64, i64), Option<f64>>,
+    conn: &mut PgConnection,
+) -> Result<Option<f64>, AppError> {
+    let rate = match rates.entry((from_id, to_id)) {
+        Entry::Occupied(entry) => *entry.get(),
+        Entry::Vacant(entry) => *entry
+            .insert(exchange_rate(from_id, to_id, date, conn)?.and_then(|rate| rate.rate.to_f64())),
+    };
+    Ok(rate.map(|rate| amount * rate))
+}
+
+fn gain_percent(gain: f64, cost: f64) -> Option<f64> {
+    (cost > 0.0).then(|| gain / cost * 100.0)
+}
+
+fn allocations(
+    positions: &[PositionValue],
+    group: impl Fn(&PositionValue) -> Option<&String>,
+) -> Vec<Allocation> {
+    let mut groups: BTreeMap<String, Allocation> = BTreeMap::new();
+    for position in positions {
+        let name = group(position).map_or(UNCLASSIFIED, String::as_str);
+        groups
+            .entry(name.to_string())
+            .or_insert_with(|| Allocation {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .add(position);
+    }
+    let mut groups: Vec<Allocation> = groups.into_values().collect();
+    groups.sort_by(|a, b| b.market_value.total_cmp(&a.market_value));
+    groups
+}
+
+fn value_portfolio(
+    user_id: i64,
+    date: NaiveDate,
+    currency: Option<String>,
+    conn: &mut PgConnection,
+) -> Result<PortfolioValuation, AppError> {
+    let (currency_id, currency) = report_currency(user_id, currency, conn)?;
+
+    let mut rates = HashMap::new();
+    let mut companies_info: HashMap<i64, CompanyInfo> = HashMap::new();
+    let mut positions = Vec::new();
+    let mut unpriced = Vec::new();
+    for position in load_positions(user_id, date, conn)? {
+        let company = company_info(position.company_id, &mut companies_info, conn)?;
+        let Some(close) = close_on(position.company_id, date, conn)? else {
+            unpriced.push(company.ticker.clone());
+            continue;
+        };
+        let price_currency_id = price_currency(&close, company, position.cost.currency, conn);
+        // Lots are in the shares of the splits applied so far, the close in the ones
+        // of its date
+        let factor = SplitFactors::applied(position.company_id, conn)?.since(close.date);
+        let market_value = convert(
+            position.quantity * close.close / factor,
+            price_currency_id,
+            currency_id,
+            date,
+            &mut rates,
+            conn,
+        )?;
+        let cost = convert(
+            position.cost.amount.to_f64().unwrap_or_default(),
+            position.cost.currency,
+            currency_id,
+            date,
+            &mut rates,
+            conn,
+        )?;
+        let (Some(market_value), Some(cost)) = (market_value, cost) else {
+            unpriced.push(company.ticker.clone());
+            continue;
+        };
+        positions.push(PositionValue {
+            account_id: position.account_id,
+            ticker: company.ticker.clone(),
+            name: company.name.clone(),
+            sector: company.sector.clone(),
+            country: company.country.clone(),
+            quantity: position.quantity,
+            price_date: close.date,
+            close: close.close,
+            price_currency_id,
+            market_value,
+            cost,
+            unrealized_gain: market_value - cost,
+            unrealized_gain_percent: gain_percent(market_value - cost, cost),
+            weight: 0.0,
+        });
+    }
+
+    let market_value: f64 = positions.iter().map(|position| position.market_value).sum();
+    let cost: f64 = positions.iter().map(|position| position.cost).sum();
+    if market_value != 0.0 {
+        for position in positions.iter_mut() {
+            position.weight = position.market_value / market_value;
+        }
+    }
+    positions.sort_by(|a, b| b.market_value.total_cmp(&a.market_value));
+    Ok(PortfolioValuation {
+        date,
+        currency,
+        market_value,
+        cost,
+        unrealized_gain: market_value - cost,
+        unrealized_gain_percent: gain_percent(market_value - cost, cost),
+        sectors: allocations(&positions, |position| position.sector.as_ref()),
+        countries: allocations(&positions, |position| position.country.as_ref()),
+        positions,
+        unpriced,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "portfolio/valuation",
+    params(ValuationQuery),
+    responses(
+        (status = 200, body = PortfolioValuation, description = "Positions of the user valued at the close of the date, by position, sector and country"),
+        (status = "4XX", body = ErrorMessage, description = "Opusi daisy"),
+        (status = "5XX", body = ErrorMessage, description = "Opusi daisy"),
+    )
+)]
+async fn portfolio_valuation(
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    Query(query_params): Query<ValuationQuery>,
+) -> AppResult<PortfolioValuation> {
+    let date = query_params.date.unwrap_or(Utc::now().date_naive());
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| value_portfolio(current_user.id, date, query_params.currency, conn))
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
@@ -0,0 +1,102 @@
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+use crate::{
+    currencies::Money,
+    db::schema::{
+        accounts, assets_details, investment_details, transactions, transactions_details,
+    },
+    dividends::DIVIDEND_CATEGORY,
+    server::AppError,
+};
+
+/// Shares of a company held in an account, adding up its lots. Quantities are in
+/// today's shares since lots are adjusted on every split.
+#[derive(Debug)]
+pub struct Position {
+    pub account_id: i64,
+    pub company_id: i64,
+    pub quantity: f64,
+    /// In the currency of the account
+    pub cost: Money,
+}
+
+/// Shares of a company bought, or sold, by a transaction
+#[derive(Debug)]
+pub struct Lot {
+    pub account_id: i64,
+    pub company_id: i64,
+    /// Date of the transaction
+    pub date: NaiveDate,
+    /// In today's shares
+    pub quantity: f64,
+    /// In the currency of the account
+    pub cost: Money,
+}
+
+/// Lots of the user bought up to the date, by account and company. Dividend
+/// transactions carry the asset they pay on but aren't lots
+pub fn load_lots(user_id: i64, date: NaiveDate, conn: &mut PgConnection) -> QueryResult<Vec<Lot>> {
+    let lots: Vec<(i64, Option<i64>, NaiveDate, f64, Money)> = transactions::table
+        .inner_join(transactions_details::table)
+        .inner_join(accounts::table)
+        .inner_join(
+            investment_details::table
+                .on(transactions_details::investment_details_id
+                    .eq(investment_details::id.nullable())),
+        )
+        .inner_join(assets_details::table.on(investment_details::asset_id.eq(assets_details::id)))
+        .filter(transactions::user_id.eq(user_id))
+        .filter(transactions::date.le(date))
+        .filter(assets_details::company_id.is_not_null())
+        .filter(transactions::category.not_ilike(format!("{DIVIDEND_CATEGORY}%")))
+        .order((accounts::id, assets_details::company_id, transactions::date))
+        .select((
+            accounts::id,
+            assets_details::company_id,
+            transactions::date,
+            investment_details::quantity,
+            (investment_details::cost, accounts::currency_id),
+        ))
+        .load(conn)?;
+    Ok(lots
+        .into_iter()
+        .filter_map(|(account_id, company_id, date, quantity, cost)| {
+            Some(Lot {
+                account_id,
+                company_id: company_id?,
+                date,
+                quantity,
+                cost,
+            })
+        })
+        .collect())
+}
+
+/// Positions of the user with the lots bought up to the date, by account and company
+pub fn load_positions(
+    user_id: i64,
+    date: NaiveDate,
+    conn: &mut PgConnection,
+) -> Result<Vec<Position>, AppError> {
+    let mut positions: Vec<Position> = Vec::new();
+    for lot in load_lots(user_id, date, conn)? {
+        match positions.last_mut() {
+            Some(position)
+                if position.account_id == lot.account_id
+                    && position.company_id == lot.company_id =>
+            {
+                position.quantity += lot.quantity;
+                position.cost = position.cost.checked_add(&lot.cost)?;
+            }
+            _ => positions.push(Position {
+                account_id: lot.account_id,
+                company_id: lot.company_id,
+                quantity: lot.quantity,
+                cost: lot.cost,
+            }),
+        }
+    }
+    positions.retain(|position| position.quantity != 0.0);
+    Ok(positions)
+}
@@ -12,7 +12,13 @@ use tokio::io::AsyncWriteExt;
 use utoipa::{self, OpenApi, ToSchema};
 
 use crate::{
-    db::schema::{assets_details, investment_details, transactions, transactions_details},
+    companies::resolve_identifier,
+    corporate_actions::SplitFactors,
+    currencies::{currency_decimals, Money},
+    db::schema::{
+        accounts, assets_details, investment_details, transactions, transactions_details,
+    },
+    dividends::{is_dividend, match_dividend},
     server::{AppError, AppResult, JWTUserRequest},
     AppState,
 };
@@ -27,7 +33,7 @@ const CONTENT_LENGTH_LIMIT: usize = 20 * 1024 * 1024;
 #[derive(OpenApi)]
 #[openapi(
     paths(upload_transactions_file, create_transaction),
-    components(schemas(AssetDetail, InvestmentDetail, AccountReq, TransactionDetail, Transaction, TransactionRequest)),
+    components(schemas(AssetDetail, InvestmentDetail, AccountReq, TransactionDetailFields, TransactionFields, TransactionRequest)),
     security(("token_jwt" = []))
 )]
 pub struct ApiDoc;
@@ -179,6 +185,10 @@ struct AssetDetail {
     category: String,
     name: String,
     company_id: Option<i64>,
+    /// Ticker, ISIN, CUSIP, CIK or FIGI used to link the asset to a company when
+    /// `company_id` isn't given
+    #[diesel(skip_insertion)]
+    identifier: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Insertable, ToSchema)]
@@ -191,29 +201,63 @@ struct InvestmentDetail {
     asset_id: i64,
 }
 
-#[derive(Debug, Deserialize, Serialize, Insertable, ToSchema)]
+#[derive(Debug, Insertable)]
 #[diesel(table_name = transactions_details)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 struct TransactionDetail {
+    description: Option<String>,
+    comment: Option<String>,
+    /// In the currency the transaction was made in, not always the account's
+    original_amount: BigDecimal,
+    #[diesel(serialize_as = BigDecimal)]
+    fee: Money,
+}
+
+/// Details as sent, their fee is in the currency of the account
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+#[schema(as = TransactionDetail)]
+struct TransactionDetailFields {
     description: Option<String>,
     comment: Option<String>,
     original_amount: BigDecimal,
     fee: BigDecimal,
 }
 
-#[derive(Debug, Deserialize, Serialize, Insertable, ToSchema)]
+impl TransactionDetailFields {
+    fn into_detail(self, currency: i64, decimals: i32) -> TransactionDetail {
+        TransactionDetail {
+            description: self.description,
+            comment: self.comment,
+            original_amount: self.original_amount,
+            fee: Money::new(self.fee, currency).round(decimals),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
 #[diesel(table_name = transactions)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 struct Transaction {
-    #[serde(skip)]
     user_id: i64,
-    #[serde(skip)]
     details_id: i64,
-    #[serde(skip)]
     account_id: i64,
+    date: chrono::NaiveDate,
+    #[diesel(serialize_as = BigDecimal)]
+    amount: Money,
+    category: String,
+    dividend_id: Option<i64>,
+}
+
+/// Transaction as sent, its amount is in the currency of the account
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+#[schema(as = Transaction)]
+struct TransactionFields {
     date: chrono::NaiveDate,
     amount: BigDecimal,
     category: String,
+    /// Dividend paid by the transaction, matched by company and date when left out
+    #[serde(default)]
+    dividend_id: Option<i64>,
 }
 
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
@@ -225,9 +269,9 @@ enum AccountReq {
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 struct TransactionRequest {
-    details: TransactionDetail,
+    details: TransactionDetailFields,
     investment_details: Option<InvestmentDetail>,
-    transaction: Transaction,
+    transaction: TransactionFields,
     asset: Option<AssetDetail>,
     account: AccountReq,
 }
@@ -253,17 +297,49 @@ async fn create_transaction(
         .await?
         .interact(move |conn| {
             conn.transaction(|conn| {
+                let (account_id, currency) = match req.account {
+                    AccountReq::Id(id) => {
+                        let currency: i64 = accounts::table
+                            .find(id)
+                            .filter(accounts::user_id.eq(current_user.id))
+                            .select(accounts::currency_id)
+                            .first(conn)
+                            .optional()?
+                            .ok_or(AppError::DoesNotExist)?;
+                        (id, currency)
+                    }
+                    AccountReq::Account(v) => {
+                        let account = create_account_from_request(v, current_user.id, conn)?;
+                        (account.id, account.balance.currency)
+                    }
+                };
+                // Amounts are in the currency of the account
+                let decimals = currency_decimals(currency, conn)?;
+
                 let mut investment_details_id: Option<i64> = None;
-                if req.investment_details.is_some() && req.asset.is_some() {
-                    let asset = req.asset.unwrap();
+                let mut asset = req.asset;
+                if let Some(asset) = asset.as_mut() {
+                    if let (None, Some(identifier)) = (asset.company_id, &asset.identifier) {
+                        asset.company_id = resolve_identifier(identifier, conn)?.company_id();
+                    }
+                }
+                let company_id = asset.as_ref().and_then(|asset| asset.company_id);
+                if let (Some(mut investment), Some(asset)) = (req.investment_details, asset) {
                     let asset_id: i64 = diesel::insert_into(assets_details::table)
                         .values(asset)
                         .returning(assets_details::id)
                         .get_result(conn)
                         .map_err(AppError::DatabaseQueryError)?;
 
-                    let mut investment = req.investment_details.unwrap();
                     investment.asset_id = asset_id;
+                    investment.cost = Money::new(std::mem::take(&mut investment.cost), currency)
+                        .round(decimals)
+                        .into();
+                    // Lots bought before a split are stored in today's shares
+                    if let Some(company_id) = company_id {
+                        investment.quantity *=
+                            SplitFactors::applied(company_id, conn)?.since(req.transaction.date);
+                    }
                     investment_details_id = Some(
                         diesel::insert_into(investment_details::table)
                             .values(investment)
@@ -273,23 +349,31 @@ async fn create_transaction(
                     );
                 }
 
+                let details = req.details.into_detail(currency, decimals);
                 let details_id: i64 = diesel::insert_into(transactions_details::table)
                     .values((
                         transactions_details::investment_details_id.eq(investment_details_id),
-                        req.details,
+                        details,
                     ))
                     .returning(transactions_details::id)
                     .get_result(conn)
                     .map_err(AppError::DatabaseQueryError)?;
 
-                let mut transaction = req.transaction;
-                transaction.user_id = current_user.id;
-                transaction.details_id = details_id;
-                transaction.account_id = match req.account {
-                    AccountReq::Id(v) => v,
-                    AccountReq::Account(v) => {
-                        create_account_from_request(v, current_user.id, conn)?.id
+                let fields = req.transaction;
+                let mut dividend_id = fields.dividend_id;
+                if let (None, Some(company_id)) = (dividend_id, company_id) {
+                    if is_dividend(&fields.category) {
+                        dividend_id = match_dividend(company_id, fields.date, conn)?;
                     }
+                }
+                let transaction = Transaction {
+                    user_id: current_user.id,
+                    details_id,
+                    account_id,
+                    date: fields.date,
+                    amount: Money::new(fields.amount, currency).round(decimals),
+                    category: fields.category,
+                    dividend_id,
                 };
 
                 debug!("transaction {transaction:?}");
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{ToResponse, ToSchema};
+
+use crate::server::AppError;
+
+pub const DEFAULT_DISCOUNT_RATE: f64 = 0.09;
+pub const DEFAULT_TERMINAL_GROWTH: f64 = 0.025;
+pub const DEFAULT_PROJECTION_YEARS: i32 = 5;
+pub const MAX_PROJECTION_YEARS: i32 = 30;
+/// Bounds of the growth rate derived from the company's history, a single volatile
+/// year would otherwise drive the whole projection
+pub const MIN_DERIVED_GROWTH: f64 = -0.2;
+pub const MAX_DERIVED_GROWTH: f64 = 0.25;
+/// Upper bound of every rate given by the user, 100% a year
+pub const MAX_RATE: f64 = 1.0;
+
+const DISCOUNT_RATE_STEPS: [f64; 5] = [-0.02, -0.01, 0.0, 0.01, 0.02];
+const TERMINAL_GROWTH_STEPS: [f64; 5] = [-0.01, -0.005, 0.0, 0.005, 0.01];
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DcfParameters {
+    pub discount_rate: f64,
+    pub terminal_growth: f64,
+    pub growth_rate: f64,
+    pub projection_years: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct SensitivityGrid {
+    /// Columns of the grid
+    terminal_growths: Vec<f64>,
+    rows: Vec<SensitivityRow>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SensitivityRow {
+    discount_rate: f64,
+    /// Intrinsic value per share for each terminal growth, empty when the
+    /// shifted rates wouldn't pass [`validate_parameters`]
+    values: Vec<Option<f64>>,
+}
+
+/// Checks a rate given by the user, compounding by `1 + rate` has to stay positive
+pub fn validate_rate(name: &str, rate: f64) -> Result<(), AppError> {
+    if !rate.is_finite() || rate <= -1.0 || rate > MAX_RATE {
+        return Err(AppError::ValidationError(format!(
+            "{name} must be a number greater than -1 and at most {MAX_RATE}"
+        )));
+    }
+    Ok(())
+}
+
+/// Checks the parameters given by the user before projecting anything with them
+pub fn validate_parameters(
+    discount_rate: f64,
+    terminal_growth: f64,
+    projection_years: i32,
+) -> Result<(), AppError> {
+    if !(1..=MAX_PROJECTION_YEARS).contains(&projection_years) {
+        return Err(AppError::ValidationError(format!(
+            "projection_years must be between 1 and {MAX_PROJECTION_YEARS}"
+        )));
+    }
+    validate_rate("discount_rate", discount_rate)?;
+    validate_rate("terminal_growth", terminal_growth)?;
+    if discount_rate <= terminal_growth {
+        return Err(AppError::ValidationError(
+            "discount_rate must be greater than terminal_growth".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Two stage discounted cash flow: an explicit projection of the free cash flow
+/// followed by a Gordon growth terminal value
+pub struct DiscountedCashFlow {
+    pub base_free_cash_flow: f64,
+    pub shares_outstanding: f64,
+}
+
+impl DiscountedCashFlow {
+    pub fn projected_cash_flows(&self, parameters: &DcfParameters) -> Vec<f64> {
+        (1..=parameters.projection_years)
+            .map(|year| self.base_free_cash_flow * (1.0 + parameters.growth_rate).powi(year))
+            .collect()
+    }
+
+    pub fn terminal_value(&self, parameters: &DcfParameters) -> Option<f64> {
+        if parameters.discount_rate <= parameters.terminal_growth {
+            return None;
+        }
+        let last_cash_flow = self.base_free_cash_flow
+            * (1.0 + parameters.growth_rate).powi(parameters.projection_years);
+        Some(
+            last_cash_flow * (1.0 + parameters.terminal_growth)
+                / (parameters.discount_rate - parameters.terminal_growth),
+        )
+    }
+
+    pub fn equity_value(&self, parameters: &DcfParameters) -> Option<f64> {
+        let discount =
+            |cash_flow: f64, year: i32| cash_flow / (1.0 + parameters.discount_rate).powi(year);
+        let projected: f64 = self
+            .projected_cash_flows(parameters)
+            .into_iter()
+            .zip(1..)
+            .map(|(cash_flow, year)| discount(cash_flow, year))
+            .sum();
+        let terminal = discount(
+            self.terminal_value(parameters)?,
+            parameters.projection_years,
+        );
+        Some(projected + terminal)
+    }
+
+    pub fn intrinsic_value_per_share(&self, parameters: &DcfParameters) -> Option<f64> {
+        if self.shares_outstanding <= 0.0 {
+            return None;
+        }
+        Some(self.equity_value(parameters)? / self.shares_outstanding)
+    }
+
+    pub fn sensitivity(&self, parameters: &DcfParameters) -> SensitivityGrid {
+        let terminal_growths: Vec<f64> = TERMINAL_GROWTH_STEPS
+            .iter()
+            .map(|step| parameters.terminal_growth + step)
+            .collect();
+        let rows = DISCOUNT_RATE_STEPS
+            .iter()
+            .map(|step| {
+                let discount_rate = parameters.discount_rate + step;
+                let values = terminal_growths
+                    .iter()
+                    .map(|&terminal_growth| {
+                        // The steps can push the rates out of bounds
+                        validate_parameters(
+                            discount_rate,
+                            terminal_growth,
+                            parameters.projection_years,
+                        )
+                        .ok()?;
+                        self.intrinsic_value_per_share(&DcfParameters {
+                            discount_rate,
+                            terminal_growth,
+                            ..parameters.clone()
+                        })
+                    })
+                    .collect();
+                SensitivityRow {
+                    discount_rate,
+                    values,
+                }
+            })
+            .collect();
+        SensitivityGrid {
+            terminal_growths,
+            rows,
+        }
+    }
+}
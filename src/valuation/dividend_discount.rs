@@ -0,0 +1,113 @@
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    dcf::{validate_rate, DEFAULT_DISCOUNT_RATE},
+    models::{Figures, ValuationModel},
+};
+use crate::{
+    db::schema::{cashflow_statements, non_gaap_figures, periods, rentability_ratios},
+    fundamentals::{latest_diluted_shares, FISCAL_YEAR},
+    server::AppError,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct DividendDiscountParameters {
+    required_return: Option<f64>,
+    /// Defaults to the sustainable growth, retention ratio times return on equity
+    dividend_growth: Option<f64>,
+}
+
+/// Gordon growth model over the last fiscal year dividends
+pub struct DividendDiscount {
+    dividend_per_share: f64,
+    retention_ratio: Option<f64>,
+    return_on_equity: Option<f64>,
+    required_return: f64,
+    dividend_growth: Option<f64>,
+}
+
+impl ValuationModel for DividendDiscount {
+    const NAME: &'static str = "dividend_discount";
+    type Parameters = DividendDiscountParameters;
+
+    fn load(
+        company_id: i64,
+        parameters: DividendDiscountParameters,
+        conn: &mut PgConnection,
+    ) -> Result<Self, AppError> {
+        let required_return = parameters.required_return.unwrap_or(DEFAULT_DISCOUNT_RATE);
+        validate_rate("required_return", required_return)?;
+        if let Some(dividend_growth) = parameters.dividend_growth {
+            validate_rate("dividend_growth", dividend_growth)?;
+        }
+        let dividends_paid: f64 = cashflow_statements::table
+            .inner_join(periods::table)
+            .filter(cashflow_statements::company_id.eq(company_id))
+            .filter(cashflow_statements::is_ttm.eq(false))
+            .filter(periods::period.eq(FISCAL_YEAR))
+            .order(cashflow_statements::date.desc())
+            .select(cashflow_statements::dividends_paid)
+            .first(conn)
+            .optional()
+            .map_err(AppError::DatabaseQueryError)?
+            .ok_or(AppError::DoesNotExist)?;
+        let shares = latest_diluted_shares(company_id, conn)
+            .map_err(AppError::DatabaseQueryError)?
+            .ok_or(AppError::DoesNotExist)?;
+        let retention_ratio: Option<f64> = non_gaap_figures::table
+            .inner_join(periods::table)
+            .filter(non_gaap_figures::company_id.eq(company_id))
+            .filter(non_gaap_figures::is_ttm.eq(false))
+            .filter(periods::period.eq(FISCAL_YEAR))
+            .order(non_gaap_figures::date.desc())
+            .select(non_gaap_figures::retention_ratio)
+            .first(conn)
+            .optional()
+            .map_err(AppError::DatabaseQueryError)?;
+        let return_on_equity: Option<f64> = rentability_ratios::table
+            .inner_join(periods::table)
+            .filter(rentability_ratios::company_id.eq(company_id))
+            .filter(rentability_ratios::is_ttm.eq(false))
+            .filter(periods::period.eq(FISCAL_YEAR))
+            .order(rentability_ratios::date.desc())
+            .select(rentability_ratios::return_on_equity)
+            .first(conn)
+            .optional()
+            .map_err(AppError::DatabaseQueryError)?;
+
+        let sustainable_growth = retention_ratio
+            .zip(return_on_equity)
+            .map(|(b, roe)| b * roe);
+        Ok(Self {
+            // Dividends are reported as a cash outflow
+            dividend_per_share: if shares > 0.0 {
+                dividends_paid.abs() / shares
+            } else {
+                0.0
+            },
+            retention_ratio,
+            return_on_equity,
+            required_return,
+            dividend_growth: parameters.dividend_growth.or(sustainable_growth),
+        })
+    }
+
+    fn inputs(&self) -> Figures {
+        Figures::from([
+            ("dividend_per_share", Some(self.dividend_per_share)),
+            ("retention_ratio", self.retention_ratio),
+            ("return_on_equity", self.return_on_equity),
+            ("required_return", Some(self.required_return)),
+            ("dividend_growth", self.dividend_growth),
+        ])
+    }
+
+    fn outputs(&self) -> Figures {
+        let value = self.dividend_growth.and_then(|growth| {
+            (self.dividend_per_share > 0.0 && self.required_return > growth)
+                .then(|| self.dividend_per_share * (1.0 + growth) / (self.required_return - growth))
+        });
+        Figures::from([("intrinsic_value_per_share", value)])
+    }
+}
@@ -0,0 +1,113 @@
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    dcf::{validate_rate, DEFAULT_DISCOUNT_RATE},
+    models::{Figures, ValuationModel},
+};
+use crate::{
+    db::schema::{balance_sheet_statements, non_gaap_figures, periods},
+    fundamentals::{latest_diluted_shares, FISCAL_YEAR},
+    server::AppError,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct EarningsPowerParameters {
+    cost_of_capital: Option<f64>,
+}
+
+/// Greenwald's earnings power value, the current operating profit as a perpetuity
+/// without growth, adjusted by the net cash
+pub struct EarningsPowerValue {
+    net_operating_profit_after_tax: f64,
+    cash_and_short_term_investments: f64,
+    total_debt: f64,
+    shares_outstanding: f64,
+    cost_of_capital: f64,
+}
+
+impl EarningsPowerValue {
+    fn earnings_power(&self) -> Option<f64> {
+        (self.cost_of_capital > 0.0)
+            .then(|| self.net_operating_profit_after_tax / self.cost_of_capital)
+    }
+}
+
+impl ValuationModel for EarningsPowerValue {
+    const NAME: &'static str = "earnings_power";
+    type Parameters = EarningsPowerParameters;
+
+    fn load(
+        company_id: i64,
+        parameters: EarningsPowerParameters,
+        conn: &mut PgConnection,
+    ) -> Result<Self, AppError> {
+        let cost_of_capital = parameters.cost_of_capital.unwrap_or(DEFAULT_DISCOUNT_RATE);
+        validate_rate("cost_of_capital", cost_of_capital)?;
+        let net_operating_profit_after_tax: f64 = non_gaap_figures::table
+            .inner_join(periods::table)
+            .filter(non_gaap_figures::company_id.eq(company_id))
+            .filter(non_gaap_figures::is_ttm.eq(false))
+            .filter(periods::period.eq(FISCAL_YEAR))
+            .order(non_gaap_figures::date.desc())
+            .select(non_gaap_figures::net_operating_profit_after_tax)
+            .first(conn)
+            .optional()
+            .map_err(AppError::DatabaseQueryError)?
+            .ok_or(AppError::DoesNotExist)?;
+        let (cash_and_short_term_investments, total_debt) = balance_sheet_statements::table
+            .inner_join(periods::table)
+            .filter(balance_sheet_statements::company_id.eq(company_id))
+            .filter(balance_sheet_statements::is_ttm.eq(false))
+            .filter(periods::period.eq(FISCAL_YEAR))
+            .order(balance_sheet_statements::date.desc())
+            .select((
+                balance_sheet_statements::cash_and_short_term_investments,
+                balance_sheet_statements::total_debt,
+            ))
+            .first::<(f64, f64)>(conn)
+            .optional()
+            .map_err(AppError::DatabaseQueryError)?
+            .ok_or(AppError::DoesNotExist)?;
+        let shares_outstanding = latest_diluted_shares(company_id, conn)
+            .map_err(AppError::DatabaseQueryError)?
+            .ok_or(AppError::DoesNotExist)?;
+        Ok(Self {
+            net_operating_profit_after_tax,
+            cash_and_short_term_investments,
+            total_debt,
+            shares_outstanding,
+            cost_of_capital,
+        })
+    }
+
+    fn inputs(&self) -> Figures {
+        Figures::from([
+            (
+                "net_operating_profit_after_tax",
+                Some(self.net_operating_profit_after_tax),
+            ),
+            (
+                "cash_and_short_term_investments",
+                Some(self.cash_and_short_term_investments),
+            ),
+            ("total_debt", Some(self.total_debt)),
+            ("shares_outstanding", Some(self.shares_outstanding)),
+            ("cost_of_capital", Some(self.cost_of_capital)),
+        ])
+    }
+
+    fn outputs(&self) -> Figures {
+        let earnings_power = self.earnings_power();
+        let equity_value = earnings_power
+            .map(|value| value + self.cash_and_short_term_investments - self.total_debt);
+        let value = equity_value
+            .filter(|_| self.shares_outstanding > 0.0)
+            .map(|value| value / self.shares_outstanding);
+        Figures::from([
+            ("earnings_power_value", earnings_power),
+            ("equity_value", equity_value),
+            ("intrinsic_value_per_share", value),
+        ])
+    }
+}
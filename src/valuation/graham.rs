@@ -0,0 +1,62 @@
+use diesel::prelude::*;
+
+use super::models::{Figures, NoParameters, ValuationModel};
+use crate::{
+    db::schema::{per_share_values, periods},
+    fundamentals::FISCAL_YEAR,
+    server::AppError,
+};
+
+/// Benjamin Graham's ceiling of 15 times earnings and 1.5 times book value
+const GRAHAM_MULTIPLIER: f64 = 22.5;
+
+pub struct GrahamNumber {
+    earnings_per_share: f64,
+    book_value_per_share: f64,
+}
+
+impl ValuationModel for GrahamNumber {
+    const NAME: &'static str = "graham";
+    type Parameters = NoParameters;
+
+    fn load(
+        company_id: i64,
+        _parameters: NoParameters,
+        conn: &mut PgConnection,
+    ) -> Result<Self, AppError> {
+        let (earnings_per_share, book_value_per_share) = per_share_values::table
+            .inner_join(periods::table)
+            .filter(per_share_values::company_id.eq(company_id))
+            .filter(per_share_values::is_ttm.eq(false))
+            .filter(periods::period.eq(FISCAL_YEAR))
+            .order(per_share_values::date.desc())
+            .select((
+                per_share_values::earnings_per_share,
+                per_share_values::book_value_per_share,
+            ))
+            .first::<(f64, f64)>(conn)
+            .optional()
+            .map_err(AppError::DatabaseQueryError)?
+            .ok_or(AppError::DoesNotExist)?;
+        Ok(Self {
+            earnings_per_share,
+            book_value_per_share,
+        })
+    }
+
+    fn inputs(&self) -> Figures {
+        Figures::from([
+            ("earnings_per_share", Some(self.earnings_per_share)),
+            ("book_value_per_share", Some(self.book_value_per_share)),
+            ("multiplier", Some(GRAHAM_MULTIPLIER)),
+        ])
+    }
+
+    fn outputs(&self) -> Figures {
+        // Meaningless for companies with losses or negative equity
+        let value = (self.earnings_per_share > 0.0 && self.book_value_per_share > 0.0).then(|| {
+            (GRAHAM_MULTIPLIER * self.earnings_per_share * self.book_value_per_share).sqrt()
+        });
+        Figures::from([("intrinsic_value_per_share", value)])
+    }
+}
@@ -0,0 +1,364 @@
+use axum::{
+    extract::{Path, Query, RawQuery},
+    routing::get,
+    Extension, Json, Router,
+};
+use chrono::NaiveDate;
+use diesel::{prelude::*, upsert::excluded};
+use serde::{Deserialize, Serialize};
+use utoipa::{self, IntoParams, OpenApi, ToResponse, ToSchema};
+
+use super::{
+    dcf::{
+        validate_parameters, validate_rate, DcfParameters, DiscountedCashFlow, SensitivityGrid,
+        SensitivityRow, DEFAULT_DISCOUNT_RATE, DEFAULT_PROJECTION_YEARS, DEFAULT_TERMINAL_GROWTH,
+        MAX_DERIVED_GROWTH, MIN_DERIVED_GROWTH,
+    },
+    models::{evaluate_model, Valuation, MODELS},
+};
+use crate::{
+    companies::get_company_id,
+    db::schema::dcf_parameters,
+    fundamentals::{free_cash_flow_growth_history, free_cash_flow_history, latest_diluted_shares},
+    server::{AppError, AppResult, JWTUserRequest},
+    AppState,
+};
+
+const HISTORY_YEARS: i64 = 5;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_dcf, get_dcf_parameters, save_dcf_parameters, list_valuations, get_valuation),
+    components(schemas(DcfResponse, DcfParameters, SavedDcfParameters, SensitivityGrid, SensitivityRow, Valuation),
+    responses(DcfResponse, SavedDcfParameters, Valuation)),
+    security(("token_jwt" = []))
+)]
+pub struct ApiDoc;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/companies/:ticker/valuation", get(list_valuations))
+        .route(
+            "/companies/:ticker/valuation/models/:model",
+            get(get_valuation),
+        )
+        .route("/companies/:ticker/valuation/dcf", get(get_dcf))
+        .route(
+            "/companies/:ticker/valuation/dcf/parameters",
+            get(get_dcf_parameters).put(save_dcf_parameters),
+        )
+        .with_state(state)
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+struct DcfQuery {
+    discount_rate: Option<f64>,
+    terminal_growth: Option<f64>,
+    growth_rate: Option<f64>,
+    projection_years: Option<i32>,
+}
+
+#[derive(
+    Debug,
+    Queryable,
+    Selectable,
+    Insertable,
+    AsChangeset,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    ToResponse,
+)]
+#[diesel(table_name = dcf_parameters)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(treat_none_as_null = true)]
+struct SavedDcfParameters {
+    discount_rate: f64,
+    terminal_growth: f64,
+    /// Derived from the company's free cash flow growth when empty
+    growth_rate: Option<f64>,
+    projection_years: i32,
+}
+
+impl SavedDcfParameters {
+    fn validate(&self) -> Result<(), AppError> {
+        if let Some(growth_rate) = self.growth_rate {
+            validate_rate("growth_rate", growth_rate)?;
+        }
+        validate_parameters(
+            self.discount_rate,
+            self.terminal_growth,
+            self.projection_years,
+        )
+    }
+}
+
+impl Default for SavedDcfParameters {
+    fn default() -> Self {
+        Self {
+            discount_rate: DEFAULT_DISCOUNT_RATE,
+            terminal_growth: DEFAULT_TERMINAL_GROWTH,
+            growth_rate: None,
+            projection_years: DEFAULT_PROJECTION_YEARS,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+struct DcfResponse {
+    ticker: String,
+    parameters: DcfParameters,
+    /// Whether the growth rate was derived from the company's history
+    derived_growth: bool,
+    base_free_cash_flow: f64,
+    base_free_cash_flow_date: NaiveDate,
+    shares_outstanding: f64,
+    projected_cash_flows: Vec<f64>,
+    terminal_value: Option<f64>,
+    equity_value: Option<f64>,
+    intrinsic_value_per_share: Option<f64>,
+    sensitivity: SensitivityGrid,
+}
+
+fn load_saved_parameters(
+    user_id: i64,
+    company_id: i64,
+    conn: &mut PgConnection,
+) -> Result<Option<SavedDcfParameters>, AppError> {
+    dcf_parameters::table
+        .filter(dcf_parameters::user_id.eq(user_id))
+        .filter(dcf_parameters::company_id.eq(company_id))
+        .select(SavedDcfParameters::as_select())
+        .first(conn)
+        .optional()
+        .map_err(AppError::DatabaseQueryError)
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/valuation/dcf",
+    params(("ticker", description = "Company's ticker"), DcfQuery),
+    responses(
+        (status = 200, body = DcfResponse, description = "Intrinsic value of the company from a discounted cash flow"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn get_dcf(
+    Path(ticker): Path<String>,
+    Query(query_params): Query<DcfQuery>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<DcfResponse> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            let saved =
+                load_saved_parameters(current_user.id, company_id, conn)?.unwrap_or_default();
+
+            let (base_free_cash_flow_date, base_free_cash_flow) =
+                free_cash_flow_history(company_id, 1, conn)
+                    .map_err(AppError::DatabaseQueryError)?
+                    .into_iter()
+                    .next()
+                    .ok_or(AppError::DoesNotExist)?;
+            let shares_outstanding = latest_diluted_shares(company_id, conn)
+                .map_err(AppError::DatabaseQueryError)?
+                .ok_or(AppError::DoesNotExist)?;
+
+            let requested_growth = query_params.growth_rate.or(saved.growth_rate);
+            let growth_rate = match requested_growth {
+                Some(growth_rate) => growth_rate,
+                None => {
+                    let history: Vec<f64> =
+                        free_cash_flow_growth_history(company_id, HISTORY_YEARS, conn)
+                            .map_err(AppError::DatabaseQueryError)?
+                            .into_iter()
+                            .filter(|growth| growth.is_finite())
+                            .collect();
+                    if history.is_empty() {
+                        return Err(AppError::ValidationError(
+                            "The company has no growth history, provide a growth_rate".to_owned(),
+                        ));
+                    }
+                    (history.iter().sum::<f64>() / history.len() as f64)
+                        .clamp(MIN_DERIVED_GROWTH, MAX_DERIVED_GROWTH)
+                }
+            };
+
+            let resolved = SavedDcfParameters {
+                discount_rate: query_params.discount_rate.unwrap_or(saved.discount_rate),
+                terminal_growth: query_params
+                    .terminal_growth
+                    .unwrap_or(saved.terminal_growth),
+                growth_rate: Some(growth_rate),
+                projection_years: query_params
+                    .projection_years
+                    .unwrap_or(saved.projection_years),
+            };
+            resolved.validate()?;
+            let parameters = DcfParameters {
+                discount_rate: resolved.discount_rate,
+                terminal_growth: resolved.terminal_growth,
+                growth_rate,
+                projection_years: resolved.projection_years,
+            };
+
+            let model = DiscountedCashFlow {
+                base_free_cash_flow,
+                shares_outstanding,
+            };
+            Ok(Json(DcfResponse {
+                ticker,
+                derived_growth: requested_growth.is_none(),
+                base_free_cash_flow,
+                base_free_cash_flow_date,
+                shares_outstanding,
+                projected_cash_flows: model.projected_cash_flows(&parameters),
+                terminal_value: model.terminal_value(&parameters),
+                equity_value: model.equity_value(&parameters),
+                intrinsic_value_per_share: model.intrinsic_value_per_share(&parameters),
+                sensitivity: model.sensitivity(&parameters),
+                parameters,
+            }))
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/valuation/dcf/parameters",
+    params(("ticker", description = "Company's ticker")),
+    responses(
+        (status = 200, body = SavedDcfParameters, description = "The user's DCF parameters for the company, or the defaults"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn get_dcf_parameters(
+    Path(ticker): Path<String>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+) -> AppResult<SavedDcfParameters> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            Ok(Json(
+                load_saved_parameters(current_user.id, company_id, conn)?.unwrap_or_default(),
+            ))
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+}
+
+#[utoipa::path(
+    put,
+    path = "companies/{ticker}/valuation/dcf/parameters",
+    params(("ticker", description = "Company's ticker")),
+    request_body = SavedDcfParameters,
+    responses(
+        (status = 200, body = SavedDcfParameters, description = "Save the user's DCF parameters for the company"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn save_dcf_parameters(
+    Path(ticker): Path<String>,
+    state: AppState,
+    Extension(current_user): Extension<JWTUserRequest>,
+    Json(parameters): Json<SavedDcfParameters>,
+) -> AppResult<SavedDcfParameters> {
+    parameters.validate()?;
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            diesel::insert_into(dcf_parameters::table)
+                .values((
+                    dcf_parameters::user_id.eq(current_user.id),
+                    dcf_parameters::company_id.eq(company_id),
+                    &parameters,
+                ))
+                .on_conflict((dcf_parameters::user_id, dcf_parameters::company_id))
+                .do_update()
+                .set((
+                    dcf_parameters::discount_rate.eq(excluded(dcf_parameters::discount_rate)),
+                    dcf_parameters::terminal_growth.eq(excluded(dcf_parameters::terminal_growth)),
+                    dcf_parameters::growth_rate.eq(excluded(dcf_parameters::growth_rate)),
+                    dcf_parameters::projection_years.eq(excluded(dcf_parameters::projection_years)),
+                    dcf_parameters::updated_at.eq(diesel::dsl::now),
+                ))
+                .returning(SavedDcfParameters::as_returning())
+                .get_result(conn)
+                .map_err(AppError::DatabaseQueryError)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/valuation",
+    params(("ticker", description = "Company's ticker")),
+    responses(
+        (status = 200, body = Vec<Valuation>, description = "Every valuation model with its default parameters, models lacking data are left out"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn list_valuations(Path(ticker): Path<String>, state: AppState) -> AppResult<Vec<Valuation>> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            let mut valuations = Vec::with_capacity(MODELS.len());
+            for model in MODELS {
+                match evaluate_model(model, company_id, "", conn) {
+                    Ok(valuation) => valuations.push(valuation),
+                    Err(AppError::DoesNotExist) => continue,
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(Json(valuations))
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+}
+
+#[utoipa::path(
+    get,
+    path = "companies/{ticker}/valuation/models/{model}",
+    params(
+        ("ticker", description = "Company's ticker"),
+        ("model", description = "One of graham, dividend_discount, earnings_power or reverse_dcf, the query string overrides the model's parameters"),
+    ),
+    responses(
+        (status = 200, body = Valuation, description = "A valuation model with the figures it was computed from"),
+        (status = "4XX", body = ErrorMessage, description = "Client error"),
+        (status = "5XX", body = ErrorMessage, description = "Server error"),
+    )
+)]
+async fn get_valuation(
+    Path((ticker, model)): Path<(String, String)>,
+    RawQuery(query): RawQuery,
+    state: AppState,
+) -> AppResult<Valuation> {
+    state
+        .db_write()
+        .await?
+        .interact(move |conn| {
+            let company_id = get_company_id(&ticker, conn)?;
+            evaluate_model(&model, company_id, &query.unwrap_or_default(), conn)
+        })
+        .await
+        .map_err(AppError::DatabaseConnectionInteractError)?
+        .map(Json)
+}
@@ -0,0 +1,9 @@
+mod dcf;
+mod dividend_discount;
+mod earnings_power;
+mod graham;
+mod handlers;
+mod models;
+mod reverse_dcf;
+
+pub use handlers::{routes, ApiDoc};
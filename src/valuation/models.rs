@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+use diesel::PgConnection;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use utoipa::{ToResponse, ToSchema};
+
+use super::{
+    dividend_discount::DividendDiscount, earnings_power::EarningsPowerValue, graham::GrahamNumber,
+    reverse_dcf::ReverseDiscountedCashFlow,
+};
+use crate::server::AppError;
+
+/// Named figures a model was computed from or produced
+pub type Figures = BTreeMap<&'static str, Option<f64>>;
+
+/// Implement this trait and add the model to [`MODELS`] and [`evaluate_model`]
+/// to expose a new valuation model
+pub trait ValuationModel: Sized {
+    const NAME: &'static str;
+    /// Overridable through the query string
+    type Parameters: DeserializeOwned;
+
+    fn load(
+        company_id: i64,
+        parameters: Self::Parameters,
+        conn: &mut PgConnection,
+    ) -> Result<Self, AppError>;
+    fn inputs(&self) -> Figures;
+    fn outputs(&self) -> Figures;
+}
+
+/// For models without overridable parameters
+#[derive(Debug, Deserialize)]
+pub struct NoParameters {}
+
+#[derive(Debug, Serialize, ToSchema, ToResponse)]
+pub struct Valuation {
+    model: &'static str,
+    #[schema(value_type = Object)]
+    inputs: Figures,
+    #[schema(value_type = Object)]
+    outputs: Figures,
+}
+
+pub const MODELS: [&str; 4] = [
+    GrahamNumber::NAME,
+    DividendDiscount::NAME,
+    EarningsPowerValue::NAME,
+    ReverseDiscountedCashFlow::NAME,
+];
+
+fn evaluate<M: ValuationModel>(
+    company_id: i64,
+    query: &str,
+    conn: &mut PgConnection,
+) -> Result<Valuation, AppError> {
+    let parameters = serde_urlencoded::from_str::<M::Parameters>(query)
+        .map_err(|err| AppError::ValidationError(err.to_string()))?;
+    let model = M::load(company_id, parameters, conn)?;
+    Ok(Valuation {
+        model: M::NAME,
+        inputs: model.inputs(),
+        outputs: model.outputs(),
+    })
+}
+
+pub fn evaluate_model(
+    name: &str,
+    company_id: i64,
+    query: &str,
+    conn: &mut PgConnection,
+) -> Result<Valuation, AppError> {
+    match name {
+        GrahamNumber::NAME => evaluate::<GrahamNumber>(company_id, query, conn),
+        DividendDiscount::NAME => evaluate::<DividendDiscount>(company_id, query, conn),
+        EarningsPowerValue::NAME => evaluate::<EarningsPowerValue>(company_id, query, conn),
+        ReverseDiscountedCashFlow::NAME => {
+            evaluate::<ReverseDiscountedCashFlow>(company_id, query, conn)
+        }
+        _ => Err(AppError::DoesNotExist),
+    }
+}
@@ -0,0 +1,136 @@
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    dcf::{
+        validate_parameters, DcfParameters, DiscountedCashFlow, DEFAULT_DISCOUNT_RATE,
+        DEFAULT_PROJECTION_YEARS, DEFAULT_TERMINAL_GROWTH,
+    },
+    models::{Figures, ValuationModel},
+};
+use crate::{
+    db::schema::{non_gaap_figures, periods},
+    fundamentals::{free_cash_flow_history, latest_diluted_shares, FISCAL_YEAR},
+    server::AppError,
+};
+
+const MIN_GROWTH: f64 = -0.5;
+const MAX_GROWTH: f64 = 1.0;
+const ITERATIONS: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct ReverseDcfParameters {
+    discount_rate: Option<f64>,
+    terminal_growth: Option<f64>,
+    projection_years: Option<i32>,
+}
+
+/// Growth of the free cash flow the market is pricing in
+pub struct ReverseDiscountedCashFlow {
+    market_capitalization: f64,
+    model: DiscountedCashFlow,
+    parameters: DcfParameters,
+}
+
+impl ReverseDiscountedCashFlow {
+    /// Bisection over the growth rate, the equity value grows with it as long as the
+    /// base free cash flow is positive
+    fn implied_growth(&self) -> Option<f64> {
+        if self.model.base_free_cash_flow <= 0.0 || self.market_capitalization <= 0.0 {
+            return None;
+        }
+        let value_at = |growth_rate: f64| {
+            self.model.equity_value(&DcfParameters {
+                growth_rate,
+                ..self.parameters.clone()
+            })
+        };
+        let (mut low, mut high) = (MIN_GROWTH, MAX_GROWTH);
+        if value_at(low)? > self.market_capitalization
+            || value_at(high)? < self.market_capitalization
+        {
+            return None;
+        }
+        for _ in 0..ITERATIONS {
+            let middle = (low + high) / 2.0;
+            if value_at(middle)? < self.market_capitalization {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        Some((low + high) / 2.0)
+    }
+}
+
+impl ValuationModel for ReverseDiscountedCashFlow {
+    const NAME: &'static str = "reverse_dcf";
+    type Parameters = ReverseDcfParameters;
+
+    fn load(
+        company_id: i64,
+        parameters: ReverseDcfParameters,
+        conn: &mut PgConnection,
+    ) -> Result<Self, AppError> {
+        let parameters = DcfParameters {
+            discount_rate: parameters.discount_rate.unwrap_or(DEFAULT_DISCOUNT_RATE),
+            terminal_growth: parameters
+                .terminal_growth
+                .unwrap_or(DEFAULT_TERMINAL_GROWTH),
+            growth_rate: 0.0,
+            projection_years: parameters
+                .projection_years
+                .unwrap_or(DEFAULT_PROJECTION_YEARS),
+        };
+        validate_parameters(
+            parameters.discount_rate,
+            parameters.terminal_growth,
+            parameters.projection_years,
+        )?;
+        let market_capitalization: f64 = non_gaap_figures::table
+            .inner_join(periods::table)
+            .filter(non_gaap_figures::company_id.eq(company_id))
+            .filter(non_gaap_figures::is_ttm.eq(false))
+            .filter(periods::period.eq(FISCAL_YEAR))
+            .order(non_gaap_figures::date.desc())
+            .select(non_gaap_figures::market_capitalization)
+            .first(conn)
+            .optional()
+            .map_err(AppError::DatabaseQueryError)?
+            .ok_or(AppError::DoesNotExist)?;
+        let (_, base_free_cash_flow) = free_cash_flow_history(company_id, 1, conn)
+            .map_err(AppError::DatabaseQueryError)?
+            .into_iter()
+            .next()
+            .ok_or(AppError::DoesNotExist)?;
+        let shares_outstanding = latest_diluted_shares(company_id, conn)
+            .map_err(AppError::DatabaseQueryError)?
+            .ok_or(AppError::DoesNotExist)?;
+        Ok(Self {
+            market_capitalization,
+            model: DiscountedCashFlow {
+                base_free_cash_flow,
+                shares_outstanding,
+            },
+            parameters,
+        })
+    }
+
+    fn inputs(&self) -> Figures {
+        Figures::from([
+            ("market_capitalization", Some(self.market_capitalization)),
+            ("base_free_cash_flow", Some(self.model.base_free_cash_flow)),
+            ("shares_outstanding", Some(self.model.shares_outstanding)),
+            ("discount_rate", Some(self.parameters.discount_rate)),
+            ("terminal_growth", Some(self.parameters.terminal_growth)),
+            (
+                "projection_years",
+                Some(f64::from(self.parameters.projection_years)),
+            ),
+        ])
+    }
+
+    fn outputs(&self) -> Figures {
+        Figures::from([("implied_growth", self.implied_growth())])
+    }
+}